@@ -383,6 +383,7 @@ impl<'a> Builder<'a> {
                 test::RunFail,
                 test::RunPassValgrind,
                 test::MirOpt,
+                test::Yk,
                 test::Codegen,
                 test::CodegenUnits,
                 test::Incremental,
@@ -792,6 +792,12 @@ default_test!(MirOpt {
     suite: "mir-opt"
 });
 
+default_test!(Yk {
+    path: "src/test/yk",
+    mode: "yk",
+    suite: "yk"
+});
+
 default_test!(Codegen {
     path: "src/test/codegen",
     mode: "codegen",
@@ -1224,6 +1230,10 @@ impl Step for Compiletest {
             cmd.env("PROFILER_SUPPORT", "1");
         }
 
+        if builder.config.rustc_parallel_queries {
+            cmd.arg("--parallel-queries");
+        }
+
         cmd.env("RUST_TEST_TMPDIR", builder.out.join("tmp"));
 
         cmd.arg("--adb-path").arg("adb");
@@ -665,6 +665,15 @@ extern "rust-intrinsic" {
     /// Any use other than with `if` statements will probably not have an effect.
     pub fn unlikely(b: bool) -> bool;
 
+    /// Hints to the Yorick tracer that `val` should be treated as a constant
+    /// of the trace, e.g. an interpreter's opcode. Returns `val` unchanged.
+    ///
+    /// No code is generated for this intrinsic. Each call site is recorded in
+    /// the `.yk_promote` section, so that the runtime can guard on the value
+    /// and specialise the trace for it.
+    #[cfg(not(stage0))]
+    pub fn yk_promote<T>(val: T) -> T;
+
     /// Executes a breakpoint trap, for inspection by a debugger.
     pub fn breakpoint();
 
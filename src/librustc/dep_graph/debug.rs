@@ -39,10 +39,15 @@ impl DepNodeFilter {
 
     /// Tests whether `node` meets the filter, returning true if so.
     pub fn test(&self, node: &DepNode) -> bool {
-        let debug_str = format!("{:?}", node);
+        self.test_label(&format!("{:?}", node))
+    }
+
+    /// Tests whether the node with the `Debug` output `label` meets the
+    /// filter, returning true if so.
+    pub fn test_label(&self, label: &str) -> bool {
         self.text.split('&')
                  .map(|s| s.trim())
-                 .all(|f| debug_str.contains(f))
+                 .all(|f| label.contains(f))
     }
 }
 
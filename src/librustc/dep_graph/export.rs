@@ -0,0 +1,185 @@
+// Copyright 2018 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A structured export of the dependency graph of a session, written as JSON
+//! by `-Z dep-graph-export`, and a small query language over it, used by
+//! `-Z dep-graph-query`, for finding out why an incremental rebuild did the
+//! work it did.
+//!
+//! A query is a command followed by a dep-node filter, in the format
+//! described on `DepNodeFilter` (e.g. `OptimizedMir & foo`):
+//!
+//! * `why F` explains why each node matching `F` was executed, following the
+//!   dependencies which caused that back to a changed input or a new node.
+//! * `red F`, `green F` and `new F` list the matching nodes of that color.
+//! * `executed F` lists the matching nodes whose task was executed.
+//! * `deps F` and `rdeps F` list the immediate dependencies and dependents
+//!   of the matching nodes.
+
+use rustc_data_structures::fx::{FxHashMap, FxHashSet};
+use super::debug::DepNodeFilter;
+
+#[derive(RustcEncodable, RustcDecodable)]
+pub struct ExportedDepGraph {
+    pub nodes: Vec<ExportedDepNode>,
+}
+
+#[derive(RustcEncodable, RustcDecodable)]
+pub struct ExportedDepNode {
+    /// The `DepKind` of the node, e.g. `OptimizedMir`.
+    pub kind: String,
+    /// The node as `-Z dump-dep-graph` prints it, e.g. `OptimizedMir(foo[0]::bar[0])`.
+    pub label: String,
+    /// Whether the node is an input, such as `Hir` or `HirBody`.
+    pub input: bool,
+    /// The fingerprint of the node's result in this session.
+    pub fingerprint: String,
+    /// The fingerprint of the node's result in the previous session.
+    pub prev_fingerprint: Option<String>,
+    /// `green` or `red`, for whether the node's result is unchanged since the
+    /// previous session, `new` if the node isn't in the previous session, or
+    /// `unknown` if it was never given a color.
+    pub color: String,
+    /// Why the node's task was executed, or `None` if it was marked green, or
+    /// is an input. One of `dependency-changed`, `dependency-not-forced`,
+    /// `dependency-deleted`, `new`, `eval-always` or `anonymous`.
+    pub reason: Option<String>,
+    /// The dependency named by a `dependency-*` reason.
+    pub reason_dependency: Option<String>,
+    /// The indices in `nodes` of the nodes this node read.
+    pub dependencies: Vec<usize>,
+}
+
+impl ExportedDepNode {
+    fn was_executed(&self) -> bool {
+        self.reason.is_some()
+    }
+
+    fn describe(&self) -> String {
+        let dependency = self.reason_dependency.as_ref().map(|s| &s[..]).unwrap_or("");
+        let description = match self.reason.as_ref().map(|s| &s[..]) {
+            Some("dependency-changed") => {
+                format!("executed because `{}` changed", dependency)
+            }
+            Some("dependency-not-forced") => {
+                format!("executed because `{}` could not be marked green or forced",
+                        dependency)
+            }
+            Some("dependency-deleted") => {
+                format!("executed because `{}` no longer exists", dependency)
+            }
+            Some("new") => "executed because it is new in this session".to_string(),
+            Some("eval-always") => "executed because it is always evaluated".to_string(),
+            Some("anonymous") => "executed because it is anonymous".to_string(),
+            Some(reason) => format!("executed: {}", reason),
+            None if self.input && self.color == "red" => "an input, which changed".to_string(),
+            None if self.input => "an input".to_string(),
+            None => "marked green".to_string(),
+        };
+        format!("{} ({}): {}", self.label, self.color, description)
+    }
+}
+
+impl ExportedDepGraph {
+    /// Runs `query`, in the language described in this module's
+    /// documentation, returning a line of output for each result.
+    pub fn query(&self, query: &str) -> Result<Vec<String>, String> {
+        let query = query.trim();
+        let (command, filter) = match query.find(char::is_whitespace) {
+            Some(i) => (&query[..i], DepNodeFilter::new(&query[i..])),
+            None => (query, DepNodeFilter::new("")),
+        };
+        let matching = self.nodes
+                           .iter()
+                           .enumerate()
+                           .filter(|&(_, node)| filter.test_label(&node.label));
+
+        let mut output = Vec::new();
+        match command {
+            "why" => {
+                let by_label: FxHashMap<&str, usize> = self.nodes
+                                                           .iter()
+                                                           .enumerate()
+                                                           .map(|(i, node)| (&node.label[..], i))
+                                                           .collect();
+                for (index, node) in matching {
+                    if node.was_executed() {
+                        self.explain(index, &by_label, &mut output);
+                    } else {
+                        output.push(node.describe());
+                    }
+                }
+            }
+            "red" | "green" | "new" => {
+                output.extend(matching.filter(|&(_, node)| node.color == command)
+                                      .map(|(_, node)| node.describe()));
+            }
+            "executed" => {
+                output.extend(matching.filter(|&(_, node)| node.was_executed())
+                                      .map(|(_, node)| node.describe()));
+            }
+            "deps" => {
+                for (_, node) in matching {
+                    output.push(node.describe());
+                    for &dep in &node.dependencies {
+                        output.push(format!("  {}", self.nodes[dep].describe()));
+                    }
+                }
+            }
+            "rdeps" => {
+                let mut dependents: FxHashMap<usize, Vec<usize>> = FxHashMap();
+                for (index, node) in self.nodes.iter().enumerate() {
+                    for &dep in &node.dependencies {
+                        dependents.entry(dep).or_default().push(index);
+                    }
+                }
+                for (index, node) in matching {
+                    output.push(node.describe());
+                    for &dependent in dependents.get(&index).into_iter().flatten() {
+                        output.push(format!("  {}", self.nodes[dependent].describe()));
+                    }
+                }
+            }
+            _ => {
+                return Err(format!("unknown dep-graph query command `{}`: expected one of \
+                                    `why`, `red`, `green`, `new`, `executed`, `deps` or \
+                                    `rdeps`", command));
+            }
+        }
+        Ok(output)
+    }
+
+    /// Explains why the node at `index` was executed, following the chain of
+    /// dependencies which caused that for as long as they are in the graph.
+    fn explain(&self,
+               index: usize,
+               by_label: &FxHashMap<&str, usize>,
+               output: &mut Vec<String>) {
+        let mut seen = FxHashSet();
+        let mut next = Some(index);
+        let mut indent = String::new();
+        while let Some(index) = next.take() {
+            if !seen.insert(index) {
+                break
+            }
+            let node = &self.nodes[index];
+            output.push(format!("{}{}", indent, node.describe()));
+            indent.push_str("  ");
+
+            if let Some(ref dependency) = node.reason_dependency {
+                next = by_label.get(&dependency[..]).cloned();
+                if next.is_none() {
+                    output.push(format!("{}{}: not in this session's graph",
+                                        indent, dependency));
+                }
+            }
+        }
+    }
+}
@@ -23,6 +23,7 @@ use ich::{StableHashingContext, StableHashingContextProvider, Fingerprint};
 
 use super::debug::EdgeFilter;
 use super::dep_node::{DepNode, DepKind, WorkProductId};
+use super::export::{ExportedDepGraph, ExportedDepNode};
 use super::query::DepGraphQuery;
 use super::safe::DepGraphSafe;
 use super::serialized::{SerializedDepGraph, SerializedDepNodeIndex};
@@ -62,6 +63,19 @@ impl DepNodeColor {
     }
 }
 
+/// Why a node that existed in the previous session could not be marked green,
+/// so that its task had to be executed again. These are only recorded for
+/// `-Z dep-graph-export` and `-Z dep-graph-query`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ExecutionReason {
+    /// The result of this dependency changed.
+    DependencyChanged(DepNode),
+    /// This dependency could neither be marked green nor be forced.
+    DependencyNotForced(DepNode),
+    /// This dependency is an input which no longer exists.
+    DependencyDeleted(DepNode),
+}
+
 struct DepGraphData {
     /// The new encoding of the dependency graph, optimized for red/green
     /// tracking. The `current` field is the dependency graph of only the
@@ -85,6 +99,10 @@ struct DepGraphData {
 
     // Used for testing, only populated when -Zquery-dep-graph is specified.
     loaded_from_cache: Lock<FxHashMap<DepNodeIndex, bool>>,
+
+    // Why `try_mark_green` failed for each node it failed for. Only populated
+    // when -Zdep-graph-export or -Zdep-graph-query is specified.
+    execution_reasons: Lock<FxHashMap<DepNode, ExecutionReason>>,
 }
 
 impl DepGraph {
@@ -106,6 +124,7 @@ impl DepGraph {
                 previous: prev_graph,
                 colors: Lock::new(DepNodeColorMap::new(prev_graph_node_count)),
                 loaded_from_cache: Lock::new(FxHashMap()),
+                execution_reasons: Lock::new(FxHashMap()),
             })),
             fingerprints: Lrc::new(Lock::new(fingerprints)),
         }
@@ -617,6 +636,9 @@ impl DepGraph {
                             immediately red",
                             dep_node,
                             data.previous.index_to_node(dep_dep_node_index));
+                    let dep_dep_node = data.previous.index_to_node(dep_dep_node_index);
+                    self.record_execution_reason(tcx, dep_node,
+                        ExecutionReason::DependencyChanged(dep_dep_node));
                     return None
                 }
                 None => {
@@ -643,6 +665,8 @@ impl DepGraph {
                                 if dep_node.extract_def_id(tcx).is_none() {
                                     // If the node does not exist anymore, we
                                     // just fail to mark green.
+                                    self.record_execution_reason(tcx, dep_node,
+                                        ExecutionReason::DependencyDeleted(*dep_dep_node));
                                     return None
                                 } else {
                                     // If the node does exist, it should have
@@ -677,6 +701,8 @@ impl DepGraph {
                                         dependency {:?} was red after forcing",
                                        dep_node,
                                        dep_dep_node);
+                                self.record_execution_reason(tcx, dep_node,
+                                    ExecutionReason::DependencyChanged(*dep_dep_node));
                                 return None
                             }
                             None => {
@@ -695,6 +721,8 @@ impl DepGraph {
                         // The DepNode could not be forced.
                         debug!("try_mark_green({:?}) - END - dependency {:?} \
                                 could not be forced", dep_node, dep_dep_node);
+                        self.record_execution_reason(tcx, dep_node,
+                            ExecutionReason::DependencyNotForced(*dep_dep_node));
                         return None
                     }
                 }
@@ -778,6 +806,98 @@ impl DepGraph {
         Some(dep_node_index)
     }
 
+    fn record_execution_reason(&self,
+                               tcx: TyCtxt<'_, '_, '_>,
+                               dep_node: &DepNode,
+                               reason: ExecutionReason) {
+        let opts = &tcx.sess.opts.debugging_opts;
+        if opts.dep_graph_export.is_some() || opts.dep_graph_query.is_some() {
+            self.data
+                .as_ref()
+                .unwrap()
+                .execution_reasons
+                .borrow_mut()
+                .insert(*dep_node, reason);
+        }
+    }
+
+    /// Why the task of `dep_node` was executed in this session rather than the
+    /// node being marked green, if that was because of one of its dependencies.
+    pub fn execution_reason(&self, dep_node: &DepNode) -> Option<ExecutionReason> {
+        self.data.as_ref()?.execution_reasons.borrow().get(dep_node).cloned()
+    }
+
+    /// Exports the current dependency graph, together with the colors of its
+    /// nodes and why they were executed, as described in the `export` module.
+    ///
+    /// This formats dep-nodes with their `Debug` implementation, and so should
+    /// be called with a `TyCtxt` available for def-paths to be printed.
+    pub fn export(&self) -> ExportedDepGraph {
+        let data = self.data.as_ref().unwrap();
+        let current = data.current.borrow();
+        let colors = data.colors.borrow();
+        let fingerprints = self.fingerprints.borrow();
+        let reasons = data.execution_reasons.borrow();
+
+        let nodes = current.nodes.iter_enumerated().map(|(index, dep_node)| {
+            let prev_index = data.previous.node_to_index_opt(dep_node);
+            let color = prev_index.and_then(|prev_index| colors.get(prev_index));
+            let (color, marked_green) = match (prev_index, color) {
+                (None, _) => ("new", false),
+                (Some(_), Some(DepNodeColor::Green(_))) => {
+                    // A green node was either marked green, or executed
+                    // and found to have an unchanged result, as happens
+                    // when `try_mark_green` failed for it or it's always
+                    // evaluated.
+                    let executed = reasons.contains_key(dep_node) ||
+                                   dep_node.kind.is_eval_always();
+                    ("green", !executed)
+                }
+                (Some(_), Some(DepNodeColor::Red)) => ("red", false),
+                (Some(_), None) => ("unknown", false),
+            };
+
+            let (reason, reason_dependency) = if marked_green || dep_node.kind.is_input() {
+                (None, None)
+            } else {
+                match reasons.get(dep_node) {
+                    Some(&ExecutionReason::DependencyChanged(dep)) => {
+                        (Some("dependency-changed"), Some(format!("{:?}", dep)))
+                    }
+                    Some(&ExecutionReason::DependencyNotForced(dep)) => {
+                        (Some("dependency-not-forced"), Some(format!("{:?}", dep)))
+                    }
+                    Some(&ExecutionReason::DependencyDeleted(dep)) => {
+                        (Some("dependency-deleted"), Some(format!("{:?}", dep)))
+                    }
+                    None if prev_index.is_none() => (Some("new"), None),
+                    None if dep_node.kind.is_eval_always() => (Some("eval-always"), None),
+                    None if dep_node.kind.is_anon() => (Some("anonymous"), None),
+                    None => (None, None),
+                }
+            };
+            let fingerprint = fingerprints.get(index)
+                                          .cloned()
+                                          .unwrap_or(Fingerprint::ZERO);
+
+            ExportedDepNode {
+                kind: format!("{:?}", dep_node.kind),
+                label: format!("{:?}", dep_node),
+                input: dep_node.kind.is_input(),
+                fingerprint: fingerprint.to_hex(),
+                prev_fingerprint: prev_index.map(|prev_index| {
+                    data.previous.fingerprint_by_index(prev_index).to_hex()
+                }),
+                color: color.to_string(),
+                reason: reason.map(|reason| reason.to_string()),
+                reason_dependency,
+                dependencies: current.edges[index].iter().map(|dep| dep.index()).collect(),
+            }
+        }).collect();
+
+        ExportedDepGraph { nodes }
+    }
+
     // Returns true if the given node has been marked as green during the
     // current compilation session. Used in various assertions
     pub fn is_green(&self, dep_node: &DepNode) -> bool {
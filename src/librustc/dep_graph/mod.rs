@@ -11,6 +11,7 @@
 pub mod debug;
 mod dep_node;
 mod dep_tracking_map;
+mod export;
 mod graph;
 mod prev;
 mod query;
@@ -20,7 +21,9 @@ pub mod cgu_reuse_tracker;
 
 pub use self::dep_tracking_map::{DepTrackingMap, DepTrackingMapConfig};
 pub use self::dep_node::{DepNode, DepKind, DepConstructor, WorkProductId, label_strs};
+pub use self::export::{ExportedDepGraph, ExportedDepNode};
 pub use self::graph::{DepGraph, WorkProduct, DepNodeIndex, DepNodeColor, OpenTask};
+pub use self::graph::ExecutionReason;
 pub use self::graph::WorkProductFileKind;
 pub use self::prev::PreviousDepGraph;
 pub use self::query::DepGraphQuery;
@@ -0,0 +1,123 @@
+// Copyright 2018 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Lint configuration files, as passed with `-Z lint-config`.
+//!
+//! A lint configuration file is a JSON object which sets lint levels for the
+//! whole crate and, optionally, for the source files under some paths:
+//!
+//! ```json
+//! {
+//!     "lints": { "nonstandard_style": "deny", "unused_variables": "warn" },
+//!     "paths": [
+//!         { "path": "src/generated", "lints": { "dead_code": "allow" } }
+//!     ]
+//! }
+//! ```
+//!
+//! Lint names are those accepted by `-A`/`-W`/`-D`/`-F`, and so may name lint
+//! groups. A level set for a lint takes precedence over one set for a group
+//! containing it. Relative paths are relative to the directory containing the
+//! configuration file, and name either a single source file or a directory.
+//!
+//! Crate-wide levels behave as though they were passed on the command line
+//! before any `-A`/`-W`/`-D`/`-F` flag, so those flags override them. Path
+//! levels apply to each item defined in a matching source file, as though the
+//! item carried a lint attribute; attributes within the item still override
+//! them. See `LintLevelSets` for how these are combined.
+
+use lint::Level;
+use serialize::json::{self, Json};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The lint levels read from a lint configuration file.
+#[derive(Clone, Debug, Hash)]
+pub struct LintConfig {
+    /// The configuration file itself, used in diagnostics.
+    pub file: PathBuf,
+    /// Levels for the whole crate.
+    pub lints: Vec<(String, Level)>,
+    /// Levels for the source files under each of these absolute paths.
+    pub paths: Vec<(PathBuf, Vec<(String, Level)>)>,
+}
+
+impl LintConfig {
+    /// Reads and parses the lint configuration file at `file`.
+    pub fn load(file: &Path) -> Result<LintConfig, String> {
+        let error = |msg: String| {
+            format!("invalid lint configuration file `{}`: {}", file.display(), msg)
+        };
+
+        let contents = fs::read_to_string(file).map_err(|e| {
+            format!("failed to read lint configuration file `{}`: {}", file.display(), e)
+        })?;
+        let json = json::from_str(&contents).map_err(|e| error(e.to_string()))?;
+        let root = json.as_object()
+            .ok_or_else(|| error("expected a JSON object".to_string()))?;
+
+        // Relative paths are resolved against the directory the configuration
+        // file is in, wherever rustc happens to be run from.
+        let mut base = file.parent().map(Path::to_path_buf).unwrap_or_default();
+        if base.is_relative() {
+            if let Ok(cwd) = env::current_dir() {
+                base = cwd.join(base);
+            }
+        }
+
+        let mut config = LintConfig {
+            file: file.to_path_buf(),
+            lints: Vec::new(),
+            paths: Vec::new(),
+        };
+
+        for (key, value) in root {
+            match &key[..] {
+                "lints" => config.lints = parse_levels(value, "lints").map_err(&error)?,
+                "paths" => {
+                    let overrides = value.as_array()
+                        .ok_or_else(|| error("`paths` must be an array".to_string()))?;
+                    for entry in overrides {
+                        let path = entry.find("path")
+                            .and_then(|path| path.as_string())
+                            .ok_or_else(|| {
+                                error("each entry of `paths` needs a string `path`".to_string())
+                            })?;
+                        let lints = match entry.find("lints") {
+                            Some(lints) => parse_levels(lints, path).map_err(&error)?,
+                            None => Vec::new(),
+                        };
+                        config.paths.push((base.join(path), lints));
+                    }
+                }
+                _ => return Err(error(format!("unknown key `{}`", key))),
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+/// Parses an object mapping lint names to levels, where `what` names the
+/// object in error messages.
+fn parse_levels(json: &Json, what: &str) -> Result<Vec<(String, Level)>, String> {
+    let object = json.as_object()
+        .ok_or_else(|| format!("the lints for `{}` must be an object", what))?;
+    object.iter().map(|(name, level)| {
+        let level = level.as_string()
+            .and_then(Level::from_str)
+            .ok_or_else(|| {
+                format!("the level of `{}` in `{}` must be one of `allow`, `warn`, \
+                         `deny` or `forbid`", name, what)
+            })?;
+        Ok((name.replace("-", "_"), level))
+    }).collect()
+}
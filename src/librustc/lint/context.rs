@@ -40,6 +40,7 @@ use ty::layout::{LayoutError, LayoutOf, TyLayout};
 use util::nodemap::FxHashMap;
 
 use std::default::Default as StdDefault;
+use std::path::Path;
 use syntax::ast;
 use syntax::edition;
 use syntax_pos::{MultiSpan, Span, symbol::LocalInternedString};
@@ -354,7 +355,39 @@ impl LintStore {
                                    sess: &Session,
                                    lint_name: &str,
                                    level: Level) {
-        let db = match self.check_lint_name(lint_name, None) {
+        if let Some(mut db) = self.lint_name_diagnostic(sess, lint_name) {
+            let msg = format!("requested on the command line with `{} {}`",
+                              match level {
+                                  Level::Allow => "-A",
+                                  Level::Warn => "-W",
+                                  Level::Deny => "-D",
+                                  Level::Forbid => "-F",
+                              },
+                              lint_name);
+            db.note(&msg);
+            db.emit();
+        }
+    }
+
+    /// Checks the validity of a lint name set in the lint configuration file
+    /// `file`, as `check_lint_name_cmdline` does for the command line.
+    pub fn check_lint_name_config(&self,
+                                  sess: &Session,
+                                  lint_name: &str,
+                                  level: Level,
+                                  file: &Path) {
+        if let Some(mut db) = self.lint_name_diagnostic(sess, lint_name) {
+            db.note(&format!("requested as `{}({})` in the lint configuration file `{}`",
+                             level.as_str(), lint_name, file.display()));
+            db.emit();
+        }
+    }
+
+    fn lint_name_diagnostic<'a>(&self,
+                                sess: &'a Session,
+                                lint_name: &str)
+                                -> Option<DiagnosticBuilder<'a>> {
+        match self.check_lint_name(lint_name, None) {
             CheckLintNameResult::Ok(_) => None,
             CheckLintNameResult::Warning(ref msg, _) => {
                 Some(sess.struct_warn(msg))
@@ -371,19 +404,6 @@ impl LintStore {
                 ))),
                 _ => None,
             },
-        };
-
-        if let Some(mut db) = db {
-            let msg = format!("requested on the command line with `{} {}`",
-                              match level {
-                                  Level::Allow => "-A",
-                                  Level::Warn => "-W",
-                                  Level::Deny => "-D",
-                                  Level::Forbid => "-F",
-                              },
-                              lint_name);
-            db.note(&msg);
-            db.emit();
         }
     }
 
@@ -1004,11 +1024,13 @@ impl<'a, 'tcx> hir_visit::Visitor<'tcx> for LateContext<'a, 'tcx> {
 
 impl<'a> ast_visit::Visitor<'a> for EarlyContext<'a> {
     fn visit_item(&mut self, it: &'a ast::Item) {
+        let push = self.builder.push_source_file(it.span);
         self.with_lint_attrs(it.id, &it.attrs, |cx| {
             run_lints!(cx, check_item, it);
             ast_visit::walk_item(cx, it);
             run_lints!(cx, check_item_post, it);
-        })
+        });
+        self.builder.pop(push);
     }
 
     fn visit_foreign_item(&mut self, it: &'a ast::ForeignItem) {
@@ -1247,7 +1269,9 @@ pub fn check_ast_crate(
             // item), warn for it here.
             run_lints!(cx, check_crate, krate);
 
+            let push = cx.builder.push_source_file(krate.span);
             ast_visit::walk_crate(cx, krate);
+            cx.builder.pop(push);
 
             run_lints!(cx, check_crate_post, krate);
         });
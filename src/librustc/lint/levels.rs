@@ -9,13 +9,16 @@
 // except according to those terms.
 
 use std::cmp;
+use std::env;
+use std::path::PathBuf;
 
 use errors::{Applicability, DiagnosticBuilder};
 use hir::HirId;
 use ich::StableHashingContext;
 use lint::builtin;
 use lint::context::CheckLintNameResult;
-use lint::{self, Lint, LintId, Level, LintSource};
+use lint::{self, Lint, LintConfig, LintId, Level, LintSource, LintStore};
+use rustc_data_structures::sync::Lrc;
 use rustc_data_structures::stable_hasher::{HashStable, ToStableHashKey,
                                            StableHasher, StableHasherResult};
 use session::Session;
@@ -23,11 +26,15 @@ use syntax::ast;
 use syntax::attr;
 use syntax::source_map::MultiSpan;
 use syntax::symbol::Symbol;
+use syntax_pos::{BytePos, FileName, Span};
 use util::nodemap::FxHashMap;
 
 pub struct LintLevelSets {
     list: Vec<LintSet>,
     lint_cap: Level,
+    // The levels the lint configuration file sets for the source files under
+    // each path, most specific path last.
+    path_specs: Vec<(PathBuf, FxHashMap<LintId, (Level, LintSource)>)>,
 }
 
 enum LintSet {
@@ -48,6 +55,7 @@ impl LintLevelSets {
         let mut me = LintLevelSets {
             list: Vec::new(),
             lint_cap: Level::Forbid,
+            path_specs: Vec::new(),
         };
         me.process_command_line(sess);
         return me
@@ -62,6 +70,18 @@ impl LintLevelSets {
         let mut specs = FxHashMap();
         self.lint_cap = sess.opts.lint_cap.unwrap_or(Level::Forbid);
 
+        // Levels from the lint configuration file come first, so that any
+        // `-A`/`-W`/`-D`/`-F` flags override them.
+        if let Some(ref config) = sess.opts.lint_config {
+            specs = lint_config_specs(sess, &store, config, &config.lints, self.lint_cap);
+
+            let mut paths = config.paths.iter().map(|&(ref path, ref lints)| {
+                (path.clone(), lint_config_specs(sess, &store, config, lints, self.lint_cap))
+            }).collect::<Vec<_>>();
+            paths.sort_by_key(|&(ref path, _)| path.components().count());
+            self.path_specs = paths;
+        }
+
         for &(ref lint_name, level) in &sess.opts.lint_opts {
             store.check_lint_name_cmdline(sess, &lint_name, level);
 
@@ -86,6 +106,24 @@ impl LintLevelSets {
         });
     }
 
+    /// Finds the indices into `path_specs` of the levels set for the source
+    /// file with the (unmapped) name `name`.
+    fn path_specs_for(&self, name: &FileName) -> Vec<usize> {
+        let path = match *name {
+            FileName::Real(ref path) if path.is_absolute() => path.clone(),
+            FileName::Real(ref path) => match env::current_dir() {
+                Ok(cwd) => cwd.join(path),
+                Err(_) => path.clone(),
+            },
+            _ => return Vec::new(),
+        };
+        self.path_specs.iter()
+            .enumerate()
+            .filter(|&(_, &(ref prefix, _))| path.starts_with(prefix))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
     fn get_lint_level(&self,
                       lint: &'static Lint,
                       idx: u32,
@@ -156,16 +194,51 @@ impl LintLevelSets {
     }
 }
 
+/// Resolves the lint levels `lints`, read from the lint configuration file, to
+/// the lints they apply to.
+fn lint_config_specs(sess: &Session,
+                     store: &LintStore,
+                     config: &LintConfig,
+                     lints: &[(String, Level)],
+                     lint_cap: Level)
+    -> FxHashMap<LintId, (Level, LintSource)>
+{
+    let mut resolved = lints.iter().filter_map(|&(ref lint_name, level)| {
+        store.check_lint_name_config(sess, lint_name, level, &config.file);
+        let ids = store.find_lints(lint_name).ok()?;
+        Some((lint_name, cmp::min(level, lint_cap), ids))
+    }).collect::<Vec<_>>();
+
+    // The file can't order its entries, so lint groups are applied first and
+    // the levels given for individual lints win over those of their groups.
+    resolved.sort_by_key(|&(_, _, ref ids)| cmp::Reverse(ids.len()));
+
+    let mut specs = FxHashMap();
+    for (lint_name, level, ids) in resolved {
+        let src = LintSource::ConfigFile(Symbol::intern(lint_name));
+        for id in ids {
+            specs.insert(id, (level, src));
+        }
+    }
+    specs
+}
+
 pub struct LintLevelsBuilder<'a> {
     sess: &'a Session,
     sets: LintLevelSets,
     id_to_set: FxHashMap<HirId, u32>,
     cur: u32,
     warn_about_weird_lints: bool,
+    // The indices into `sets.path_specs` which apply to each source file seen
+    // so far, keyed by its start position, and those applied to the source
+    // file currently being visited.
+    source_file_specs: FxHashMap<BytePos, Lrc<Vec<usize>>>,
+    cur_path_specs: Option<Lrc<Vec<usize>>>,
 }
 
 pub struct BuilderPush {
     prev: u32,
+    prev_path_specs: Option<Lrc<Vec<usize>>>,
 }
 
 impl<'a> LintLevelsBuilder<'a> {
@@ -177,7 +250,61 @@ impl<'a> LintLevelsBuilder<'a> {
             cur: 0,
             id_to_set: FxHashMap(),
             warn_about_weird_lints: sess.buffered_lints.borrow().is_some(),
+            source_file_specs: FxHashMap(),
+            cur_path_specs: None,
+        }
+    }
+
+    /// Applies the lint levels that the lint configuration file sets for the
+    /// source file containing `span`, unless they're already applied.
+    ///
+    /// This should be called when entering an item, before `push`ing its
+    /// attributes, so that these levels override those of enclosing items but
+    /// not those of the item itself. Like `push`, this returns a `BuilderPush`
+    /// which should be passed to `pop`.
+    pub fn push_source_file(&mut self, span: Span) -> BuilderPush {
+        let push = BuilderPush {
+            prev: self.cur,
+            prev_path_specs: self.cur_path_specs.clone(),
+        };
+        if self.sets.path_specs.is_empty() || span.is_dummy() {
+            return push
+        }
+
+        let file = self.sess.source_map().lookup_char_pos(span.lo()).file;
+        let sets = &self.sets;
+        let indices = self.source_file_specs.entry(file.start_pos).or_insert_with(|| {
+            Lrc::new(match file.unmapped_path {
+                Some(ref name) => sets.path_specs_for(name),
+                None => Vec::new(),
+            })
+        }).clone();
+        if indices.is_empty() || self.cur_path_specs.as_ref() == Some(&indices) {
+            return push
+        }
+
+        let mut specs = FxHashMap();
+        for &i in indices.iter() {
+            for (id, &(level, src)) in &self.sets.path_specs[i].1 {
+                // As with attributes, these can't lower a `forbid` from an
+                // enclosing scope.
+                if level != Level::Forbid {
+                    if let (Some(Level::Forbid), _) =
+                            self.sets.get_lint_id_level(*id, self.cur, None) {
+                        continue
+                    }
+                }
+                specs.insert(*id, (level, src));
+            }
         }
+
+        self.cur = self.sets.list.len() as u32;
+        self.sets.list.push(LintSet::Node {
+            specs: specs,
+            parent: push.prev,
+        });
+        self.cur_path_specs = Some(indices);
+        push
     }
 
     /// Pushes a list of AST lint attributes onto this context.
@@ -370,6 +497,7 @@ impl<'a> LintLevelsBuilder<'a> {
                 LintSource::Default => id.to_string(),
                 LintSource::Node(name, _) => name.to_string(),
                 LintSource::CommandLine(name) => name.to_string(),
+                LintSource::ConfigFile(name) => name.to_string(),
             };
             let (lint_attr_name, lint_attr_span) = match *src {
                 LintSource::Node(name, span) => (name, span),
@@ -392,6 +520,9 @@ impl<'a> LintLevelsBuilder<'a> {
                 LintSource::CommandLine(_) => {
                     diag_builder.note("`forbid` lint level was set on command line")
                 }
+                LintSource::ConfigFile(_) => {
+                    diag_builder.note("`forbid` lint level was set in the lint configuration file")
+                }
             }.emit();
             // don't set a separate error for every lint in the group
             break
@@ -408,12 +539,14 @@ impl<'a> LintLevelsBuilder<'a> {
 
         BuilderPush {
             prev: prev,
+            prev_path_specs: self.cur_path_specs.clone(),
         }
     }
 
     /// Called after `push` when the scope of a set of attributes are exited.
     pub fn pop(&mut self, push: BuilderPush) {
         self.cur = push.prev;
+        self.cur_path_specs = push.prev_path_specs;
     }
 
     /// Used to emit a lint-related diagnostic based on the current state of
@@ -485,9 +618,12 @@ impl<'a> HashStable<StableHashingContext<'a>> for LintLevelMap {
 
         id_to_set.hash_stable(hcx, hasher);
 
+        // The levels in `path_specs` are hashed as part of `list`, where they
+        // were applied.
         let LintLevelSets {
             ref list,
             lint_cap,
+            path_specs: _,
         } = *sets;
 
         lint_cap.hash_stable(hcx, hasher);
@@ -474,20 +474,27 @@ pub enum LintSource {
 
     /// Lint level was set by a command-line flag.
     CommandLine(Symbol),
+
+    /// Lint level was set by the lint configuration file, for the lint or
+    /// lint group with this name.
+    ConfigFile(Symbol),
 }
 
 impl_stable_hash_for!(enum self::LintSource {
     Default,
     Node(name, span),
-    CommandLine(text)
+    CommandLine(text),
+    ConfigFile(text)
 });
 
 pub type LevelSource = (Level, LintSource);
 
 pub mod builtin;
+mod config;
 mod context;
 mod levels;
 
+pub use self::config::LintConfig;
 pub use self::levels::{LintLevelSets, LintLevelMap};
 
 pub struct LintBuffer {
@@ -578,6 +585,25 @@ pub fn struct_lint_level<'a>(sess: &'a Session,
                              hyphen_case_flag_val));
             }
         }
+        LintSource::ConfigFile(lint_config_name) => {
+            let file = sess.opts.lint_config.as_ref()
+                .map(|config| config.file.display().to_string())
+                .unwrap_or_default();
+            let level_str = level.as_str();
+            if lint_config_name.as_str() == name {
+                sess.diag_note_once(
+                    &mut err,
+                    DiagnosticMessageId::from(lint),
+                    &format!("`{}({})` requested in the lint configuration file `{}`",
+                             level_str, name, file));
+            } else {
+                sess.diag_note_once(
+                    &mut err,
+                    DiagnosticMessageId::from(lint),
+                    &format!("`{}({})` implied by `{}({})` in the lint configuration file `{}`",
+                             level_str, name, level_str, lint_config_name, file));
+            }
+        }
         LintSource::Node(lint_attr_name, src) => {
             sess.diag_span_note_once(&mut err, DiagnosticMessageId::from(lint),
                                      src, "lint level defined here");
@@ -646,7 +672,9 @@ fn lint_levels<'a, 'tcx>(tcx: TyCtxt<'a, 'tcx, 'tcx>, cnum: CrateNum)
     let krate = tcx.hir.krate();
 
     builder.with_lint_attrs(ast::CRATE_NODE_ID, &krate.attrs, |builder| {
+        let push = builder.levels.push_source_file(krate.span);
         intravisit::walk_crate(builder, krate);
+        builder.levels.pop(push);
     });
 
     Lrc::new(builder.levels.build_map())
@@ -677,9 +705,11 @@ impl<'a, 'tcx> intravisit::Visitor<'tcx> for LintLevelMapBuilder<'a, 'tcx> {
     }
 
     fn visit_item(&mut self, it: &'tcx hir::Item) {
+        let push = self.levels.push_source_file(it.span);
         self.with_lint_attrs(it.id, &it.attrs, |builder| {
             intravisit::walk_item(builder, it);
         });
+        self.levels.pop(push);
     }
 
     fn visit_foreign_item(&mut self, it: &'tcx hir::ForeignItem) {
@@ -2627,6 +2627,12 @@ pub struct UnsafetyCheckResult {
 #[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
 pub struct GeneratorLayout<'tcx> {
     pub fields: Vec<LocalDecl<'tcx>>,
+
+    /// The local that each of `fields` was saved from, before the state transformation.
+    pub field_locals: Vec<Local>,
+
+    /// The index of the state discriminant in the generator struct. The saved locals follow it.
+    pub state_field: usize,
 }
 
 #[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
@@ -2806,7 +2812,7 @@ BraceStructTypeFoldableImpl! {
 
 BraceStructTypeFoldableImpl! {
     impl<'tcx> TypeFoldable<'tcx> for GeneratorLayout<'tcx> {
-        fields
+        fields, field_locals, state_field
     }
 }
 
@@ -214,6 +214,8 @@ pub enum ErrorOutputType {
     HumanReadable(ColorConfig),
     Json(bool),
     Short(ColorConfig),
+    /// A SARIF log of all the diagnostics, written at the end of the session.
+    Sarif,
 }
 
 impl Default for ErrorOutputType {
@@ -372,6 +374,9 @@ top_level_options!(
         debuginfo: DebugInfo [TRACKED],
         lint_opts: Vec<(String, lint::Level)> [TRACKED],
         lint_cap: Option<lint::Level> [TRACKED],
+        // The lint configuration file read for `-Z lint-config`. Its contents,
+        // rather than the path in `debugging_opts`, are what's tracked.
+        lint_config: Option<lint::LintConfig> [TRACKED],
         describe_lints: bool [UNTRACKED],
         output_types: OutputTypes [TRACKED],
         search_paths: SearchPaths [UNTRACKED],
@@ -591,6 +596,7 @@ impl Default for Options {
             debuginfo: DebugInfo::None,
             lint_opts: Vec::new(),
             lint_cap: None,
+            lint_config: None,
             describe_lints: false,
             output_types: OutputTypes(BTreeMap::new()),
             search_paths: SearchPaths::new(),
@@ -623,6 +629,8 @@ impl Options {
     pub fn build_dep_graph(&self) -> bool {
         self.incremental.is_some() || self.debugging_opts.dump_dep_graph
             || self.debugging_opts.query_dep_graph
+            || self.debugging_opts.dep_graph_export.is_some()
+            || self.debugging_opts.dep_graph_query.is_some()
     }
 
     #[inline(always)]
@@ -801,6 +809,8 @@ macro_rules! options {
             Some("a space-separated list of passes, or `all`");
         pub const parse_opt_uint: Option<&'static str> =
             Some("a number");
+        pub const parse_opt_byte_size: Option<&'static str> =
+            Some("a number of bytes, optionally followed by `K`, `M`, `G` or `T`");
         pub const parse_panic_strategy: Option<&'static str> =
             Some("either `panic` or `abort`");
         pub const parse_relro_level: Option<&'static str> =
@@ -931,6 +941,22 @@ macro_rules! options {
             }
         }
 
+        fn parse_opt_byte_size(slot: &mut Option<u64>, v: Option<&str>) -> bool {
+            let s = match v {
+                Some(s) => s,
+                None => { *slot = None; return false }
+            };
+            let (digits, shift) = match s.chars().last() {
+                Some('K') | Some('k') => (&s[..s.len() - 1], 10),
+                Some('M') | Some('m') => (&s[..s.len() - 1], 20),
+                Some('G') | Some('g') => (&s[..s.len() - 1], 30),
+                Some('T') | Some('t') => (&s[..s.len() - 1], 40),
+                _ => (s, 0),
+            };
+            *slot = digits.parse::<u64>().ok().and_then(|n| n.checked_mul(1 << shift));
+            slot.is_some()
+        }
+
         fn parse_passes(slot: &mut Passes, v: Option<&str>) -> bool {
             match v {
                 Some("all") => {
@@ -1216,6 +1242,9 @@ options! {DebuggingOptions, DebuggingSetter, basic_debugging_options,
         "parse only; do not compile, assemble, or link"),
     no_codegen: bool = (false, parse_bool, [TRACKED],
         "run all passes except codegen; no output"),
+    pipelined_metadata: bool = (false, parse_bool, [UNTRACKED],
+        "write the metadata requested with --emit=metadata before codegen, and announce it \
+         on stdout as a JSON artifact notification"),
     treat_err_as_bug: bool = (false, parse_bool, [TRACKED],
         "treat all errors that occur as bugs"),
     report_delayed_bugs: bool = (false, parse_bool, [TRACKED],
@@ -1238,10 +1267,31 @@ options! {DebuggingOptions, DebuggingSetter, basic_debugging_options,
         "verify incr. comp. hashes of green query instances"),
     incremental_ignore_spans: bool = (false, parse_bool, [UNTRACKED],
         "ignore spans during ICH computation -- used for testing"),
+    incremental_cache_limit: Option<u64> = (None, parse_opt_byte_size, [UNTRACKED],
+        "after compiling, evict the least recently used sessions of other crates from the \
+         incremental compilation directory until it is no larger than this"),
+    incremental_cache_max_age: Option<usize> = (None, parse_opt_uint, [UNTRACKED],
+        "after compiling, evict the sessions of crates not compiled within this many days \
+         from the incremental compilation directory"),
+    incremental_cache_report: bool = (false, parse_bool, [UNTRACKED],
+        "with no input file, print the disk usage of each crate in the incremental \
+         compilation directory"),
+    incremental_cache_compact: bool = (false, parse_bool, [UNTRACKED],
+        "with no input file, garbage collect the incremental compilation directory, \
+         applying any -Z incremental-cache-limit and -Z incremental-cache-max-age"),
+    incremental_seed: Option<PathBuf> = (None, parse_opt_pathbuf, [UNTRACKED],
+        "a read-only incremental compilation directory to copy a crate's most recent \
+         session from when there is none for it in -C incremental"),
     dump_dep_graph: bool = (false, parse_bool, [UNTRACKED],
         "dump the dependency graph to $RUST_DEP_GRAPH (default: /tmp/dep_graph.gv)"),
     query_dep_graph: bool = (false, parse_bool, [UNTRACKED],
         "enable queries of the dependency graph for regression testing"),
+    dep_graph_export: Option<PathBuf> = (None, parse_opt_pathbuf, [UNTRACKED],
+        "write the dependency graph, with the colors of its nodes and why they were \
+         executed, to this file as JSON"),
+    dep_graph_query: Option<String> = (None, parse_opt_string, [UNTRACKED],
+        "answer a query about the dependency graph, e.g. `why OptimizedMir & foo`, \
+         printing the answer to stdout"),
     profile_queries: bool = (false, parse_bool, [UNTRACKED],
         "trace and profile the queries of the incremental compilation framework"),
     profile_queries_and_keys: bool = (false, parse_bool, [UNTRACKED],
@@ -1382,12 +1432,31 @@ options! {DebuggingOptions, DebuggingSetter, basic_debugging_options,
         "run the self profiler"),
     profile_json: bool = (false, parse_bool, [UNTRACKED],
         "output a json file with profiler results"),
+    lint_config: Option<PathBuf> = (None, parse_opt_pathbuf, [UNTRACKED],
+        "read lint levels for the crate, and for source files under given paths, from this \
+         JSON file; `-A`/`-W`/`-D`/`-F` flags and lint attributes override them"),
+    diagnostic_output: Option<PathBuf> = (None, parse_opt_pathbuf, [UNTRACKED],
+        "write diagnostics, in the format chosen with --error-format, to this file \
+         rather than to stderr"),
+    diagnostic_output_summary: bool = (false, parse_bool, [UNTRACKED],
+        "with -Z diagnostic-output, also print diagnostics to stderr, one line each"),
+    apply_suggestions: bool = (false, parse_bool, [UNTRACKED],
+        "after compiling, rewrite the source files, applying every machine-applicable suggestion"),
+    apply_suggestions_dry_run: bool = (false, parse_bool, [UNTRACKED],
+        "print the machine-applicable suggestions as a unified diff, rather than applying them"),
+    self_profile_trace: Option<PathBuf> = (None, parse_opt_pathbuf, [UNTRACKED],
+        "write each query, cache hit and codegen work item to this file, in the Chrome \
+         trace-event format"),
     emit_stack_sizes: bool = (false, parse_bool, [UNTRACKED],
         "emits a section containing stack size metadata"),
     plt: Option<bool> = (None, parse_opt_bool, [TRACKED],
           "whether to use the PLT when calling into shared libraries;
           only has effect for PIC code on systems with ELF binaries
           (default: PLT is disabled if full relro is enabled)"),
+    yk_section_version: Option<usize> = (None, parse_opt_uint, [TRACKED],
+        "write Yorick sections in the given format version (default: the latest)"),
+    yk_compress_sections: bool = (false, parse_bool, [TRACKED],
+        "deflate version 2 Yorick sections"),
 }
 
 pub fn default_lib_output() -> CrateType {
@@ -1719,7 +1788,7 @@ pub fn rustc_optgroups() -> Vec<RustcOptGroup> {
             "",
             "error-format",
             "How errors and other messages are produced",
-            "human|json|short",
+            "human|json|short|sarif",
         ),
         opt::opt_s(
             "",
@@ -1875,13 +1944,14 @@ pub fn build_session_options_and_crate_config(
             Some("json") => ErrorOutputType::Json(false),
             Some("pretty-json") => ErrorOutputType::Json(true),
             Some("short") => ErrorOutputType::Short(color),
+            Some("sarif") => ErrorOutputType::Sarif,
             None => ErrorOutputType::HumanReadable(color),
 
             Some(arg) => early_error(
                 ErrorOutputType::HumanReadable(color),
                 &format!(
-                    "argument for --error-format must be `human`, `json` or \
-                     `short` (instead was `{}`)",
+                    "argument for --error-format must be `human`, `json`, `short` or \
+                     `sarif` (instead was `{}`)",
                     arg
                 ),
             ),
@@ -1898,6 +1968,10 @@ pub fn build_session_options_and_crate_config(
 
     let mut debugging_opts = build_debugging_options(matches, error_format);
 
+    let lint_config = debugging_opts.lint_config.as_ref().map(|path| {
+        lint::LintConfig::load(path).unwrap_or_else(|e| early_error(error_format, &e))
+    });
+
     if !debugging_opts.unstable_options && error_format == ErrorOutputType::Json(true) {
         early_error(
             ErrorOutputType::Json(false),
@@ -1905,6 +1979,13 @@ pub fn build_session_options_and_crate_config(
         );
     }
 
+    if !debugging_opts.unstable_options && error_format == ErrorOutputType::Sarif {
+        early_error(
+            ErrorOutputType::HumanReadable(color),
+            "--error-format=sarif is unstable",
+        );
+    }
+
     if debugging_opts.pgo_gen.is_some() && !debugging_opts.pgo_use.is_empty() {
         early_error(
             error_format,
@@ -1937,6 +2018,10 @@ pub fn build_session_options_and_crate_config(
         output_types.insert(OutputType::Exe, None);
     }
 
+    if debugging_opts.pipelined_metadata && !output_types.contains_key(&OutputType::Metadata) {
+        early_error(error_format, "-Z pipelined-metadata requires --emit=metadata");
+    }
+
     let mut cg = build_codegen_options(matches, error_format);
     let mut codegen_units = cg.codegen_units;
     let mut disable_thinlto = false;
@@ -2254,6 +2339,7 @@ pub fn build_session_options_and_crate_config(
             debuginfo,
             lint_opts,
             lint_cap,
+            lint_config,
             describe_lints,
             output_types: OutputTypes(output_types),
             search_paths,
@@ -2453,6 +2539,7 @@ mod dep_tracking {
     impl_dep_tracking_hash_via_hash!(Option<PanicStrategy>);
     impl_dep_tracking_hash_via_hash!(Option<RelroLevel>);
     impl_dep_tracking_hash_via_hash!(Option<lint::Level>);
+    impl_dep_tracking_hash_via_hash!(Option<lint::LintConfig>);
     impl_dep_tracking_hash_via_hash!(Option<PathBuf>);
     impl_dep_tracking_hash_via_hash!(Option<cstore::NativeLibraryKind>);
     impl_dep_tracking_hash_via_hash!(CrateType);
@@ -3195,6 +3282,14 @@ mod tests {
         opts = reference.clone();
         opts.debugging_opts.cross_lang_lto = CrossLangLto::LinkerPluginAuto;
         assert!(reference.dep_tracking_hash() != opts.dep_tracking_hash());
+
+        opts = reference.clone();
+        opts.debugging_opts.yk_section_version = Some(1);
+        assert!(reference.dep_tracking_hash() != opts.dep_tracking_hash());
+
+        opts = reference.clone();
+        opts.debugging_opts.yk_compress_sections = true;
+        assert!(reference.dep_tracking_hash() != opts.dep_tracking_hash());
     }
 
     #[test]
@@ -30,15 +30,17 @@ use rustc_data_structures::base_n;
 use rustc_data_structures::sync::{self, Lrc, Lock, LockCell, OneThread, Once, RwLock};
 
 use errors::{self, DiagnosticBuilder, DiagnosticId, Applicability};
-use errors::emitter::{Emitter, EmitterWriter};
+use errors::emitter::{Emitter, EmitterWriter, TeeEmitter};
+use errors::fixes::{CollectedSuggestions, Fixes, SuggestionCollector};
 use syntax::ast::{self, NodeId};
 use syntax::edition::Edition;
 use syntax::feature_gate::{self, AttributeType};
 use syntax::json::JsonEmitter;
+use syntax::sarif::SarifEmitter;
 use syntax::source_map;
 use syntax::parse::{self, ParseSess};
 use syntax_pos::{MultiSpan, Span};
-use util::profiling::SelfProfiler;
+use util::profiling::{SelfProfiler, TraceRecorder};
 
 use rustc_target::spec::{PanicStrategy, RelroLevel, Target, TargetTriple};
 use rustc_data_structures::flock;
@@ -48,6 +50,7 @@ use std;
 use std::cell::{self, Cell, RefCell};
 use std::env;
 use std::fmt;
+use std::fs;
 use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::time::Duration;
@@ -136,6 +139,9 @@ pub struct Session {
     /// Used by -Z self-profile
     pub self_profiling: Lock<SelfProfiler>,
 
+    /// The machine-applicable suggestions emitted, for -Z apply-suggestions.
+    pub collected_suggestions: Once<CollectedSuggestions>,
+
     /// Some measurements that are being gathered during compilation.
     pub perf_stats: PerfStats,
 
@@ -844,6 +850,48 @@ impl Session {
         profiler.save_results(&self.opts);
     }
 
+    pub fn save_trace_events(&self, path: &Path) {
+        let profiler = self.self_profiling.borrow();
+        if let Err(e) = profiler.save_trace(path) {
+            self.err(&format!("failed to write self-profile trace to `{}`: {}",
+                              path.display(), e));
+        }
+    }
+
+    /// The recorder to trace events with, when they happen where the profiler
+    /// can't be reached, such as on LLVM worker threads.
+    pub fn trace_recorder(&self) -> Option<TraceRecorder> {
+        self.self_profiling.borrow().trace_recorder()
+    }
+
+    /// Applies the machine-applicable suggestions emitted so far to the source
+    /// files, or with -Z apply-suggestions-dry-run, prints them as a diff.
+    pub fn apply_suggestions(&self) {
+        let suggestions = match self.collected_suggestions.try_get() {
+            Some(suggestions) => std::mem::replace(&mut *suggestions.lock(), Vec::new()),
+            None => return,
+        };
+        let fixes = Fixes::new(self.source_map(), &suggestions);
+        for conflict in &fixes.conflicts {
+            let spans = conflict.parts.iter().map(|part| part.span).collect::<Vec<_>>();
+            self.span_warn(spans, "suggestion not applied, as it overlaps another one");
+        }
+        if fixes.skipped > 0 {
+            // E.g. suggestions in macro expansions, or spanning several files.
+            self.warn(&format!("{} suggestion{} not applied, as not in a single file on disk",
+                               fixes.skipped,
+                               if fixes.skipped == 1 { "" } else { "s" }));
+        }
+        for file in &fixes.files {
+            if self.opts.debugging_opts.apply_suggestions_dry_run {
+                print!("{}", file.diff());
+            } else if let Err(e) = file.write() {
+                self.err(&format!("failed to apply suggestions to `{}`: {}",
+                                  file.path.display(), e));
+            }
+        }
+    }
+
     pub fn print_perf_stats(&self) {
         println!(
             "Total time spent computing symbol hashes:      {}",
@@ -1024,8 +1072,10 @@ pub fn build_session_with_source_map(
     // normal diagnostic warnings, since the warning lint can also be denied and changed
     // later via the source code.
     let warnings_allow = sopts
-        .lint_opts
+        .lint_config
         .iter()
+        .flat_map(|config| config.lints.iter())
+        .chain(sopts.lint_opts.iter())
         .filter(|&&(ref key, _)| *key == "warnings")
         .map(|&(_, ref level)| *level == lint::Allow)
         .last()
@@ -1040,6 +1090,11 @@ pub fn build_session_with_source_map(
 
     let external_macro_backtrace = sopts.debugging_opts.external_macro_backtrace;
 
+    let emitter_dest = match (emitter_dest, &sopts.debugging_opts.diagnostic_output) {
+        (None, &Some(ref path)) => Some(open_diagnostic_output(path, false, sopts.error_format)),
+        (emitter_dest, _) => emitter_dest,
+    };
+
     let emitter: Box<dyn Emitter + sync::Send> =
         match (sopts.error_format, emitter_dest) {
             (config::ErrorOutputType::HumanReadable(color_config), None) => Box::new(
@@ -1075,7 +1130,29 @@ pub fn build_session_with_source_map(
             (config::ErrorOutputType::Short(_), Some(dst)) => {
                 Box::new(EmitterWriter::new(dst, Some(source_map.clone()), true, false))
             }
+            (config::ErrorOutputType::Sarif, None) => Box::new(
+                SarifEmitter::stderr(Some(registry), source_map.clone()),
+            ),
+            (config::ErrorOutputType::Sarif, Some(dst)) => Box::new(
+                SarifEmitter::new(dst, Some(registry), source_map.clone()),
+            ),
         };
+    let emitter = if sopts.debugging_opts.diagnostic_output.is_some() &&
+                     sopts.debugging_opts.diagnostic_output_summary {
+        with_diagnostic_summary(emitter, sopts.error_format, source_map.clone())
+    } else {
+        emitter
+    };
+    let collected_suggestions = if sopts.debugging_opts.apply_suggestions ||
+                                   sopts.debugging_opts.apply_suggestions_dry_run {
+        Some(Lrc::new(Lock::new(Vec::new())))
+    } else {
+        None
+    };
+    let emitter: Box<dyn Emitter + sync::Send> = match collected_suggestions {
+        Some(ref suggestions) => Box::new(SuggestionCollector::new(emitter, suggestions.clone())),
+        None => emitter,
+    };
 
     let diagnostic_handler = errors::Handler::with_emitter_and_flags(
         emitter,
@@ -1089,7 +1166,11 @@ pub fn build_session_with_source_map(
         },
     );
 
-    build_session_(sopts, local_crate_source_file, diagnostic_handler, source_map)
+    let sess = build_session_(sopts, local_crate_source_file, diagnostic_handler, source_map);
+    if let Some(suggestions) = collected_suggestions {
+        sess.collected_suggestions.set(suggestions);
+    }
+    sess
 }
 
 pub fn build_session_(
@@ -1117,6 +1198,14 @@ pub fn build_session_(
     let local_crate_source_file =
         local_crate_source_file.map(|path| file_path_mapping.map_prefix(path).0);
 
+    // Tracing doesn't need -Z self-profile: the profiler is always run, and
+    // only prints its results with it.
+    let self_profiler = if sopts.debugging_opts.self_profile_trace.is_some() {
+        SelfProfiler::with_trace(Some(TraceRecorder::new()))
+    } else {
+        SelfProfiler::new()
+    };
+
     let optimization_fuel_crate = sopts.debugging_opts.fuel.as_ref().map(|i| i.0.clone());
     let optimization_fuel_limit =
         LockCell::new(sopts.debugging_opts.fuel.as_ref().map(|i| i.1).unwrap_or(0));
@@ -1168,7 +1257,8 @@ pub fn build_session_(
         imported_macro_spans: OneThread::new(RefCell::new(FxHashMap::default())),
         incr_comp_session: OneThread::new(RefCell::new(IncrCompSession::NotInitialized)),
         cgu_reuse_tracker,
-        self_profiling: Lock::new(SelfProfiler::new()),
+        self_profiling: Lock::new(self_profiler),
+        collected_suggestions: Once::new(),
         profile_channel: Lock::new(None),
         perf_stats: PerfStats {
             symbol_hash_time: Lock::new(Duration::from_secs(0)),
@@ -1301,6 +1391,42 @@ pub enum IncrCompSession {
     InvalidBecauseOfErrors { session_directory: PathBuf },
 }
 
+/// Opens the file diagnostics are written to with `-Z diagnostic-output`. It is
+/// truncated unless `append` is set, as when several handlers write to it in
+/// turn.
+pub fn open_diagnostic_output(path: &Path,
+                              append: bool,
+                              output: config::ErrorOutputType)
+                              -> Box<dyn Write + Send> {
+    let file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .append(append)
+        .truncate(!append)
+        .open(path);
+    match file {
+        Ok(file) => Box::new(file),
+        Err(e) => early_error(output, &format!("failed to open diagnostic output file `{}`: {}",
+                                               path.display(), e)),
+    }
+}
+
+/// Makes `emitter`, which writes to a diagnostic output file, also print each
+/// diagnostic to stderr in the short human-readable format.
+pub fn with_diagnostic_summary(emitter: Box<dyn Emitter + sync::Send>,
+                               output: config::ErrorOutputType,
+                               source_map: Lrc<source_map::SourceMap>)
+                               -> Box<dyn Emitter + sync::Send> {
+    let color_config = match output {
+        config::ErrorOutputType::HumanReadable(color_config) |
+        config::ErrorOutputType::Short(color_config) => color_config,
+        config::ErrorOutputType::Json(_) |
+        config::ErrorOutputType::Sarif => errors::ColorConfig::Auto,
+    };
+    let summary = EmitterWriter::stderr(color_config, Some(source_map), true, false);
+    Box::new(TeeEmitter::new(emitter, Box::new(summary)))
+}
+
 pub fn early_error(output: config::ErrorOutputType, msg: &str) -> ! {
     let emitter: Box<dyn Emitter + sync::Send> = match output {
         config::ErrorOutputType::HumanReadable(color_config) => {
@@ -1310,6 +1436,11 @@ pub fn early_error(output: config::ErrorOutputType, msg: &str) -> ! {
         config::ErrorOutputType::Short(color_config) => {
             Box::new(EmitterWriter::stderr(color_config, None, true, false))
         }
+        // A SARIF log of its own for a single message would be one log too
+        // many in the output.
+        config::ErrorOutputType::Sarif => {
+            Box::new(EmitterWriter::stderr(errors::ColorConfig::Auto, None, false, false))
+        }
     };
     let handler = errors::Handler::with_emitter(true, false, emitter);
     handler.emit(&MultiSpan::new(), msg, errors::Level::Fatal);
@@ -1325,6 +1456,9 @@ pub fn early_warn(output: config::ErrorOutputType, msg: &str) {
         config::ErrorOutputType::Short(color_config) => {
             Box::new(EmitterWriter::stderr(color_config, None, true, false))
         }
+        config::ErrorOutputType::Sarif => {
+            Box::new(EmitterWriter::stderr(errors::ColorConfig::Auto, None, false, false))
+        }
     };
     let handler = errors::Handler::with_emitter(true, false, emitter);
     handler.emit(&MultiSpan::new(), msg, errors::Level::Warning);
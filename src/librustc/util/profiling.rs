@@ -10,8 +10,14 @@
 
 use session::config::Options;
 
-use std::fs;
-use std::io::{self, StdoutLock, Write};
+use rustc_data_structures::fx::FxHashMap;
+use serialize::json::as_json;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, StdoutLock, Write};
+use std::path::Path;
+use std::process;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, ThreadId};
 use std::time::Instant;
 
 macro_rules! define_categories {
@@ -21,6 +27,14 @@ macro_rules! define_categories {
             $($name),*
         }
 
+        impl ProfileCategory {
+            fn name(&self) -> &'static str {
+                match *self {
+                    $(ProfileCategory::$name => stringify!($name)),*
+                }
+            }
+        }
+
         #[allow(nonstandard_style)]
         struct Categories<T> {
             $($name: T),*
@@ -121,18 +135,142 @@ define_categories! {
     Other,
 }
 
+/// Records timestamped events to be viewed in a trace viewer, such as
+/// `chrome://tracing`. Clones record into the same trace, so that work done
+/// on other threads, like LLVM's, can be recorded alongside the queries.
+#[derive(Clone)]
+pub struct TraceRecorder {
+    start: Instant,
+    data: Arc<Mutex<TraceData>>,
+}
+
+struct TraceData {
+    events: Vec<TraceEvent>,
+    /// Trace viewers expect numeric thread ids, so threads are numbered in the
+    /// order they record their first event.
+    thread_ids: FxHashMap<ThreadId, usize>,
+}
+
+struct TraceEvent {
+    name: String,
+    category: &'static str,
+    /// Microseconds since the recorder was created.
+    timestamp: u64,
+    /// The duration, in microseconds, of a complete event. Instant events
+    /// have none.
+    duration: Option<u64>,
+    thread: usize,
+}
+
+/// An event which is recorded when dropped, lasting from when it was started.
+pub struct TraceSpan {
+    recorder: TraceRecorder,
+    name: String,
+    category: &'static str,
+    start: u64,
+}
+
+impl Drop for TraceSpan {
+    fn drop(&mut self) {
+        let name = ::std::mem::replace(&mut self.name, String::new());
+        self.recorder.record_complete(name, self.category, self.start);
+    }
+}
+
+impl TraceRecorder {
+    pub fn new() -> TraceRecorder {
+        TraceRecorder {
+            start: Instant::now(),
+            data: Arc::new(Mutex::new(TraceData {
+                events: Vec::new(),
+                thread_ids: FxHashMap::default(),
+            })),
+        }
+    }
+
+    fn now(&self) -> u64 {
+        let elapsed = self.start.elapsed();
+        elapsed.as_secs() * 1_000_000 + (elapsed.subsec_nanos() / 1_000) as u64
+    }
+
+    fn record(&self, name: String, category: &'static str, timestamp: u64, duration: Option<u64>) {
+        let mut data = self.data.lock().unwrap();
+        let thread_count = data.thread_ids.len();
+        let thread = *data.thread_ids.entry(thread::current().id()).or_insert(thread_count);
+        data.events.push(TraceEvent { name, category, timestamp, duration, thread });
+    }
+
+    /// Records an event lasting from `start`, as returned by `now`, until now.
+    fn record_complete(&self, name: String, category: &'static str, start: u64) {
+        let duration = self.now() - start;
+        self.record(name, category, start, Some(duration));
+    }
+
+    /// Records an event without a duration.
+    pub fn record_instant(&self, name: String, category: &'static str) {
+        let now = self.now();
+        self.record(name, category, now, None);
+    }
+
+    /// Starts an event which lasts until the returned span is dropped.
+    pub fn span(&self, name: String, category: &'static str) -> TraceSpan {
+        TraceSpan {
+            recorder: self.clone(),
+            name,
+            category,
+            start: self.now(),
+        }
+    }
+
+    /// Writes the events recorded so far in the Chrome trace-event format.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let data = self.data.lock().unwrap();
+        let mut out = BufWriter::new(File::create(path)?);
+        let pid = process::id();
+
+        write!(out, "{{\"traceEvents\":[")?;
+        for (i, event) in data.events.iter().enumerate() {
+            if i > 0 {
+                write!(out, ",")?;
+            }
+            write!(out, "\n{{\"name\":{},\"cat\":\"{}\",\"ts\":{},\"pid\":{},\"tid\":{},",
+                   as_json(&event.name), event.category, event.timestamp, pid, event.thread)?;
+            match event.duration {
+                Some(duration) => write!(out, "\"ph\":\"X\",\"dur\":{}}}", duration)?,
+                // Instant events are scoped to their thread.
+                None => write!(out, "\"ph\":\"i\",\"s\":\"t\"}}")?,
+            }
+        }
+        write!(out, "\n],\"displayTimeUnit\":\"ms\"}}\n")?;
+        out.flush()
+    }
+}
+
 pub struct SelfProfiler {
     timer_stack: Vec<ProfileCategory>,
     data: CategoryData,
     current_timer: Instant,
+    trace: Option<TraceRecorder>,
+    /// When each activity still running on each thread started, if tracing.
+    /// With parallel queries the profiler is shared between threads, whose
+    /// activities nest independently.
+    trace_starts: FxHashMap<ThreadId, Vec<u64>>,
 }
 
 impl SelfProfiler {
     pub fn new() -> SelfProfiler {
+        SelfProfiler::with_trace(None)
+    }
+
+    /// Creates a profiler which also records each query, cache hit and
+    /// activity in `trace`.
+    pub fn with_trace(trace: Option<TraceRecorder>) -> SelfProfiler {
         let mut profiler = SelfProfiler {
             timer_stack: Vec::new(),
             data: CategoryData::new(),
             current_timer: Instant::now(),
+            trace,
+            trace_starts: FxHashMap::default(),
         };
 
         profiler.start_activity(ProfileCategory::Other);
@@ -140,7 +278,40 @@ impl SelfProfiler {
         profiler
     }
 
+    /// The recorder events are traced with, if any, to record work done
+    /// without access to the profiler.
+    pub fn trace_recorder(&self) -> Option<TraceRecorder> {
+        self.trace.clone()
+    }
+
     pub fn start_activity(&mut self, category: ProfileCategory) {
+        self.start_timer(category);
+        if let Some(ref trace) = self.trace {
+            self.trace_starts.entry(thread::current().id()).or_default().push(trace.now());
+        }
+    }
+
+    pub fn end_activity(&mut self, category: ProfileCategory) {
+        self.end_timer(category);
+        self.end_trace(category.name(), category);
+    }
+
+    /// Ends the activity started to execute the query `name`, which is traced
+    /// under the query's name rather than its category's.
+    pub fn end_query(&mut self, name: &'static str, category: ProfileCategory) {
+        self.end_timer(category);
+        self.end_trace(name, category);
+    }
+
+    fn end_trace(&mut self, name: &'static str, category: ProfileCategory) {
+        if let Some(ref trace) = self.trace {
+            let starts = self.trace_starts.get_mut(&thread::current().id()).unwrap();
+            let start = starts.pop().unwrap();
+            trace.record_complete(name.to_string(), category.name(), start);
+        }
+    }
+
+    fn start_timer(&mut self, category: ProfileCategory) {
         match self.timer_stack.last().cloned() {
             None => {
                 self.current_timer = Instant::now();
@@ -167,12 +338,16 @@ impl SelfProfiler {
         self.data.query_counts.set(category, (hits, total + 1));
     }
 
-    pub fn record_query_hit(&mut self, category: ProfileCategory) {
+    pub fn record_query_hit(&mut self, name: &'static str, category: ProfileCategory) {
         let (hits, total) = *self.data.query_counts.get(category);
         self.data.query_counts.set(category, (hits + 1, total));
+
+        if let Some(ref trace) = self.trace {
+            trace.record_instant(name.to_string(), "cache hit");
+        }
     }
 
-    pub fn end_activity(&mut self, category: ProfileCategory) {
+    fn end_timer(&mut self, category: ProfileCategory) {
         match self.timer_stack.pop() {
             None => bug!("end_activity() was called but there was no running activity"),
             Some(c) =>
@@ -245,4 +420,12 @@ impl SelfProfiler {
 
         fs::write("self_profiler_results.json", json).unwrap();
     }
+
+    /// Writes the events traced so far to `path`, see `TraceRecorder::save`.
+    pub fn save_trace(&self, path: &Path) -> io::Result<()> {
+        match self.trace {
+            Some(ref trace) => trace.save(path),
+            None => Ok(()),
+        }
+    }
 }
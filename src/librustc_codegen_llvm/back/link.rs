@@ -22,7 +22,7 @@ use rustc::session::config::{RUST_CGU_EXT, Lto};
 use rustc::session::filesearch;
 use rustc::session::search_paths::PathKind;
 use rustc::session::Session;
-use rustc::middle::cstore::{NativeLibrary, LibSource, NativeLibraryKind};
+use rustc::middle::cstore::{EncodedMetadata, NativeLibrary, LibSource, NativeLibraryKind};
 use rustc::middle::dependency_format::Linkage;
 use {CodegenResults, CrateInfo};
 use rustc::util::common::time;
@@ -31,15 +31,17 @@ use rustc::hir::def_id::CrateNum;
 use tempfile::{Builder as TempFileBuilder, TempDir};
 use rustc_target::spec::{PanicStrategy, RelroLevel, LinkerFlavor};
 use rustc_data_structures::fx::FxHashSet;
+use serialize::json;
 use context::get_reloc_model;
 use llvm;
 
 use std::ascii;
 use std::char;
 use std::env;
+use std::ffi::OsString;
 use std::fmt;
 use std::fs;
-use std::io;
+use std::io::{self, Write};
 use std::iter;
 use std::path::{Path, PathBuf};
 use std::process::{Output, Stdio};
@@ -286,21 +288,9 @@ fn link_binary_output(sess: &Session,
 
     if outputs.outputs.contains_key(&OutputType::Metadata) {
         let out_filename = filename_for_metadata(sess, crate_name, outputs);
-        // To avoid races with another rustc process scanning the output directory,
-        // we need to write the file somewhere else and atomically move it to its
-        // final destination, with a `fs::rename` call. In order for the rename to
-        // always succeed, the temporary file needs to be on the same filesystem,
-        // which is why we create it inside the output directory specifically.
-        let metadata_tmpdir = match TempFileBuilder::new()
-            .prefix("rmeta")
-            .tempdir_in(out_filename.parent().unwrap())
-        {
-            Ok(tmpdir) => tmpdir,
-            Err(err) => sess.fatal(&format!("couldn't create a temp dir: {}", err)),
-        };
-        let metadata = emit_metadata(sess, codegen_results, &metadata_tmpdir);
-        if let Err(e) = fs::rename(metadata, &out_filename) {
-            sess.fatal(&format!("failed to write {}: {}", out_filename.display(), e));
+        // With `-Z pipelined-metadata`, this was written before codegen.
+        if !sess.opts.debugging_opts.pipelined_metadata {
+            write_rmeta(sess, &codegen_results.metadata, &out_filename);
         }
         out_filenames.push(out_filename);
     }
@@ -356,15 +346,65 @@ fn archive_config<'a>(sess: &'a Session,
     }
 }
 
+fn write_rmeta(sess: &Session, metadata: &EncodedMetadata, out_filename: &Path) {
+    // To avoid races with another rustc process scanning the output directory,
+    // we need to write the file somewhere else and atomically move it to its
+    // final destination, with a `fs::rename` call. In order for the rename to
+    // always succeed, the temporary file needs to be on the same filesystem,
+    // which is why we create it inside the output directory specifically.
+    let metadata_tmpdir = match TempFileBuilder::new()
+        .prefix("rmeta")
+        .tempdir_in(out_filename.parent().unwrap())
+    {
+        Ok(tmpdir) => tmpdir,
+        Err(err) => sess.fatal(&format!("couldn't create a temp dir: {}", err)),
+    };
+    let metadata = emit_metadata(sess, metadata, &metadata_tmpdir);
+    if let Err(e) = fs::rename(metadata, out_filename) {
+        sess.fatal(&format!("failed to write {}: {}", out_filename.display(), e));
+    }
+}
+
+#[derive(RustcEncodable)]
+struct ArtifactNotification<'a> {
+    artifact: &'a str,
+    emit: &'a str,
+}
+
+/// Writes the `.rmeta` file as soon as the crate's metadata is encoded, before
+/// the crate is codegened, and announces it on stdout with a line like
+/// `{"artifact":"libfoo.rmeta","emit":"metadata"}`. This is done for
+/// `-Z pipelined-metadata`, so that build tools can start compiling the
+/// crate's dependents against its metadata while it is codegened and linked.
+pub(crate) fn emit_pipelined_metadata(sess: &Session,
+                                      metadata: &EncodedMetadata,
+                                      outputs: &OutputFilenames,
+                                      crate_name: &str) {
+    let out_filename = filename_for_metadata(sess, crate_name, outputs);
+    write_rmeta(sess, metadata, &out_filename);
+
+    let notification = ArtifactNotification {
+        artifact: &out_filename.to_string_lossy(),
+        emit: "metadata",
+    };
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let result = writeln!(stdout, "{}", json::as_json(&notification))
+        .and_then(|_| stdout.flush());
+    if let Err(e) = result {
+        sess.fatal(&format!("failed to announce {}: {}", out_filename.display(), e));
+    }
+}
+
 /// We use a temp directory here to avoid races between concurrent rustc processes,
 /// such as builds in the same directory using the same filename for metadata while
 /// building an `.rlib` (stomping over one another), or writing an `.rmeta` into a
 /// directory being searched for `extern crate` (observing an incomplete file).
 /// The returned path is the temporary file containing the complete metadata.
-fn emit_metadata<'a>(sess: &'a Session, codegen_results: &CodegenResults, tmpdir: &TempDir)
+fn emit_metadata<'a>(sess: &'a Session, metadata: &EncodedMetadata, tmpdir: &TempDir)
                      -> PathBuf {
     let out_filename = tmpdir.path().join(METADATA_FILENAME);
-    let result = fs::write(&out_filename, &codegen_results.metadata.raw_data);
+    let result = fs::write(&out_filename, &metadata.raw_data);
 
     if let Err(e) = result {
         sess.fatal(&format!("failed to write {}: {}", out_filename.display(), e));
@@ -453,7 +493,7 @@ fn link_rlib<'a>(sess: &'a Session,
         RlibFlavor::Normal => {
             // Instead of putting the metadata in an object file section, rlibs
             // contain the metadata in a separate file.
-            ab.add_file(&emit_metadata(sess, codegen_results, tmpdir));
+            ab.add_file(&emit_metadata(sess, &codegen_results.metadata, tmpdir));
 
             // For LTO purposes, the bytecode of this library is also inserted
             // into the archive.
@@ -727,6 +767,22 @@ fn link_natively(sess: &Session,
         if crate_type == config::CrateType::Executable {
             cmd.arg("-Wl,--no-gc-sections");
             cmd.args(sess.yk_link_objects.borrow().iter().map(|o| o.path()));
+
+            // Executables don't export symbols by default, but the runtime finds the sections
+            // by looking up their start and stop symbols with `dlsym(3)`.
+            let mut dyn_list = String::from("{\n");
+            for obj in sess.yk_link_objects.borrow().iter() {
+                dyn_list.push_str(&format!("  {};\n  {};\n",
+                                           obj.start_symbol(), obj.stop_symbol()));
+            }
+            dyn_list.push_str("};\n");
+            let dyn_list_path = tmpdir.join("yk_dynamic_list");
+            if let Err(e) = fs::write(&dyn_list_path, dyn_list) {
+                sess.fatal(&format!("failed to write Yorick dynamic list: {}", e));
+            }
+            let mut arg = OsString::from("-Wl,--dynamic-list=");
+            arg.push(&dyn_list_path);
+            cmd.arg(arg);
         }
     }
 
@@ -35,6 +35,7 @@ use CrateInfo;
 use rustc::hir::def_id::{CrateNum, LOCAL_CRATE};
 use rustc::ty::TyCtxt;
 use rustc::util::common::{time_ext, time_depth, set_time_depth, print_time_passes_entry};
+use rustc::util::profiling::TraceRecorder;
 use rustc_fs_util::{path2cstr, link_or_copy};
 use rustc_data_structures::small_c_str::SmallCStr;
 use rustc_data_structures::svh::Svh;
@@ -387,6 +388,8 @@ pub struct CodegenContext {
     // A reference to the TimeGraph so we can register timings. None means that
     // measuring is disabled.
     time_graph: Option<TimeGraph>,
+    // Where to record the work items done, if -Z self-profile-trace is given.
+    trace: Option<TraceRecorder>,
     // The assembler command if no_integrated_as option is enabled, None otherwise
     assembler_cmd: Option<Arc<AssemblerCommand>>,
 }
@@ -653,6 +656,9 @@ fn generate_lto_work(cgcx: &CodegenContext,
                  CODEGEN_WORK_PACKAGE_KIND,
                  "generate lto")
     }).unwrap_or(Timeline::noop());
+    let _trace_span = cgcx.trace.as_ref().map(|trace| {
+        trace.span("generate lto".to_string(), "llvm")
+    });
     let (lto_modules, copy_jobs) = lto::run(cgcx, modules, import_only_modules, &mut timeline)
         .unwrap_or_else(|e| e.raise());
 
@@ -1616,6 +1622,7 @@ fn start_executing_work(tcx: TyCtxt,
         coordinator_send,
         diag_emitter: shared_emitter.clone(),
         time_graph,
+        trace: sess.trace_recorder(),
         output_filenames: tcx.output_filenames(LOCAL_CRATE),
         regular_module_config: modules_config,
         metadata_module_config: metadata_config,
@@ -2142,6 +2149,7 @@ fn spawn_work(cgcx: CodegenContext, work: WorkItem) {
                          &work.name())
             });
             let mut timeline = timeline.unwrap_or(Timeline::noop());
+            let _trace_span = cgcx.trace.as_ref().map(|trace| trace.span(work.name(), "llvm"));
             execute_work_item(&cgcx, work, &mut timeline).ok()
         };
     });
@@ -764,6 +764,14 @@ pub fn codegen_crate<'a, 'tcx>(tcx: TyCtxt<'a, 'tcx, 'tcx>,
     let metadata = time(tcx.sess, "write metadata", || {
         write_metadata(tcx, &metadata_llvm_module)
     });
+    if tcx.sess.opts.debugging_opts.pipelined_metadata {
+        time(tcx.sess, "emit pipelined metadata", || {
+            ::back::link::emit_pipelined_metadata(tcx.sess,
+                                                  &metadata,
+                                                  &tcx.output_filenames(LOCAL_CRATE),
+                                                  &tcx.crate_name(LOCAL_CRATE).as_str())
+        });
+    }
     tcx.sess.profiler(|p| p.end_activity(ProfileCategory::Codegen));
 
     let metadata_module = ModuleCodegen {
@@ -874,6 +882,7 @@ pub fn codegen_crate<'a, 'tcx>(tcx: TyCtxt<'a, 'tcx, 'tcx>,
 
     let mut total_codegen_time = Duration::new(0, 0);
     let mut all_stats = Stats::default();
+    let trace = tcx.sess.trace_recorder();
 
     for cgu in codegen_units.into_iter() {
         ongoing_codegen.wait_for_signal_to_codegen_item();
@@ -889,6 +898,9 @@ pub fn codegen_crate<'a, 'tcx>(tcx: TyCtxt<'a, 'tcx, 'tcx>,
                                      write::CODEGEN_WORK_PACKAGE_KIND,
                                      &format!("codegen {}", cgu.name()))
                 });
+                let _trace_span = trace.as_ref().map(|trace| {
+                    trace.span(format!("codegen {}", cgu.name()), "codegen")
+                });
                 let start_time = Instant::now();
                 let stats = compile_codegen_unit(tcx, *cgu.name());
                 all_stats.extend(stats);
@@ -39,6 +39,7 @@ use rustc_plugin::registry::Registry;
 use rustc_plugin as plugin;
 use rustc_passes::{self, ast_validation, hir_stats, loops, rvalue_promotion};
 use rustc_yk_sections::mir_cfg::emit_mir_cfg_section;
+use rustc_yk_sections::promote::emit_promote_section;
 use rustc::util::nodemap::DefIdSet;
 use super::Compilation;
 
@@ -346,8 +347,9 @@ pub fn compile_input(
                     .borrow()
                     .contains(&config::CrateType::Executable);
                 if is_exe {
-                    tcx.sess.yk_link_objects.borrow_mut()
-                       .push(emit_mir_cfg_section(&tcx, &def_ids));
+                    let mut yk_link_objects = tcx.sess.yk_link_objects.borrow_mut();
+                    yk_link_objects.push(emit_mir_cfg_section(&tcx, &def_ids));
+                    yk_link_objects.push(emit_promote_section(&tcx, &def_ids));
                 }
 
                 Ok((outputs.clone(), ongoing_codegen, tcx.dep_graph.clone()))
@@ -373,6 +375,10 @@ pub fn compile_input(
         }
     }
 
+    if let Some(ref path) = sess.opts.debugging_opts.self_profile_trace {
+        sess.save_trace_events(path);
+    }
+
     controller_entry_point!(
         compilation_done,
         sess,
@@ -1339,9 +1345,9 @@ where
             });
 
             time(sess, "MIR effect checking", || {
-                for def_id in tcx.body_owners() {
+                tcx.par_body_owners(|def_id| {
                     mir::transform::check_unsafety::check_unsafety(tcx, def_id)
-                }
+                })
             });
             // Avoid overwhelming user with errors if type checking failed.
             // I'm not sure how helpful this is, to be honest, but it avoids
@@ -1361,6 +1367,22 @@ where
 
             time(sess, "lint checking", || lint::check_crate(tcx));
 
+            // With several query threads, optimize the MIR of all functions
+            // up front, in parallel, rather than one at a time as codegen
+            // reaches them.
+            if sess.query_threads() > 1 &&
+               sess.opts.output_types.should_codegen() &&
+               sess.err_count() == 0 {
+                time(sess, "MIR optimization", || {
+                    tcx.par_body_owners(|def_id| {
+                        let node_id = tcx.hir.as_local_node_id(def_id).unwrap();
+                        if let hir::BodyOwnerKind::Fn = tcx.hir.body_owner_kind(node_id) {
+                            tcx.optimized_mir(def_id);
+                        }
+                    });
+                });
+            }
+
             return Ok(f(tcx, analysis, rx, tcx.sess.compile_status()));
         },
     )
@@ -543,15 +543,21 @@ fn run_compiler_with_pool<'a>(
 
         let control = callbacks.build_controller(&sess, &matches);
 
-        driver::compile_input(codegen_backend,
-                              &sess,
-                              &cstore,
-                              &input_file_path,
-                              &input,
-                              &odir,
-                              &ofile,
-                              Some(plugins),
-                              &control)
+        let result = driver::compile_input(codegen_backend,
+                                           &sess,
+                                           &cstore,
+                                           &input_file_path,
+                                           &input,
+                                           &odir,
+                                           &ofile,
+                                           Some(plugins),
+                                           &control);
+
+        // Suggestions are applied even if compilation failed, as many of them
+        // fix errors.
+        sess.apply_suggestions();
+
+        result
     };
 
     (result, Some(sess))
@@ -841,6 +847,11 @@ impl<'a> CompilerCalls<'a> for RustcDefaultCalls {
                     describe_lints(&sess, &ls, false);
                     return None;
                 }
+                if sopts.debugging_opts.incremental_cache_compact ||
+                   sopts.debugging_opts.incremental_cache_report {
+                    manage_incr_comp_cache(&sess);
+                    return None;
+                }
                 rustc_lint::register_builtins(&mut sess.lint_store.borrow_mut(), Some(&sess));
                 let mut cfg = config::build_configuration(&sess, cfg.clone());
                 let codegen_backend = get_codegen_backend(&sess);
@@ -1182,6 +1193,29 @@ the command line flag directly.
 ");
 }
 
+/// Handles `-Z incremental-cache-compact` and `-Z incremental-cache-report`,
+/// compacting the incremental compilation directory before reporting on it.
+fn manage_incr_comp_cache(sess: &Session) {
+    if sess.opts.incremental.is_none() {
+        early_error(sess.opts.error_format,
+                    "-Z incremental-cache-compact and -Z incremental-cache-report \
+                     require -C incremental");
+    }
+    if sess.opts.debugging_opts.incremental_cache_compact {
+        if let Err(err) = rustc_incremental::compact_incr_comp_cache(sess) {
+            sess.err(&format!("failed to compact the incremental compilation directory: {}",
+                              err));
+        }
+    }
+    if sess.opts.debugging_opts.incremental_cache_report {
+        if let Err(err) = rustc_incremental::report_incr_comp_cache_usage(sess) {
+            sess.err(&format!("failed to read the incremental compilation directory: {}",
+                              err));
+        }
+    }
+    sess.abort_if_errors();
+}
+
 fn describe_lints(sess: &Session, lint_store: &lint::LintStore, loaded_plugins: bool) {
     println!("
 Available lint options:
@@ -17,7 +17,7 @@ use snippet::{Annotation, AnnotationType, Line, MultilineAnnotation, StyledStrin
 use styled_buffer::StyledBuffer;
 
 use rustc_data_structures::fx::FxHashMap;
-use rustc_data_structures::sync::Lrc;
+use rustc_data_structures::sync::{self, Lrc};
 use atty;
 use std::borrow::Cow;
 use std::io::prelude::*;
@@ -40,6 +40,31 @@ pub trait Emitter {
     }
 }
 
+/// Emits each diagnostic with two emitters, e.g. to write diagnostics to a file
+/// while still showing them on the terminal.
+pub struct TeeEmitter {
+    first: Box<dyn Emitter + sync::Send>,
+    second: Box<dyn Emitter + sync::Send>,
+}
+
+impl TeeEmitter {
+    pub fn new(first: Box<dyn Emitter + sync::Send>,
+               second: Box<dyn Emitter + sync::Send>) -> TeeEmitter {
+        TeeEmitter { first, second }
+    }
+}
+
+impl Emitter for TeeEmitter {
+    fn emit(&mut self, db: &DiagnosticBuilder) {
+        self.first.emit(db);
+        self.second.emit(db);
+    }
+
+    fn should_show_explain(&self) -> bool {
+        self.first.should_show_explain()
+    }
+}
+
 impl Emitter for EmitterWriter {
     fn emit(&mut self, db: &DiagnosticBuilder) {
         let mut primary_span = db.span.clone();
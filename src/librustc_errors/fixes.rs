@@ -0,0 +1,435 @@
+// Copyright 2018 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Applying the machine-applicable suggestions of a session to its source
+//! files.
+//!
+//! A `SuggestionCollector` records the suggestions as they are emitted, and
+//! `Fixes::new` turns them into edits of each file. A suggestion is applied
+//! whole or not at all: one which overlaps a suggestion collected before it is
+//! a conflict, and is left for the next run.
+
+use emitter::Emitter;
+use {Applicability, DiagnosticBuilder, SourceMapperDyn, Substitution};
+
+use rustc_data_structures::sync::{self, Lrc, Lock};
+use syntax_pos::FileName;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// The lines of context around each hunk of a diff.
+const CONTEXT: usize = 3;
+
+pub type CollectedSuggestions = Lrc<Lock<Vec<Substitution>>>;
+
+/// Records the machine-applicable suggestions of the diagnostics it emits.
+pub struct SuggestionCollector {
+    emitter: Box<dyn Emitter + sync::Send>,
+    suggestions: CollectedSuggestions,
+}
+
+impl SuggestionCollector {
+    pub fn new(emitter: Box<dyn Emitter + sync::Send>,
+               suggestions: CollectedSuggestions) -> SuggestionCollector {
+        SuggestionCollector { emitter, suggestions }
+    }
+}
+
+impl Emitter for SuggestionCollector {
+    fn emit(&mut self, db: &DiagnosticBuilder) {
+        for sugg in &db.suggestions {
+            // With several alternatives, there is no telling which to apply.
+            if sugg.applicability == Applicability::MachineApplicable &&
+               sugg.substitutions.len() == 1 {
+                self.suggestions.lock().push(sugg.substitutions[0].clone());
+            }
+        }
+        self.emitter.emit(db);
+    }
+
+    fn should_show_explain(&self) -> bool {
+        self.emitter.should_show_explain()
+    }
+}
+
+/// A replacement of the bytes `lo..hi` of a file.
+#[derive(Clone, Debug, PartialEq)]
+struct Edit {
+    lo: usize,
+    hi: usize,
+    snippet: String,
+}
+
+impl Edit {
+    /// Two insertions at the same place conflict too, as they could be
+    /// applied in either order.
+    fn overlaps(&self, other: &Edit) -> bool {
+        (self.lo < other.hi && other.lo < self.hi) || self.lo == other.lo
+    }
+}
+
+/// The edits of one source file.
+pub struct FileFix {
+    pub path: PathBuf,
+    /// The source the edits apply to, as it was compiled.
+    pub src: Lrc<String>,
+    /// Sorted, and not overlapping.
+    edits: Vec<Edit>,
+}
+
+pub struct Fixes {
+    pub files: Vec<FileFix>,
+    /// Suggestions which overlap one collected before them.
+    pub conflicts: Vec<Substitution>,
+    /// Suggestions which can't be applied, e.g. because they span several
+    /// files, or a file which isn't on disk.
+    pub skipped: usize,
+}
+
+impl Fixes {
+    pub fn new(cm: &SourceMapperDyn, suggestions: &[Substitution]) -> Fixes {
+        let mut files: BTreeMap<PathBuf, (Lrc<String>, Vec<Edit>)> = BTreeMap::new();
+        let mut applied: Vec<&Substitution> = Vec::new();
+        let mut conflicts = Vec::new();
+        let mut skipped = 0;
+        'suggestions: for substitution in suggestions {
+            // The same suggestion is often made twice, e.g. by a lint which is
+            // checked for each expansion of a macro.
+            if applied.contains(&substitution) {
+                continue;
+            }
+            let mut edits: Vec<(PathBuf, Lrc<String>, Edit)> = Vec::new();
+            for part in &substitution.parts {
+                if part.span.is_dummy() {
+                    skipped += 1;
+                    continue 'suggestions;
+                }
+                let lo = cm.lookup_char_pos(part.span.lo());
+                let hi = cm.lookup_char_pos(part.span.hi());
+                let file = lo.file;
+                let (path, src) = match (&file.name, &file.src) {
+                    (&FileName::Real(ref path), &Some(ref src))
+                        if !file.name_was_remapped && Lrc::ptr_eq(&file, &hi.file) => {
+                        (path.clone(), src.clone())
+                    }
+                    _ => {
+                        skipped += 1;
+                        continue 'suggestions;
+                    }
+                };
+                let edit = Edit {
+                    lo: (part.span.lo().0 - file.start_pos.0) as usize,
+                    hi: (part.span.hi().0 - file.start_pos.0) as usize,
+                    snippet: part.snippet.clone(),
+                };
+                edits.push((path, src, edit));
+            }
+            let conflict = edits.iter().enumerate().any(|(i, &(ref path, _, ref edit))| {
+                let other_parts = edits[..i].iter()
+                    .any(|&(ref p, _, ref e)| p == path && e.overlaps(edit));
+                let earlier = files.get(path)
+                    .map_or(false, |&(_, ref es)| es.iter().any(|e| e.overlaps(edit)));
+                other_parts || earlier
+            });
+            if conflict {
+                conflicts.push(substitution.clone());
+                continue;
+            }
+            for (path, src, edit) in edits {
+                files.entry(path).or_insert_with(|| (src, Vec::new())).1.push(edit);
+            }
+            applied.push(substitution);
+        }
+
+        let files = files.into_iter().map(|(path, (src, mut edits))| {
+            edits.sort_by_key(|edit| edit.lo);
+            FileFix { path, src, edits }
+        }).collect();
+        Fixes { files, conflicts, skipped }
+    }
+}
+
+impl FileFix {
+    /// The source with the edits applied.
+    pub fn fixed(&self) -> String {
+        apply(&self.src, 0, &self.edits)
+    }
+
+    /// Writes the fixed source to the file, unless it changed since it was
+    /// compiled.
+    pub fn write(&self) -> io::Result<()> {
+        let on_disk = fs::read_to_string(&self.path)?;
+        // The source map doesn't keep the byte order mark.
+        let bom = if on_disk.starts_with('\u{feff}') { "\u{feff}" } else { "" };
+        if on_disk[bom.len()..] != self.src[..] {
+            return Err(io::Error::new(io::ErrorKind::Other,
+                                      "the file changed since it was compiled"));
+        }
+        fs::write(&self.path, format!("{}{}", bom, self.fixed()))
+    }
+
+    /// The edits as a unified diff.
+    pub fn diff(&self) -> String {
+        let src = &self.src[..];
+        let mut starts = vec![0];
+        starts.extend(src.match_indices('\n').map(|(i, _)| i + 1).filter(|&i| i < src.len()));
+        let line_of = |pos: usize| match starts.binary_search(&pos) {
+            Ok(line) => line,
+            Err(line) => line - 1,
+        };
+        let line_end = |line: usize| starts.get(line + 1).cloned().unwrap_or(src.len());
+
+        // The edits of each run of changed lines.
+        let mut blocks: Vec<(usize, usize, Vec<Edit>)> = Vec::new();
+        for edit in &self.edits {
+            let first = line_of(edit.lo);
+            let last = if edit.hi > edit.lo { line_of(edit.hi - 1) } else { first };
+            if let Some(block) = blocks.last_mut() {
+                if first <= block.1 {
+                    block.1 = ::std::cmp::max(block.1, last);
+                    block.2.push(edit.clone());
+                    continue;
+                }
+            }
+            blocks.push((first, last, vec![edit.clone()]));
+        }
+
+        let mut diff = format!("--- {}\n+++ {}\n", self.path.display(), self.path.display());
+        // The number of lines added, less those removed, by the previous hunks.
+        let mut delta: isize = 0;
+        let mut i = 0;
+        while i < blocks.len() {
+            // Blocks close enough for their context to meet share a hunk.
+            let mut j = i + 1;
+            while j < blocks.len() && blocks[j].0 - blocks[j - 1].1 <= 2 * CONTEXT + 1 {
+                j += 1;
+            }
+            let from = blocks[i].0.saturating_sub(CONTEXT);
+            let to = ::std::cmp::min(blocks[j - 1].1 + CONTEXT + 1, starts.len());
+
+            let mut body = String::new();
+            let (mut old_len, mut new_len) = (0, 0);
+            let mut line = from;
+            for &(first, last, ref edits) in &blocks[i..j] {
+                for context in line..first {
+                    push_line(&mut body, ' ', &src[starts[context]..line_end(context)]);
+                }
+                let old = &src[starts[first]..line_end(last)];
+                let new = apply(old, starts[first], edits);
+                for old_line in lines(old) {
+                    push_line(&mut body, '-', old_line);
+                    old_len += 1;
+                }
+                for new_line in lines(&new) {
+                    push_line(&mut body, '+', new_line);
+                    new_len += 1;
+                }
+                old_len += first - line;
+                new_len += first - line;
+                line = last + 1;
+            }
+            for context in line..to {
+                push_line(&mut body, ' ', &src[starts[context]..line_end(context)]);
+            }
+            old_len += to - line;
+            new_len += to - line;
+
+            let new_from = (from as isize + delta) as usize;
+            diff.push_str(&format!("@@ -{} +{} @@\n",
+                                   hunk_range(from, old_len),
+                                   hunk_range(new_from, new_len)));
+            diff.push_str(&body);
+            delta += new_len as isize - old_len as isize;
+            i = j;
+        }
+        diff
+    }
+}
+
+/// Applies `edits` to `src`, which starts at byte `offset` of its file.
+fn apply(src: &str, offset: usize, edits: &[Edit]) -> String {
+    let mut fixed = String::with_capacity(src.len());
+    let mut pos = 0;
+    for edit in edits {
+        fixed.push_str(&src[pos..edit.lo - offset]);
+        fixed.push_str(&edit.snippet);
+        pos = edit.hi - offset;
+    }
+    fixed.push_str(&src[pos..]);
+    fixed
+}
+
+/// The lines of `s`, with their line endings.
+fn lines(s: &str) -> Vec<&str> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for (i, _) in s.match_indices('\n') {
+        lines.push(&s[start..i + 1]);
+        start = i + 1;
+    }
+    if start < s.len() {
+        lines.push(&s[start..]);
+    }
+    lines
+}
+
+fn push_line(body: &mut String, prefix: char, line: &str) {
+    body.push(prefix);
+    body.push_str(line);
+    if !line.ends_with('\n') {
+        body.push_str("\n\\ No newline at end of file\n");
+    }
+}
+
+/// The `start,count` of a hunk header, where `start` is 1-based, or the line
+/// before the hunk if it is empty.
+fn hunk_range(from: usize, len: usize) -> String {
+    if len == 0 {
+        format!("{},0", from)
+    } else {
+        format!("{},{}", from + 1, len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {SourceMapper, SubstitutionPart};
+    use syntax_pos::{BytePos, CharPos, FileLinesResult, Loc, SourceFile, Span, NO_EXPANSION};
+    use std::env;
+    use std::process;
+
+    /// A source map of a single file, which starts at byte 1 so that no span
+    /// of it is a dummy.
+    struct OneFile(Lrc<SourceFile>);
+
+    impl OneFile {
+        fn new(path: &str, src: &str) -> OneFile {
+            let name = FileName::Real(PathBuf::from(path));
+            OneFile(Lrc::new(SourceFile::new(name.clone(), false, name, src.to_string(),
+                                             BytePos(1))))
+        }
+
+        fn part(&self, lo: u32, hi: u32, snippet: &str) -> SubstitutionPart {
+            let start = self.0.start_pos;
+            SubstitutionPart {
+                span: Span::new(start + BytePos(lo), start + BytePos(hi), NO_EXPANSION),
+                snippet: snippet.to_string(),
+            }
+        }
+
+        fn sugg(&self, lo: u32, hi: u32, snippet: &str) -> Substitution {
+            Substitution { parts: vec![self.part(lo, hi, snippet)] }
+        }
+    }
+
+    impl SourceMapper for OneFile {
+        fn lookup_char_pos(&self, pos: BytePos) -> Loc {
+            let line = self.0.lookup_line(pos).unwrap();
+            let col = CharPos((pos - self.0.lines[line]).0 as usize);
+            Loc { file: self.0.clone(), line: line + 1, col, col_display: col.0 }
+        }
+        fn span_to_lines(&self, _: Span) -> FileLinesResult { unimplemented!() }
+        fn span_to_string(&self, _: Span) -> String { unimplemented!() }
+        fn span_to_filename(&self, _: Span) -> FileName { unimplemented!() }
+        fn merge_spans(&self, _: Span, _: Span) -> Option<Span> { unimplemented!() }
+        fn call_span_if_macro(&self, sp: Span) -> Span { sp }
+        fn ensure_source_file_source_present(&self, _: Lrc<SourceFile>) -> bool { true }
+        fn doctest_offset_line(&self, line: usize) -> usize { line }
+    }
+
+    fn fixes(cm: OneFile, suggestions: &[Substitution]) -> Fixes {
+        let cm: Lrc<SourceMapperDyn> = Lrc::new(cm);
+        Fixes::new(&*cm, suggestions)
+    }
+
+    #[test]
+    fn overlapping_suggestions_conflict() {
+        let cm = OneFile::new("foo.rs", "let x = 1;\n");
+        let first = cm.sugg(4, 5, "_x");
+        let overlapping = cm.sugg(4, 9, "_y = 2");
+        let adjacent = cm.sugg(5, 5, ": u8");
+        let fixes = fixes(cm, &[first, overlapping.clone(), adjacent]);
+        assert_eq!(fixes.conflicts, vec![overlapping]);
+        assert_eq!(fixes.skipped, 0);
+        assert_eq!(fixes.files.len(), 1);
+        assert_eq!(fixes.files[0].fixed(), "let _x: u8 = 1;\n");
+    }
+
+    #[test]
+    fn insertions_at_the_same_place_conflict() {
+        let cm = OneFile::new("foo.rs", "fn f() {}\n");
+        let first = cm.sugg(0, 0, "pub ");
+        let second = cm.sugg(0, 0, "unsafe ");
+        assert!(Edit { lo: 0, hi: 0, snippet: String::new() }
+                    .overlaps(&Edit { lo: 0, hi: 0, snippet: String::new() }));
+        let fixes = fixes(cm, &[first.clone(), first, second.clone()]);
+        assert_eq!(fixes.conflicts, vec![second]);
+        assert_eq!(fixes.files[0].fixed(), "pub fn f() {}\n");
+    }
+
+    #[test]
+    fn dummy_spans_are_skipped() {
+        let cm = OneFile::new("foo.rs", "fn f() {}\n");
+        let dummy = Substitution {
+            parts: vec![SubstitutionPart { span: Span::new(BytePos(0), BytePos(0), NO_EXPANSION),
+                                           snippet: "pub ".to_string() }],
+        };
+        let fixes = fixes(cm, &[dummy]);
+        assert_eq!(fixes.skipped, 1);
+        assert!(fixes.files.is_empty());
+    }
+
+    #[test]
+    fn diff_with_several_hunks() {
+        let cm = OneFile::new("foo.rs", "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\nm\nn\no\np\n");
+        let first = cm.sugg(2, 3, "B");
+        let second = cm.sugg(24, 25, "M1\nM2");
+        let fixes = fixes(cm, &[second, first]);
+        assert_eq!(fixes.files[0].diff(), "\
+--- foo.rs
++++ foo.rs
+@@ -1,5 +1,5 @@
+ a
+-b
++B
+ c
+ d
+ e
+@@ -10,7 +10,8 @@
+ j
+ k
+ l
+-m
++M1
++M2
+ n
+ o
+ p
+");
+    }
+
+    #[test]
+    fn no_write_if_the_file_changed() {
+        let path = env::temp_dir().join(format!("fixes-changed-{}.rs", process::id()));
+        fs::write(&path, "let y = 1;\n").unwrap();
+        let cm = OneFile::new(path.to_str().unwrap(), "let x = 1;\n");
+        let sugg = cm.sugg(4, 5, "_x");
+        let fixes = fixes(cm, &[sugg]);
+        let result = fixes.files[0].write();
+        let on_disk = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::Other);
+        assert_eq!(on_disk, "let y = 1;\n");
+    }
+}
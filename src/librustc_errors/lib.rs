@@ -52,6 +52,7 @@ use termcolor::{ColorSpec, Color};
 mod diagnostic;
 mod diagnostic_builder;
 pub mod emitter;
+pub mod fixes;
 mod snippet;
 pub mod registry;
 mod styled_buffer;
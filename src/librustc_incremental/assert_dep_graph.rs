@@ -10,7 +10,8 @@
 
 //! This pass is only used for the UNIT TESTS and DEBUGGING NEEDS
 //! around dependency graph construction. It serves two purposes; it
-//! will dump graphs in graphviz form to disk, and it searches for
+//! will dump graphs in graphviz form to disk (or export or query them, see
+//! `rustc::dep_graph::export`), and it searches for
 //! `#[rustc_if_this_changed]` and `#[rustc_then_this_would_need]`
 //! annotations. These annotations can be used to test whether paths
 //! exist in the graph. These checks run after codegen, so they view the
@@ -49,6 +50,7 @@ use rustc::dep_graph::debug::{DepNodeFilter, EdgeFilter};
 use rustc::hir::def_id::DefId;
 use rustc::ty::TyCtxt;
 use rustc_data_structures::fx::FxHashSet;
+use rustc_serialize::json::as_pretty_json;
 use rustc_data_structures::graph::implementation::{
     Direction, INCOMING, OUTGOING, NodeIndex
 };
@@ -58,6 +60,7 @@ use rustc::ich::{ATTR_IF_THIS_CHANGED, ATTR_THEN_THIS_WOULD_NEED};
 use std::env;
 use std::fs::{self, File};
 use std::io::Write;
+use std::path::Path;
 use syntax::ast;
 use syntax_pos::Span;
 
@@ -67,6 +70,14 @@ pub fn assert_dep_graph<'a, 'tcx>(tcx: TyCtxt<'a, 'tcx, 'tcx>) {
             dump_graph(tcx);
         }
 
+        if let Some(ref path) = tcx.sess.opts.debugging_opts.dep_graph_export {
+            export_graph(tcx, path);
+        }
+
+        if let Some(ref query) = tcx.sess.opts.debugging_opts.dep_graph_query {
+            query_graph(tcx, query);
+        }
+
         // if the `rustc_attrs` feature is not enabled, then the
         // attributes we are interested in cannot be present anyway, so
         // skip the walk.
@@ -265,6 +276,26 @@ fn dump_graph(tcx: TyCtxt) {
     }
 }
 
+fn export_graph(tcx: TyCtxt, path: &Path) {
+    let graph = tcx.dep_graph.export();
+    let json = format!("{}\n", as_pretty_json(&graph));
+    if let Err(err) = fs::write(path, json) {
+        tcx.sess.err(&format!("failed to write dep-graph export `{}`: {}",
+                              path.display(), err));
+    }
+}
+
+fn query_graph(tcx: TyCtxt, query: &str) {
+    match tcx.dep_graph.export().query(query) {
+        Ok(lines) => {
+            for line in lines {
+                println!("{}", line);
+            }
+        }
+        Err(err) => tcx.sess.err(&err),
+    }
+}
+
 pub struct GraphvizDepGraph<'q>(FxHashSet<&'q DepNode>,
                                 Vec<(&'q DepNode, &'q DepNode)>);
 
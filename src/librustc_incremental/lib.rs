@@ -48,3 +48,5 @@ pub use persist::prepare_session_directory;
 pub use persist::finalize_session_directory;
 pub use persist::delete_workproduct_files;
 pub use persist::garbage_collect_session_directories;
+pub use persist::compact_incr_comp_cache;
+pub use persist::report_incr_comp_cache_usage;
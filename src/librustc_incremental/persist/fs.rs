@@ -100,6 +100,36 @@
 //! so, it will also place a read lock on that the respective session directory
 //! so that it won't be deleted while the metadata hashes are loaded.
 //!
+//! ## Seeding from a Shared Cache
+//!
+//! With `-Z incremental-seed=<dir>`, a crate which has no finalized session in
+//! the incremental compilation directory yet starts from its most recent
+//! session in `<dir>`, which is laid out like an incremental compilation
+//! directory. The seed is only ever read: the session is copied into the new
+//! session directory, which then goes through the usual life cycle. This lets
+//! a build machine ship a warm cache to other machines. For the cached data
+//! to be reused there, the crate must be built the same way, and any absolute
+//! paths to its source files must be mapped to the same names with
+//! `--remap-path-prefix`: source files are identified by their remapped names
+//! in the cache, and work products are stored relative to the session
+//! directory.
+//!
+//! ## Size and Age Limits
+//!
+//! Garbage collection only ever removes the sessions of the crate being
+//! compiled, so the sessions of crates which are no longer compiled, or of old
+//! versions of a crate, stay around forever. With `-Z incremental-cache-limit`
+//! and `-Z incremental-cache-max-age`, finalizing a session directory also
+//! evicts the finalized sessions of any crate in the incremental compilation
+//! directory, least recently used first, until the directory is no larger
+//! than the given size and contains no session older than the given number of
+//! days. Sessions locked by another compiler process are left alone, as is the
+//! one just finalized.
+//!
+//! `-Z incremental-cache-report` prints how much space each crate takes up,
+//! and `-Z incremental-cache-compact` garbage collects every crate directory
+//! and applies the limits, both without compiling anything.
+//!
 //! ## Preconditions
 //!
 //! This system relies on two features being available in the file system in
@@ -120,6 +150,7 @@ use rustc_data_structures::{flock, base_n};
 use rustc_data_structures::fx::{FxHashSet, FxHashMap};
 use rustc_data_structures::svh::Svh;
 
+use std::cmp;
 use std::fs as std_fs;
 use std::io;
 use std::mem;
@@ -249,11 +280,15 @@ pub fn prepare_session_directory(sess: &Session,
         let source_directory = if let Some(dir) = source_directory {
             dir
         } else {
-            // There's nowhere to copy from, we're done
-            debug!("no source directory found. Continuing with empty session \
-                    directory.");
+            // There's nowhere to copy from locally, but there may be a
+            // session to start from in the seed directory
+            let seeded = seed_session_directory(sess, &crate_dir, &session_dir);
+            if !seeded {
+                debug!("no source directory found. Continuing with empty session \
+                        directory.");
+            }
 
-            sess.init_incr_comp_session(session_dir, directory_lock, false);
+            sess.init_incr_comp_session(session_dir, directory_lock, seeded);
             return
         };
 
@@ -384,6 +419,12 @@ pub fn finalize_session_directory(sess: &Session, svh: Svh) {
     }
 
     let _ = garbage_collect_session_directories(sess);
+
+    let session_directory = sess.incr_comp_session_dir().clone();
+    if let Err(err) = enforce_cache_limits(sess, Some(&session_directory)) {
+        sess.warn(&format!("Failed to apply the limits of the incremental \
+                            compilation directory: {}", err));
+    }
 }
 
 pub fn delete_all_session_dir_contents(sess: &Session) -> io::Result<()> {
@@ -455,6 +496,91 @@ fn copy_files(sess: &Session,
     Ok(files_linked > 0 || files_copied == 0)
 }
 
+/// Copies the crate's most recent session from the `-Z incremental-seed`
+/// directory, if one is given and it has a session for the crate, into the
+/// new session directory `session_dir`, returning whether it did so.
+///
+/// The seed directory is treated as read-only: its lock files are not used,
+/// so it must not be modified while compilers are reading from it, only
+/// replaced as a whole.
+fn seed_session_directory(sess: &Session, crate_dir: &Path, session_dir: &Path) -> bool {
+    let seed_dir = match sess.opts.debugging_opts.incremental_seed {
+        Some(ref seed_dir) => seed_dir,
+        None => return false,
+    };
+
+    // The crate directory is named after the crate and its disambiguator, so
+    // it's the same in the seed directory.
+    let seed_crate_dir = seed_dir.join(crate_dir.file_name().unwrap());
+    let iter = match seed_crate_dir.read_dir() {
+        Ok(iter) => iter.filter_map(|e| e.ok().map(|e| e.path())),
+        Err(_) => {
+            debug!("seed_session_directory() - no crate directory `{}`",
+                   seed_crate_dir.display());
+            return false
+        }
+    };
+    let source_directory = match find_source_directory_in_iter(iter, &FxHashSet()) {
+        Some(source_directory) => source_directory,
+        None => return false,
+    };
+
+    debug!("seed_session_directory() - copying data from seed: {}",
+           source_directory.display());
+
+    match copy_seed_files(session_dir, &source_directory) {
+        Ok(files_copied) => {
+            if sess.opts.debugging_opts.incremental_info {
+                println!("[incremental] session directory: \
+                          {} files copied from seed `{}`",
+                         files_copied,
+                         source_directory.display());
+            }
+            true
+        }
+        Err(err) => {
+            sess.warn(&format!("Failed to copy incremental compilation session \
+                                directory `{}` from the seed directory: {}",
+                               source_directory.display(),
+                               err));
+
+            // Don't start from a partial copy
+            if let Err(err) = safe_remove_dir_all(session_dir)
+                .and_then(|_| std_fs::create_dir(session_dir)) {
+                sess.fatal(&format!("Failed to clear incremental compilation \
+                                     session directory `{}`: {}",
+                                    session_dir.display(),
+                                    err));
+            }
+            false
+        }
+    }
+}
+
+/// Copies the files of the seed session directory `source_dir` into
+/// `target_dir`. They are copied rather than hard-linked, and made writable,
+/// so that they are independent of the seed directory.
+fn copy_seed_files(target_dir: &Path, source_dir: &Path) -> io::Result<usize> {
+    let mut files_copied = 0;
+
+    for entry in source_dir.read_dir()? {
+        let entry = entry?;
+        let target_file_path = target_dir.join(entry.file_name());
+
+        debug!("copying into session dir: {}", entry.path().display());
+        std_fs::copy(entry.path(), &target_file_path)?;
+
+        let mut permissions = std_fs::metadata(&target_file_path)?.permissions();
+        if permissions.readonly() {
+            permissions.set_readonly(false);
+            std_fs::set_permissions(&target_file_path, permissions)?;
+        }
+        files_copied += 1;
+    }
+
+    Ok(files_copied)
+}
+
 /// Generate unique directory path of the form:
 /// {crate_dir}/s-{timestamp}-{random-number}-working
 fn generate_session_dir_path(crate_dir: &Path) -> PathBuf {
@@ -650,7 +776,10 @@ pub fn garbage_collect_session_directories(sess: &Session) -> io::Result<()> {
     debug!("garbage_collect_session_directories() - session directory: {}",
         session_directory.display());
 
-    let crate_directory = session_directory.parent().unwrap();
+    garbage_collect_crate_directory(sess, session_directory.parent().unwrap())
+}
+
+fn garbage_collect_crate_directory(sess: &Session, crate_directory: &Path) -> io::Result<()> {
     debug!("garbage_collect_session_directories() - crate directory: {}",
         crate_directory.display());
 
@@ -863,6 +992,246 @@ pub fn garbage_collect_session_directories(sess: &Session) -> io::Result<()> {
     Ok(())
 }
 
+/// The disk usage of one crate's directory within the incremental compilation
+/// directory.
+struct CrateCacheUsage {
+    directory: PathBuf,
+    /// The finalized session directories of the crate, with their timestamps.
+    sessions: Vec<(SystemTime, PathBuf)>,
+    /// The size of everything in the crate directory.
+    size: u64,
+    /// The size of the work products, e.g. object files, in its sessions.
+    work_products_size: u64,
+}
+
+fn cache_usage(incr_dir: &Path) -> io::Result<Vec<CrateCacheUsage>> {
+    let mut usage = vec![];
+
+    for crate_entry in incr_dir.read_dir()? {
+        let crate_directory = match crate_entry {
+            Ok(crate_entry) => crate_entry.path(),
+            _ => continue,
+        };
+        if !crate_directory.is_dir() {
+            continue
+        }
+
+        let mut crate_usage = CrateCacheUsage {
+            directory: crate_directory.clone(),
+            sessions: vec![],
+            size: 0,
+            work_products_size: 0,
+        };
+        // Another rustc may delete crate and session directories while they
+        // are looked at, in which case they are skipped.
+        let dir_entries = match crate_directory.read_dir() {
+            Ok(dir_entries) => dir_entries,
+            Err(_) => continue,
+        };
+        for dir_entry in dir_entries {
+            let path = match dir_entry {
+                Ok(dir_entry) => dir_entry.path(),
+                _ => continue,
+            };
+            crate_usage.size += disk_usage(&path);
+
+            let directory_name = path.file_name().unwrap().to_string_lossy().into_owned();
+            if !is_session_directory(&directory_name) {
+                continue
+            }
+            let session_entries = match path.read_dir() {
+                Ok(session_entries) => session_entries,
+                Err(_) => continue,
+            };
+            crate_usage.work_products_size += session_entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| {
+                    let file_name = entry.file_name();
+                    file_name != DEP_GRAPH_FILENAME &&
+                    file_name != WORK_PRODUCTS_FILENAME &&
+                    file_name != QUERY_CACHE_FILENAME
+                })
+                .map(|entry| disk_usage(&entry.path()))
+                .sum::<u64>();
+
+            if is_finalized(&directory_name) {
+                if let Ok(timestamp) = extract_timestamp_from_session_dir(&directory_name) {
+                    crate_usage.sessions.push((timestamp, path));
+                }
+            }
+        }
+        usage.push(crate_usage);
+    }
+
+    Ok(usage)
+}
+
+/// The number of bytes taken up by the file or directory at `path`.
+fn disk_usage(path: &Path) -> u64 {
+    let metadata = match std_fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return 0,
+    };
+    if !metadata.is_dir() {
+        return metadata.len()
+    }
+    match path.read_dir() {
+        Ok(entries) => {
+            entries.filter_map(|entry| entry.ok())
+                   .map(|entry| disk_usage(&entry.path()))
+                   .sum()
+        }
+        Err(_) => 0,
+    }
+}
+
+/// Evicts finalized session directories, of any crate, from the incremental
+/// compilation directory until it meets the `-Z incremental-cache-limit` and
+/// `-Z incremental-cache-max-age` limits, if they are given. The session
+/// directory `keep` is never evicted, nor are those in use by other compiler
+/// processes.
+///
+/// A session directory is created each time its crate is compiled, from the
+/// crate's previous session directory, so the least recently used sessions
+/// are the ones with the oldest timestamps, and they are evicted first.
+/// Evicting a session evicts the crate's work products along with it.
+fn enforce_cache_limits(sess: &Session, keep: Option<&Path>) -> io::Result<()> {
+    let size_limit = sess.opts.debugging_opts.incremental_cache_limit;
+    let max_age = sess.opts.debugging_opts.incremental_cache_max_age.map(|days| {
+        Duration::from_secs(days as u64 * 24 * 60 * 60)
+    });
+    if size_limit.is_none() && max_age.is_none() {
+        return Ok(())
+    }
+
+    let usage = cache_usage(sess.opts.incremental.as_ref().unwrap())?;
+    let mut total_size: u64 = usage.iter().map(|crate_usage| crate_usage.size).sum();
+
+    // Session directory names are unique, but the paths we have may not have
+    // been canonicalized like `keep` has.
+    let keep = keep.and_then(|keep| keep.file_name());
+    let mut sessions: Vec<_> = usage.into_iter()
+                                    .flat_map(|crate_usage| crate_usage.sessions)
+                                    .filter(|&(_, ref path)| path.file_name() != keep)
+                                    .collect();
+    sessions.sort();
+
+    let now = SystemTime::now();
+    for (timestamp, path) in sessions {
+        let too_old = max_age.map_or(false, |max_age| {
+            now.duration_since(timestamp).map_or(false, |age| age > max_age)
+        });
+        let too_big = size_limit.map_or(false, |limit| total_size > limit);
+        if !too_old && !too_big {
+            // The sessions are in order of age, so the rest are within the
+            // limits too.
+            break
+        }
+
+        let size = disk_usage(&path);
+        let lock_file_path = lock_file_path(&path);
+        let lock = match flock::Lock::new(&lock_file_path,
+                                          false,  // don't wait
+                                          false,  // don't create the lock-file
+                                          true) { // get an exclusive lock
+            Ok(lock) => lock,
+            Err(_) => {
+                debug!("enforce_cache_limits() - not evicting `{}`, still in use",
+                       path.display());
+                continue
+            }
+        };
+
+        debug!("enforce_cache_limits() - evicting `{}`", path.display());
+        if let Err(err) = safe_remove_dir_all(&path) {
+            sess.warn(&format!("Failed to evict incremental compilation \
+                                session directory `{}`: {}",
+                               path.display(),
+                               err));
+        } else {
+            delete_session_dir_lock_file(sess, &lock_file_path);
+            total_size = total_size.saturating_sub(size);
+        }
+        mem::drop(lock);
+    }
+
+    Ok(())
+}
+
+/// Garbage collects the directory of each crate in the incremental compilation
+/// directory, as happens for a single crate when it is compiled, and then
+/// applies the directory's size and age limits. This is done for
+/// `-Z incremental-cache-compact`.
+pub fn compact_incr_comp_cache(sess: &Session) -> io::Result<()> {
+    let incr_dir = sess.opts.incremental.as_ref().unwrap();
+    for crate_entry in incr_dir.read_dir()? {
+        let crate_directory = match crate_entry {
+            Ok(crate_entry) => crate_entry.path(),
+            _ => continue,
+        };
+        if crate_directory.is_dir() {
+            garbage_collect_crate_directory(sess, &crate_directory)?;
+        }
+    }
+    enforce_cache_limits(sess, None)
+}
+
+/// Prints the disk usage of each crate in the incremental compilation
+/// directory, largest first, for `-Z incremental-cache-report`.
+pub fn report_incr_comp_cache_usage(sess: &Session) -> io::Result<()> {
+    let mut usage = cache_usage(sess.opts.incremental.as_ref().unwrap())?;
+    usage.sort_by_key(|crate_usage| cmp::Reverse(crate_usage.size));
+
+    let now = SystemTime::now();
+    println!("{:<40} {:>8} {:>12} {:>14}  {}",
+             "crate", "sessions", "size", "work products", "last compiled");
+    for crate_usage in &usage {
+        let last_compiled = crate_usage.sessions
+                                       .iter()
+                                       .map(|&(timestamp, _)| timestamp)
+                                       .max()
+                                       .and_then(|timestamp| now.duration_since(timestamp).ok())
+                                       .map(|age| format!("{} ago", format_age(age)))
+                                       .unwrap_or_else(|| "never".to_string());
+        println!("{:<40} {:>8} {:>12} {:>14}  {}",
+                 crate_usage.directory.file_name().unwrap().to_string_lossy(),
+                 crate_usage.sessions.len(),
+                 format_size(crate_usage.size),
+                 format_size(crate_usage.work_products_size),
+                 last_compiled);
+    }
+    println!("{:<40} {:>8} {:>12} {:>14}",
+             "total",
+             usage.iter().map(|crate_usage| crate_usage.sessions.len()).sum::<usize>(),
+             format_size(usage.iter().map(|crate_usage| crate_usage.size).sum()),
+             format_size(usage.iter().map(|crate_usage| crate_usage.work_products_size).sum()));
+    Ok(())
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes)
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    match secs {
+        0 ... 119 => format!("{} seconds", secs),
+        120 ... 7199 => format!("{} minutes", secs / 60),
+        7200 ... 172_799 => format!("{} hours", secs / 3600),
+        _ => format!("{} days", secs / 86400),
+    }
+}
+
 fn all_except_most_recent(deletion_candidates: Vec<(SystemTime, PathBuf, Option<flock::Lock>)>)
                           -> FxHashMap<PathBuf, Option<flock::Lock>> {
     let most_recent = deletion_candidates.iter()
@@ -928,6 +1297,15 @@ fn test_all_except_most_recent() {
     );
 }
 
+#[test]
+fn test_format_size() {
+    assert_eq!(format_size(0), "0 B");
+    assert_eq!(format_size(1023), "1023 B");
+    assert_eq!(format_size(1536), "1.5 KiB");
+    assert_eq!(format_size(10 * 1024 * 1024), "10.0 MiB");
+    assert_eq!(format_size(3 << 40), "3.0 TiB");
+}
+
 #[test]
 fn test_timestamp_serialization() {
     for i in 0 .. 1_000u64 {
@@ -23,6 +23,7 @@ mod terminator;
 mod traits;
 mod validity;
 mod intrinsics;
+mod trace_replay;
 
 pub use self::eval_context::{
     EvalContext, Frame, StackPopCleanup, LocalValue,
@@ -37,3 +38,8 @@ pub use self::machine::{Machine, AllocMap};
 pub use self::operand::{ScalarMaybeUndef, Value, ValTy, Operand, OpTy};
 
 pub use self::validity::RefTracking;
+
+pub use self::trace_replay::{
+    TraceReplayMachine, TraceReplayMemoryKind, TraceLoc, YkTraceRecord, TraceStep, TraceStepKind,
+    Divergence, Replay, replay_trace,
+};
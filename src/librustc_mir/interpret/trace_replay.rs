@@ -0,0 +1,418 @@
+// Copyright 2018 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A machine which replays a Yorick trace.
+//!
+//! A trace is the sequence of basic blocks that a run of the program entered,
+//! across calls and returns. Replaying it interprets the MIR of the traced
+//! function, checking at every block entry that the interpreter went where the
+//! trace says it did, and recording every statement and terminator executed
+//! along the way. The result is the reference semantics for the trace
+//! compiler: no native code is run.
+//!
+//! Only part of the language can be replayed so far. Calling a function
+//! without MIR, such as a foreign function, or an intrinsic the interpreter
+//! does not emulate fails. Pointers can only be compared, subtracted and
+//! offset (see `TraceReplayMachine::ptr_op`), and `box` allocations can only be
+//! freed by dropping the box. All of these are reported as machine errors
+//! rather than divergences.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::error::Error;
+use std::iter;
+
+use rustc::hir::def_id::{DefId, DefIndex, LOCAL_CRATE};
+use rustc::mir;
+use rustc::mir::interpret::{EvalResult, EvalError, EvalErrorKind, Scalar, Allocation, AllocId,
+                            sign_extend, truncate};
+use rustc::ty::{self, TyCtxt, Instance, query::TyCtxtAt};
+use rustc::ty::layout::{LayoutOf, TyLayout};
+use rustc::ty::subst::Subst;
+use rustc_data_structures::fx::FxHashMap;
+
+use super::{EvalContext, Machine, PlaceTy, OpTy, MemoryKind, StackPopCleanup};
+
+/// A basic block entered by the traced program.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TraceLoc {
+    pub def_id: DefId,
+    pub block: mir::BasicBlock,
+}
+
+impl TraceLoc {
+    /// Finds the block that a Yorick trace record refers to, or `None` if the
+    /// record's crate is not loaded by `tcx`.
+    pub fn from_yk_record(tcx: TyCtxt, record: YkTraceRecord) -> Option<TraceLoc> {
+        let krate = iter::once(LOCAL_CRATE)
+            .chain(tcx.crates().iter().cloned())
+            .find(|krate| tcx.crate_hash(*krate).as_u64() == record.crate_hash)?;
+        Some(TraceLoc {
+            def_id: DefId { krate, index: DefIndex::from_raw_u32(record.def_index) },
+            block: mir::BasicBlock::new(record.block as usize),
+        })
+    }
+}
+
+/// A block as the Yorick sections identify it: by the hash of the crate
+/// defining the function, the function's def index, and the block index.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct YkTraceRecord {
+    pub crate_hash: u64,
+    pub def_index: u32,
+    pub block: u32,
+}
+
+impl fmt::Display for TraceLoc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}:{:?}", self.def_id, self.block)
+    }
+}
+
+/// One entry of the linearised trace produced by a replay.
+#[derive(Clone, Debug)]
+pub struct TraceStep<'mir, 'tcx: 'mir> {
+    /// The function whose MIR the step belongs to.
+    pub instance: Instance<'tcx>,
+    /// The position of the step within that MIR.
+    pub location: mir::Location,
+    /// Depth of the call stack, starting at 0 for the traced function.
+    pub depth: usize,
+    pub kind: TraceStepKind<'mir, 'tcx>,
+}
+
+#[derive(Clone, Debug)]
+pub enum TraceStepKind<'mir, 'tcx: 'mir> {
+    Statement(&'mir mir::Statement<'tcx>),
+    Terminator(&'mir mir::Terminator<'tcx>),
+}
+
+/// The first point at which execution and trace disagree.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// The index into the trace at which the disagreement was found.
+    pub position: usize,
+    /// The block the trace expected, or `None` if the trace had already ended.
+    pub expected: Option<TraceLoc>,
+    /// The block actually entered, or `None` if the traced function had
+    /// already returned.
+    pub actual: Option<TraceLoc>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "trace diverged at position {}: ", self.position)?;
+        match (self.expected, self.actual) {
+            (Some(exp), Some(act)) => write!(f, "expected {}, entered {}", exp, act),
+            (Some(exp), None) => write!(f, "expected {}, but the function returned", exp),
+            (None, Some(act)) => write!(f, "trace ended, but execution entered {}", act),
+            (None, None) => write!(f, "no block"),
+        }
+    }
+}
+
+/// The outcome of replaying a trace.
+#[derive(Debug)]
+pub struct Replay<'mir, 'tcx: 'mir> {
+    /// Everything executed before the replay finished or diverged.
+    pub steps: Vec<TraceStep<'mir, 'tcx>>,
+    /// `None` if the function returned exactly as the trace was used up.
+    pub divergence: Option<Divergence>,
+}
+
+#[derive(Clone, Debug)]
+enum TraceReplayError {
+    Unsupported(String),
+}
+
+impl fmt::Display for TraceReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TraceReplayError::Unsupported(ref msg) => {
+                write!(f, "{} is not supported when replaying a trace", msg)
+            }
+        }
+    }
+}
+
+impl Error for TraceReplayError {
+    fn description(&self) -> &str {
+        match *self {
+            TraceReplayError::Unsupported(_) => "unsupported operation in trace replay",
+        }
+    }
+}
+
+impl<'tcx> Into<EvalError<'tcx>> for TraceReplayError {
+    fn into(self) -> EvalError<'tcx> {
+        EvalErrorKind::MachineError(self.to_string()).into()
+    }
+}
+
+/// The machine used to replay traces. Unlike CTFE it calls any function with
+/// MIR, but anything that would need native code is an error.
+pub struct TraceReplayMachine;
+
+/// Memory allocated by the traced code, rather than for its stack frames.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TraceReplayMemoryKind {
+    /// Allocated by `box`, and freed by the `box_free` lang item.
+    Box,
+}
+
+type TraceReplayEvalContext<'a, 'mir, 'tcx> = EvalContext<'a, 'mir, 'tcx, TraceReplayMachine>;
+
+impl<'a, 'mir, 'tcx> Machine<'a, 'mir, 'tcx> for TraceReplayMachine {
+    type MemoryData = ();
+    type MemoryKinds = TraceReplayMemoryKind;
+    type PointerTag = ();
+
+    type MemoryMap = FxHashMap<AllocId, (MemoryKind<TraceReplayMemoryKind>, Allocation<()>)>;
+
+    const STATIC_KIND: Option<TraceReplayMemoryKind> = None;
+
+    #[inline(always)]
+    fn enforce_validity(_ecx: &EvalContext<'a, 'mir, 'tcx, Self>) -> bool {
+        false
+    }
+
+    fn find_fn(
+        ecx: &mut EvalContext<'a, 'mir, 'tcx, Self>,
+        instance: ty::Instance<'tcx>,
+        args: &[OpTy<'tcx>],
+        dest: Option<PlaceTy<'tcx>>,
+        ret: Option<mir::BasicBlock>,
+    ) -> EvalResult<'tcx, Option<&'mir mir::Mir<'tcx>>> {
+        // `box_free` would hand the memory back to the allocator, which has no
+        // MIR, so free it here instead.
+        if Some(instance.def_id()) == ecx.tcx.lang_items().box_free_fn() {
+            let ptr = ecx.read_value(args[0])?.to_scalar_ptr()?.to_ptr()?;
+            let kind = MemoryKind::Machine(TraceReplayMemoryKind::Box);
+            ecx.memory_mut().deallocate(ptr, None, kind)?;
+            ecx.goto_block(ret)?;
+            return Ok(None);
+        }
+        if ecx.hook_fn(instance, args, dest)? {
+            ecx.goto_block(ret)?;
+            return Ok(None);
+        }
+        match ecx.load_mir(instance.def) {
+            Ok(mir) => Ok(Some(mir)),
+            Err(err) => {
+                if let EvalErrorKind::NoMirFor(ref path) = err.kind {
+                    return Err(TraceReplayError::Unsupported(
+                        format!("calling `{}`, which has no MIR,", path)).into());
+                }
+                Err(err)
+            }
+        }
+    }
+
+    fn call_intrinsic(
+        ecx: &mut EvalContext<'a, 'mir, 'tcx, Self>,
+        instance: ty::Instance<'tcx>,
+        args: &[OpTy<'tcx>],
+        dest: PlaceTy<'tcx>,
+    ) -> EvalResult<'tcx> {
+        if ecx.emulate_intrinsic(instance, args, dest)? {
+            return Ok(());
+        }
+        let intrinsic_name = &ecx.tcx.item_name(instance.def_id()).as_str()[..];
+        Err(TraceReplayError::Unsupported(format!("intrinsic `{}`", intrinsic_name)).into())
+    }
+
+    /// Pointers into the same allocation are compared and subtracted by their
+    /// offsets, and pointers into different allocations, or to no allocation,
+    /// are never equal. Integers can be added to and subtracted from pointers,
+    /// and `Offset` moves a pointer by whole elements. Anything else, e.g.
+    /// ordering pointers into different allocations or masking their bits,
+    /// would depend on the addresses chosen by native code, so it is an error.
+    fn ptr_op(
+        ecx: &EvalContext<'a, 'mir, 'tcx, Self>,
+        bin_op: mir::BinOp,
+        left: Scalar,
+        left_layout: TyLayout<'tcx>,
+        right: Scalar,
+        right_layout: TyLayout<'tcx>,
+    ) -> EvalResult<'tcx, (Scalar, bool)> {
+        use rustc::mir::BinOp::*;
+
+        if bin_op == Offset {
+            let pointee = left_layout.ty.builtin_deref(true)
+                .expect("Offset called on non-pointer type").ty;
+            let elem_size = ecx.layout_of(pointee)?.size.bytes() as i64;
+            let count = right.to_bits(right_layout.size)?;
+            let count = sign_extend(count, right_layout.size) as i128 as i64;
+            let offset = count.checked_mul(elem_size).ok_or(EvalErrorKind::Overflow(Mul))?;
+            return Ok((left.ptr_signed_offset(offset, ecx)?, false));
+        }
+
+        match (left, right) {
+            (Scalar::Ptr(l), Scalar::Ptr(r)) if l.alloc_id == r.alloc_id => {
+                let (l, r) = (l.offset.bytes(), r.offset.bytes());
+                let res = match bin_op {
+                    Eq => Scalar::from_bool(l == r),
+                    Ne => Scalar::from_bool(l != r),
+                    Lt => Scalar::from_bool(l < r),
+                    Le => Scalar::from_bool(l <= r),
+                    Gt => Scalar::from_bool(l > r),
+                    Ge => Scalar::from_bool(l >= r),
+                    Sub => {
+                        let (diff, overflowed) = l.overflowing_sub(r);
+                        let size = left_layout.size;
+                        return Ok((Scalar::from_uint(truncate(diff as u128, size), size),
+                                   overflowed));
+                    }
+                    _ => return Err(unsupported_ptr_op(bin_op)),
+                };
+                Ok((res, false))
+            }
+            (Scalar::Ptr(_), Scalar::Ptr(_)) |
+            (Scalar::Ptr(_), Scalar::Bits { bits: 0, .. }) |
+            (Scalar::Bits { bits: 0, .. }, Scalar::Ptr(_)) if bin_op == Eq || bin_op == Ne => {
+                Ok((Scalar::from_bool(bin_op == Ne), false))
+            }
+            (Scalar::Ptr(ptr), Scalar::Bits { bits, .. }) if bin_op == Add || bin_op == Sub => {
+                let bits = truncate(bits, right_layout.size) as u64 as i64;
+                let offset = if bin_op == Add { bits } else { bits.wrapping_neg() };
+                Ok((Scalar::Ptr(ptr.wrapping_signed_offset(offset, ecx)), false))
+            }
+            (Scalar::Bits { bits, .. }, Scalar::Ptr(ptr)) if bin_op == Add => {
+                let bits = truncate(bits, left_layout.size) as u64 as i64;
+                Ok((Scalar::Ptr(ptr.wrapping_signed_offset(bits, ecx)), false))
+            }
+            _ => Err(unsupported_ptr_op(bin_op)),
+        }
+    }
+
+    fn find_foreign_static(
+        _tcx: TyCtxtAt<'a, 'tcx, 'tcx>,
+        _def_id: DefId,
+    ) -> EvalResult<'tcx, Cow<'tcx, Allocation<Self::PointerTag>>> {
+        err!(ReadForeignStatic)
+    }
+
+    #[inline(always)]
+    fn static_with_default_tag(
+        alloc: &'_ Allocation
+    ) -> Cow<'_, Allocation<Self::PointerTag>> {
+        Cow::Borrowed(alloc)
+    }
+
+    fn box_alloc(
+        ecx: &mut EvalContext<'a, 'mir, 'tcx, Self>,
+        dest: PlaceTy<'tcx>,
+    ) -> EvalResult<'tcx> {
+        let layout = ecx.layout_of(dest.layout.ty.boxed_ty())?;
+        let kind = MemoryKind::Machine(TraceReplayMemoryKind::Box);
+        let ptr = ecx.memory_mut().allocate(layout.size, layout.align, kind)?;
+        ecx.write_scalar(Scalar::Ptr(ptr), dest)
+    }
+
+    #[inline(always)]
+    fn before_terminator(_ecx: &mut EvalContext<'a, 'mir, 'tcx, Self>) -> EvalResult<'tcx> {
+        // Block entries are checked by `replay_trace`, which drives the interpreter.
+        Ok(())
+    }
+}
+
+fn unsupported_ptr_op<'tcx>(bin_op: mir::BinOp) -> EvalError<'tcx> {
+    TraceReplayError::Unsupported(format!("`{:?}` on pointers", bin_op)).into()
+}
+
+fn current_loc(ecx: &TraceReplayEvalContext<'_, '_, '_>) -> Option<TraceLoc> {
+    ecx.stack().last().map(|frame| TraceLoc {
+        def_id: frame.instance.def_id(),
+        block: frame.block,
+    })
+}
+
+/// Interprets `instance`, called with `args`, following `trace`.
+///
+/// The first entry of `trace` must be the start block of `instance`. Replay
+/// stops at the first divergence, or when the function returns. Errors are
+/// only returned if the interpreter itself fails, e.g. because the traced
+/// code does something that cannot be done without native code, or uses one
+/// of the unsupported features listed in the module documentation.
+pub fn replay_trace<'a, 'tcx>(
+    tcx: TyCtxt<'a, 'tcx, 'tcx>,
+    instance: Instance<'tcx>,
+    param_env: ty::ParamEnv<'tcx>,
+    args: &[&'tcx ty::Const<'tcx>],
+    trace: &[TraceLoc],
+) -> EvalResult<'tcx, Replay<'tcx, 'tcx>> {
+    debug!("replay_trace: {:?}, {} blocks", instance, trace.len());
+    let span = tcx.def_span(instance.def_id());
+    let mut ecx = EvalContext::new(tcx.at(span), param_env, TraceReplayMachine, ());
+    let mir = ecx.load_mir(instance.def)?;
+    if mir.arg_count != args.len() {
+        return err!(FunctionArgCountMismatch);
+    }
+    let layout = ecx.layout_of(mir.return_ty().subst(tcx, instance.substs))?;
+    let ret = ecx.allocate(layout, MemoryKind::Stack)?;
+    ecx.push_stack_frame(
+        instance,
+        mir.span,
+        mir,
+        Some(ret.into()),
+        StackPopCleanup::None { cleanup: false },
+    )?;
+    // The arguments are the first locals after the return place.
+    for (local, arg) in mir.args_iter().zip(args) {
+        let dest = ecx.eval_place(&mir::Place::Local(local))?;
+        let arg = ecx.const_to_op(arg)?;
+        if arg.layout.ty != dest.layout.ty {
+            return err!(FunctionArgMismatch(dest.layout.ty, arg.layout.ty));
+        }
+        ecx.copy_op(arg, dest)?;
+    }
+
+    let mut steps = Vec::new();
+    let mut position = 0;
+    // Every terminator, including calls and returns, leaves the interpreter at
+    // the start of a block, so the first step after one is a block entry.
+    let mut entering = true;
+    loop {
+        if entering {
+            let actual = current_loc(&ecx);
+            let expected = trace.get(position).cloned();
+            if actual != expected {
+                let divergence = Divergence { position, expected, actual };
+                debug!("replay_trace: {}", divergence);
+                return Ok(Replay { steps, divergence: Some(divergence) });
+            }
+            if actual.is_none() {
+                return Ok(Replay { steps, divergence: None });
+            }
+            position += 1;
+        }
+
+        let step = {
+            let frame = ecx.frame();
+            let block = &frame.mir.basic_blocks()[frame.block];
+            let kind = match block.statements.get(frame.stmt) {
+                Some(stmt) => TraceStepKind::Statement(stmt),
+                None => TraceStepKind::Terminator(block.terminator()),
+            };
+            TraceStep {
+                instance: frame.instance,
+                location: mir::Location { block: frame.block, statement_index: frame.stmt },
+                depth: ecx.cur_frame(),
+                kind,
+            }
+        };
+        entering = match step.kind {
+            TraceStepKind::Terminator(_) => true,
+            TraceStepKind::Statement(_) => false,
+        };
+        ecx.step()?;
+        steps.push(step);
+    }
+}
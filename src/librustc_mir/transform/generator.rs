@@ -531,12 +531,16 @@ fn compute_layout<'a, 'tcx>(tcx: TyCtxt<'a, 'tcx, 'tcx>,
     // Create a map from local indices to generator struct indices.
     // These are offset by (upvar_len + 1) because of fields which comes before locals.
     // We also create a vector of the LocalDecls of these locals.
+    let mut field_locals = Vec::new();
     let (remap, vars) = live_decls.enumerate().map(|(idx, (local, var))| {
+        field_locals.push(local);
         ((local, (var.ty, upvar_len + 1 + idx)), var)
     }).unzip();
 
     let layout = GeneratorLayout {
-        fields: vars
+        fields: vars,
+        field_locals,
+        state_field: upvar_len,
     };
 
     (remap, layout, storage_liveness)
@@ -1,9 +1,16 @@
+#![feature(yk_sections)]
+
 use std::fs;
 use std::path::{PathBuf, Path};
 use std::process::Command;
 
+pub use std::yk::{start_symbol, stop_symbol};
+
 /// An extra ELF object file to link into the resulting binary.
-pub struct YkExtraLinkObject(PathBuf);
+pub struct YkExtraLinkObject {
+    path: PathBuf,
+    section_name: String,
+}
 
 #[cfg(target_arch = "x86_64")]
 const BFD_NAME: &'static str = "elf64-x86-64";
@@ -16,8 +23,20 @@ impl YkExtraLinkObject {
     /// This is achieved by shelling out to the `objcopy(1)` utility to graft the raw data
     /// into a well-formed ELF object file with the data in a section named `sec_name`. The
     /// resulting object is deleted when it falls out of scope.
+    ///
+    /// The section is bracketed by the global symbols returned by `start_symbol()` and
+    /// `stop_symbol()`, so that the runtime can find the data in memory.
     pub fn new(source_path: &Path, section_name: &str) -> Self {
-        let out_filename = format!("{}.o", source_path.to_str().unwrap());
+        let source_str = source_path.to_str().unwrap();
+        let out_filename = format!("{}.o", source_str);
+
+        // `objcopy -I binary` names its symbols after the input path.
+        let binary_sym: String = source_str.chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        let start_arg = format!("_binary_{}_start={}", binary_sym, start_symbol(section_name));
+        let stop_arg = format!("_binary_{}_end={}", binary_sym, stop_symbol(section_name));
+        let size_arg = format!("_binary_{}_size", binary_sym);
 
         let sec_arg = format!(".data={},alloc,load,readonly,data,contents", section_name);
         let mut cmd = Command::new("objcopy");
@@ -26,8 +45,11 @@ impl YkExtraLinkObject {
             "-O", BFD_NAME,
             "-B", BFD_ARCH,
             "--rename-section", &sec_arg,
+            "--redefine-sym", &start_arg,
+            "--redefine-sym", &stop_arg,
+            "--strip-symbol", &size_arg,
             "-j", ".data",
-            source_path.to_str().unwrap(), &out_filename]);
+            source_str, &out_filename]);
 
         let output = cmd.output().unwrap();
         if !output.status.success() {
@@ -35,16 +57,31 @@ impl YkExtraLinkObject {
             eprintln!("stderr: {}", String::from_utf8_lossy(&output.stderr));
             panic!("objcopy failed");
         }
-        YkExtraLinkObject(PathBuf::from(out_filename))
+        YkExtraLinkObject {
+            path: PathBuf::from(out_filename),
+            section_name: String::from(section_name),
+        }
     }
 
     pub fn path(&self) -> &Path {
-        &self.0.as_path()
+        &self.path.as_path()
+    }
+
+    pub fn section_name(&self) -> &str {
+        &self.section_name
+    }
+
+    pub fn start_symbol(&self) -> String {
+        start_symbol(&self.section_name)
+    }
+
+    pub fn stop_symbol(&self) -> String {
+        stop_symbol(&self.section_name)
     }
 }
 
 impl Drop for YkExtraLinkObject {
     fn drop(&mut self) {
-        let _ = fs::remove_file(&self.0);
+        let _ = fs::remove_file(&self.path);
     }
 }
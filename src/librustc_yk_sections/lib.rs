@@ -1,8 +1,13 @@
 #![feature(box_patterns)]
 
 extern crate rustc;
+extern crate rustc_mir;
+extern crate rustc_target;
 extern crate rustc_yk_link;
 extern crate byteorder;
+extern crate flate2;
 extern crate mkstemp;
+extern crate serialize;
 
 pub mod mir_cfg;
+pub mod promote;
@@ -1,19 +1,40 @@
 /// Custom CFG serialiser for Yorick.
 /// At the time of writing no crate using `proc_macro` can be used in-compiler, otherwise we'd have
 /// used Serde.
+///
+/// Two section formats exist. Both start with a native endian `u16` version number, which readers
+/// use to pick a decoder:
+///
+///  - Version 1 is a flat list of records, each starting with the crate hash and def index of the
+///    function it belongs to, followed by a sentinal.
+///  - Version 2 has crate, string and function tables, groups the records by function, encodes
+///    integers as LEB128 and may be compressed.
+///
+/// Fixed size integers are native endian in both versions. The format is chosen with
+/// `-Z yk-section-version` and defaults to the latest version. `-Z yk-compress-sections`
+/// compresses version 2 sections.
 
 
 use rustc::ty::TyCtxt;
 
-use rustc::hir::def_id::DefId;
-use rustc::mir::{Mir, TerminatorKind, Operand, Constant, BasicBlock};
-use rustc::ty::{TyS, TyKind, Const};
-use rustc::util::nodemap::DefIdSet;
+use rustc::hir::def_id::{CrateNum, DefId, LOCAL_CRATE};
+use rustc::mir::{Mir, TerminatorKind, Operand, Constant, Local, Place, ProjectionElem,
+                 Rvalue, StatementKind, GeneratorLayout, START_BLOCK};
+use rustc::ty::{self, TyS, TyKind, Const, Instance, InstanceDef};
+use rustc::ty::fold::TypeFoldable;
+use rustc::util::nodemap::{DefIdSet, FxHashMap};
+use rustc_mir::monomorphize::resolve_drop_in_place;
+use serialize::leb128;
+use std::env;
+use std::iter;
 use std::path::PathBuf;
 use mkstemp::TempFile;
 use rustc_yk_link::YkExtraLinkObject;
 use std::fs;
+use std::io::Write;
 use byteorder::{NativeEndian, WriteBytesExt};
+use flate2::Compression;
+use flate2::write::DeflateEncoder;
 
 // Edge kinds.
 const GOTO: u8 = 0;
@@ -37,41 +58,164 @@ const YIELD_WITH_DROP: u8 = 17;
 const GENERATOR_DROP: u8 = 18;
 const FALSE_EDGES: u8 = 19;
 const FALSE_UNWIND: u8 = 20;
+const GENERATOR_LAYOUT: u8 = 21;
+const GENERATOR_STATES: u8 = 22;
 const NO_MIR: u8 = 254;
 const SENTINAL: u8 = 255;
 
 const MIR_CFG_SECTION_NAME: &'static str = ".yk_mir_cfg";
 const MIR_CFG_TEMPLATE: &'static str = ".ykcfg.XXXXXXXX";
-const SECTION_VERSION: u16 = 0;
+
+// Section versions.
+const SECTION_VERSION_1: u16 = 1;
+const SECTION_VERSION_2: u16 = 2;
+const LATEST_SECTION_VERSION: u16 = SECTION_VERSION_2;
+
+// Version 2 header flags.
+const FLAG_DEFLATE: u8 = 1;
+
+// What follows the blocks of a function in version 2 sections.
+const GENERATOR_INFO_NONE: u8 = 0;
+const GENERATOR_INFO_FULL: u8 = 1;
+const GENERATOR_INFO_LAYOUT: u8 = 2;
+
+// What a drop runs, as stored in `DROP_*` and `DROP_AND_REPLACE_*` records.
+const DROP_GLUE_NONE: u8 = 0;
+const DROP_GLUE_SYMBOL: u8 = 1;
+const DROP_GLUE_UNRESOLVED: u8 = 2;
+
+/// The outgoing edges of a basic block. Block indices are stored as `u32`.
+enum Edge {
+    Goto(u32),
+    SwitchInt(Vec<u32>),
+    Resume,
+    Abort,
+    Return,
+    Unreachable,
+    Drop { target: u32, unwind: Option<u32>, glue: DropGlue },
+    DropAndReplace { target: u32, unwind: Option<u32>, glue: DropGlue },
+    /// A call. The callee is `None` if it isn't statically known.
+    Call { callee: Option<DefId>, cleanup: Option<u32> },
+    Assert { target: u32, cleanup: Option<u32> },
+    Yield { resume: u32, drop: Option<u32> },
+    GeneratorDrop,
+    FalseEdges(u32),
+    FalseUnwind(u32),
+}
+
+impl Edge {
+    fn kind(&self) -> u8 {
+        match *self {
+            Edge::Goto(..) => GOTO,
+            Edge::SwitchInt(..) => SWITCHINT,
+            Edge::Resume => RESUME,
+            Edge::Abort => ABORT,
+            Edge::Return => RETURN,
+            Edge::Unreachable => UNREACHABLE,
+            Edge::Drop { unwind: None, .. } => DROP_NO_UNWIND,
+            Edge::Drop { unwind: Some(_), .. } => DROP_WITH_UNWIND,
+            Edge::DropAndReplace { unwind: None, .. } => DROP_AND_REPLACE_NO_UNWIND,
+            Edge::DropAndReplace { unwind: Some(_), .. } => DROP_AND_REPLACE_WITH_UNWIND,
+            Edge::Call { callee: Some(_), cleanup: None } => CALL_NO_CLEANUP,
+            Edge::Call { callee: Some(_), cleanup: Some(_) } => CALL_WITH_CLEANUP,
+            Edge::Call { callee: None, cleanup: None } => CALL_UNKNOWN_NO_CLEANUP,
+            Edge::Call { callee: None, cleanup: Some(_) } => CALL_UNKNOWN_WITH_CLEANUP,
+            Edge::Assert { cleanup: None, .. } => ASSERT_NO_CLEANUP,
+            Edge::Assert { cleanup: Some(_), .. } => ASSERT_WITH_CLEANUP,
+            Edge::Yield { drop: None, .. } => YIELD_NO_DROP,
+            Edge::Yield { drop: Some(_), .. } => YIELD_WITH_DROP,
+            Edge::GeneratorDrop => GENERATOR_DROP,
+            Edge::FalseEdges(..) => FALSE_EDGES,
+            Edge::FalseUnwind(..) => FALSE_UNWIND,
+        }
+    }
+}
+
+/// The drop glue that codegen would call for a drop.
+enum DropGlue {
+    /// The type needs no dropping.
+    None,
+    /// The symbol name of the `drop_in_place` instance, and the type it drops.
+    Symbol { sym: String, ty: String },
+    /// The type depends on generic parameters, so the glue isn't known until monomorphisation.
+    Unresolved,
+}
+
+/// Describes the state transformation of a generator. The transformed MIR starts with a switch
+/// over the state discriminant. Each yield point saves the next state into the discriminant and
+/// returns, and each resume block is an arm of the switch.
+struct GeneratorInfo {
+    /// The field index of the state discriminant in the generator struct.
+    state_field: u32,
+    /// (field index, original local) for each saved local.
+    saved_locals: Vec<(u32, u32)>,
+    /// The resume and suspend points, or `None` if the MIR doesn't start with a state switch.
+    states: Option<GeneratorStates>,
+}
+
+struct GeneratorStates {
+    /// (state, block) for each arm of the resume switch.
+    resumes: Vec<(u32, u32)>,
+    /// (block, state) for each block which suspends the generator.
+    suspends: Vec<(u32, u32)>,
+}
+
+/// The serialisable control flow of one function.
+struct FuncCfg {
+    def_id: DefId,
+    /// The edges of each block, in block order, or `None` if there is no MIR for the function.
+    blocks: Option<Vec<Edge>>,
+    generator: Option<GeneratorInfo>,
+}
 
 /// Serialises the control flow for the given `DefId`s into a ELF object file and returns a handle for linking.
-pub fn emit_mir_cfg_section<'a, 'tcx, 'gcx>(tcx: &'a TyCtxt<'a, 'tcx, 'gcx>, def_ids: &DefIdSet) -> YkExtraLinkObject {
-    // XXX
-    for i in tcx.crates().iter() {
-	eprintln!("0x{} -> {}", tcx.crate_hash(*i).to_string(), tcx.crate_name(*i));
+pub fn emit_mir_cfg_section<'a, 'tcx>(tcx: &'a TyCtxt<'a, 'tcx, 'tcx>, def_ids: &DefIdSet) -> YkExtraLinkObject {
+    // Records identify crates only by their hash, so allow tools (e.g. the `yk` compiletest
+    // suite) to map them back to names.
+    if env::var("YK_PRINT_CRATE_HASHES").is_ok() {
+        for krate in iter::once(&LOCAL_CRATE).chain(tcx.crates().iter()) {
+            eprintln!("0x{} -> {}", tcx.crate_hash(*krate).to_string(), tcx.crate_name(*krate));
+        }
     }
 
+    let version = match tcx.sess.opts.debugging_opts.yk_section_version {
+        Some(v) if v == SECTION_VERSION_1 as usize => SECTION_VERSION_1,
+        Some(v) if v == SECTION_VERSION_2 as usize => SECTION_VERSION_2,
+        Some(v) => tcx.sess.fatal(&format!("unsupported Yorick section version: {}", v)),
+        None => LATEST_SECTION_VERSION,
+    };
+    let compress = tcx.sess.opts.debugging_opts.yk_compress_sections;
+
+    let funcs: Vec<FuncCfg> = def_ids.iter().map(|def_id| {
+        if tcx.is_mir_available(*def_id) {
+            process_mir(tcx, *def_id, tcx.optimized_mir(*def_id))
+        } else {
+            FuncCfg { def_id: *def_id, blocks: None, generator: None }
+        }
+    }).collect();
+
     // First serialise the CFG into a plain binary file.
     let mut template = std::env::temp_dir();
     template.push(MIR_CFG_TEMPLATE);
     let mut fh = TempFile::new(template.to_str().unwrap(), false).unwrap();
 
-    // Write a version field for sanity checking when deserialising.
-    fh.write_u16::<NativeEndian>(SECTION_VERSION).unwrap();
-
-    for def_id in def_ids {
-        if tcx.is_mir_available(*def_id) {
-            process_mir(&mut fh, tcx, def_id, tcx.optimized_mir(*def_id));
+    // Write a version field for sanity checking and version negotiation when deserialising.
+    fh.write_u16::<NativeEndian>(version).unwrap();
+    if version == SECTION_VERSION_1 {
+        write_v1(&mut fh, tcx, &funcs);
+    } else {
+        let payload = encode_v2(tcx, &funcs);
+        if compress {
+            fh.write_u8(FLAG_DEFLATE).unwrap();
+            let mut enc = DeflateEncoder::new(&mut fh, Compression::best());
+            enc.write_all(&payload).unwrap();
+            enc.finish().unwrap();
         } else {
-            fh.write_u8(NO_MIR).unwrap();
-            fh.write_u64::<NativeEndian>(tcx.crate_hash(def_id.krate).as_u64()).unwrap();
-            fh.write_u32::<NativeEndian>(def_id.index.as_raw_u32()).unwrap();
+            fh.write_u8(0).unwrap();
+            fh.write_all(&payload).unwrap();
         }
     }
 
-    // Write end-of-section sentinal.
-    fh.write_u8(SENTINAL).unwrap();
-
     // Now graft it into an object file.
     let path = PathBuf::from(fh.path());
     let ret = YkExtraLinkObject::new(&path, MIR_CFG_SECTION_NAME);
@@ -80,54 +224,38 @@ pub fn emit_mir_cfg_section<'a, 'tcx, 'gcx>(tcx: &'a TyCtxt<'a, 'tcx, 'gcx>, def
     ret
 }
 
-/// For each block in the given MIR write out one CFG edge record.
-fn process_mir(fh: &mut TempFile, tcx: &TyCtxt, def_id: &DefId, mir: &Mir) {
-    for (bb, maybe_bb_data) in mir.basic_blocks().iter_enumerated() {
+/// Collects the edges of each block in the given MIR.
+fn process_mir<'a, 'tcx>(tcx: &TyCtxt<'a, 'tcx, 'tcx>, def_id: DefId, mir: &Mir<'tcx>)
+                         -> FuncCfg {
+    let mut blocks = Vec::new();
+    for maybe_bb_data in mir.basic_blocks().iter() {
         let bb_data = maybe_bb_data.terminator.as_ref().unwrap();
-        match bb_data.kind {
-            TerminatorKind::Goto{target: target_bb} => {
-                write_rec_header(fh, tcx, GOTO, def_id, bb);
-                fh.write_u32::<NativeEndian>(target_bb.index() as u32).unwrap();
-            },
+        let edge = match bb_data.kind {
+            TerminatorKind::Goto{target: target_bb} => Edge::Goto(target_bb.index() as u32),
             TerminatorKind::SwitchInt{ref targets, ..} => {
-                write_rec_header(fh, tcx, SWITCHINT, def_id, bb);
-
-                if cfg!(target_pointer_width = "64") {
-                    fh.write_u64::<NativeEndian>(targets.len() as u64).unwrap();
-                } else {
-                    panic!("unknown pointer width");
-                }
-
-                for target_bb in targets {
-                    fh.write_u32::<NativeEndian>(target_bb.index() as u32).unwrap();
-                }
+                Edge::SwitchInt(targets.iter().map(|bb| bb.index() as u32).collect())
             },
-            TerminatorKind::Resume => write_rec_header(fh, tcx, RESUME, def_id, bb),
-            TerminatorKind::Abort => write_rec_header(fh, tcx, ABORT, def_id, bb),
-            TerminatorKind::Return => write_rec_header(fh, tcx, RETURN, def_id, bb),
-            TerminatorKind::Unreachable => write_rec_header(fh, tcx, UNREACHABLE, def_id, bb),
-            TerminatorKind::Drop{target: target_bb, unwind: opt_unwind_bb, ..} => {
-                if let Some(unwind_bb) = opt_unwind_bb {
-                    write_rec_header(fh, tcx, DROP_WITH_UNWIND, def_id, bb);
-                    fh.write_u32::<NativeEndian>(target_bb.index() as u32).unwrap();
-                    fh.write_u32::<NativeEndian>(unwind_bb.index() as u32).unwrap();
-                } else {
-                    write_rec_header(fh, tcx, DROP_NO_UNWIND, def_id, bb);
-                    fh.write_u32::<NativeEndian>(target_bb.index() as u32).unwrap();
+            TerminatorKind::Resume => Edge::Resume,
+            TerminatorKind::Abort => Edge::Abort,
+            TerminatorKind::Return => Edge::Return,
+            TerminatorKind::Unreachable => Edge::Unreachable,
+            TerminatorKind::Drop{ref location, target: target_bb, unwind: opt_unwind_bb} => {
+                Edge::Drop {
+                    target: target_bb.index() as u32,
+                    unwind: opt_unwind_bb.map(|bb| bb.index() as u32),
+                    glue: drop_glue(tcx, mir, location),
                 }
             },
-            TerminatorKind::DropAndReplace{target: target_bb, unwind: opt_unwind_bb, ..} => {
-                if let Some(unwind_bb) = opt_unwind_bb {
-                    write_rec_header(fh, tcx, DROP_AND_REPLACE_WITH_UNWIND, def_id, bb);
-                    fh.write_u32::<NativeEndian>(target_bb.index() as u32).unwrap();
-                    fh.write_u32::<NativeEndian>(unwind_bb.index() as u32).unwrap();
-                } else {
-                    write_rec_header(fh, tcx, DROP_AND_REPLACE_NO_UNWIND, def_id, bb);
-                    fh.write_u32::<NativeEndian>(target_bb.index() as u32).unwrap();
+            TerminatorKind::DropAndReplace{ref location, target: target_bb,
+                                           unwind: opt_unwind_bb, ..} => {
+                Edge::DropAndReplace {
+                    target: target_bb.index() as u32,
+                    unwind: opt_unwind_bb.map(|bb| bb.index() as u32),
+                    glue: drop_glue(tcx, mir, location),
                 }
             },
             TerminatorKind::Call{ref func, cleanup: opt_cleanup_bb, ..} => {
-                if let Operand::Constant(box Constant {
+                let callee = if let Operand::Constant(box Constant {
                     literal: Const {
                         ty: &TyS {
                             sty: TyKind::FnDef(target_def_id, _substs), ..
@@ -135,67 +263,405 @@ fn process_mir(fh: &mut TempFile, tcx: &TyCtxt, def_id: &DefId, mir: &Mir) {
                     }, ..
                 }, ..) = func {
                     // A statically known call target.
-                    if opt_cleanup_bb.is_some() {
-                        write_rec_header(fh, tcx, CALL_WITH_CLEANUP, def_id, bb);
-                    } else {
-                        write_rec_header(fh, tcx, CALL_NO_CLEANUP, def_id, bb);
-                    }
-
-                    fh.write_u64::<NativeEndian>(tcx.crate_hash(target_def_id.krate).as_u64()).unwrap();
-                    fh.write_u32::<NativeEndian>(target_def_id.index.as_raw_u32()).unwrap();
-
-                    if let Some(cleanup_bb) = opt_cleanup_bb {
-                        fh.write_u32::<NativeEndian>(cleanup_bb.index() as u32).unwrap();
-                    }
+                    Some(target_def_id)
                 } else {
                     // It's a kind of call that we can't statically know the target of.
-                    if let Some(cleanup_bb) = opt_cleanup_bb {
-                        write_rec_header(fh, tcx, CALL_UNKNOWN_WITH_CLEANUP, def_id, bb);
-                        fh.write_u32::<NativeEndian>(cleanup_bb.index() as u32).unwrap();
-                    } else {
-                        write_rec_header(fh, tcx, CALL_UNKNOWN_NO_CLEANUP, def_id, bb);
-                    }
-                }
+                    None
+                };
+                Edge::Call { callee, cleanup: opt_cleanup_bb.map(|bb| bb.index() as u32) }
             },
             TerminatorKind::Assert{target: target_bb, cleanup: opt_cleanup_bb, ..} => {
-                if let Some(cleanup_bb) = opt_cleanup_bb {
-                    write_rec_header(fh, tcx, ASSERT_WITH_CLEANUP, def_id, bb);
-                    fh.write_u32::<NativeEndian>(target_bb.index() as u32).unwrap();
-                    fh.write_u32::<NativeEndian>(cleanup_bb.index() as u32).unwrap();
-                } else {
-                    write_rec_header(fh, tcx, ASSERT_NO_CLEANUP, def_id, bb);
-                    fh.write_u32::<NativeEndian>(target_bb.index() as u32).unwrap();
+                Edge::Assert {
+                    target: target_bb.index() as u32,
+                    cleanup: opt_cleanup_bb.map(|bb| bb.index() as u32),
                 }
             },
             TerminatorKind::Yield{resume: resume_bb, drop: opt_drop_bb, ..} => {
-                if let Some(drop_bb) = opt_drop_bb {
-                    write_rec_header(fh, tcx, YIELD_WITH_DROP, def_id, bb);
-                    fh.write_u32::<NativeEndian>(resume_bb.index() as u32).unwrap();
-                    fh.write_u32::<NativeEndian>(drop_bb.index() as u32).unwrap();
-                } else {
-                    write_rec_header(fh, tcx, YIELD_NO_DROP, def_id, bb);
-                    fh.write_u32::<NativeEndian>(resume_bb.index() as u32).unwrap();
+                Edge::Yield {
+                    resume: resume_bb.index() as u32,
+                    drop: opt_drop_bb.map(|bb| bb.index() as u32),
                 }
             },
-            TerminatorKind::GeneratorDrop => write_rec_header(fh, tcx, GENERATOR_DROP, def_id, bb),
+            TerminatorKind::GeneratorDrop => Edge::GeneratorDrop,
+            // Fake edges not considered.
             TerminatorKind::FalseEdges{real_target: real_target_bb, ..} => {
-                // Fake edges not considered.
-                write_rec_header(fh, tcx, FALSE_EDGES, def_id, bb);
-                fh.write_u32::<NativeEndian>(real_target_bb.index() as u32).unwrap();
+                Edge::FalseEdges(real_target_bb.index() as u32)
             },
             TerminatorKind::FalseUnwind{real_target: real_target_bb, ..} => {
-                // Fake edges not considered.
-                write_rec_header(fh, tcx, FALSE_UNWIND, def_id, bb);
-                fh.write_u32::<NativeEndian>(real_target_bb.index() as u32).unwrap();
+                Edge::FalseUnwind(real_target_bb.index() as u32)
             },
+        };
+        blocks.push(edge);
+    }
+
+    FuncCfg {
+        def_id,
+        blocks: Some(blocks),
+        generator: mir.generator_layout.as_ref().map(|layout| process_generator(tcx, mir, layout)),
+    }
+}
+
+/// Finds the drop glue that codegen would call to drop `location`. This is the `drop_in_place`
+/// instance for the place's type, unless the type needs no dropping.
+fn drop_glue<'a, 'tcx>(tcx: &TyCtxt<'a, 'tcx, 'tcx>, mir: &Mir<'tcx>, location: &Place<'tcx>)
+                       -> DropGlue {
+    let ty = tcx.erase_regions(&location.ty(mir, *tcx).to_ty(*tcx));
+    if ty.needs_subst() {
+        return DropGlue::Unresolved;
+    }
+
+    // Like codegen, reveal `impl Trait` types, which may turn out to need no dropping.
+    let ty = tcx.normalize_erasing_regions(ty::ParamEnv::reveal_all(), ty);
+    match resolve_drop_in_place(*tcx, ty) {
+        Instance { def: InstanceDef::DropGlue(_, None), .. } => DropGlue::None,
+        instance => DropGlue::Symbol {
+            sym: tcx.symbol_name(instance).name.as_str().to_string(),
+            ty: ty.to_string(),
+        },
+    }
+}
+
+/// Collects the state transformation information of a generator.
+fn process_generator<'a, 'tcx>(tcx: &TyCtxt<'a, 'tcx, 'tcx>, mir: &Mir<'tcx>,
+                               layout: &GeneratorLayout<'tcx>) -> GeneratorInfo {
+    // Which locals were saved into which fields of the generator struct.
+    let saved_locals = layout.field_locals.iter().enumerate().map(|(idx, local)| {
+        ((layout.state_field + 1 + idx) as u32, local.index() as u32)
+    }).collect();
+
+    // The state to resume block mapping, from the switch inserted at the start block. Without it
+    // we can't tell which blocks belong to which state, so only the layout is recorded.
+    let resumes = match mir.basic_blocks()[START_BLOCK].terminator().kind {
+        TerminatorKind::SwitchInt{ref values, ref targets, ..} => {
+            values.iter().zip(targets.iter())
+                .map(|(state, target_bb)| (*state as u32, target_bb.index() as u32))
+                .collect()
+        },
+        _ => {
+            return GeneratorInfo {
+                state_field: layout.state_field as u32,
+                saved_locals,
+                states: None,
+            };
+        },
+    };
+
+    // The blocks which suspend the generator, and the state they leave it in.
+    let suspends = mir.basic_blocks().iter_enumerated()
+        .filter_map(|(bb, bb_data)| {
+            match bb_data.terminator().kind {
+                TerminatorKind::Return => {},
+                _ => return None,
+            }
+            bb_data.statements.iter().rev().filter_map(|stmt| match stmt.kind {
+                StatementKind::Assign(ref place, box Rvalue::Use(Operand::Constant(ref c)))
+                    if is_state_field(place, layout.state_field) => {
+                    c.literal.assert_bits(*tcx, ty::ParamEnv::empty().and(tcx.types.u32))
+                },
+                _ => None,
+            }).next().map(|state| (bb.index() as u32, state as u32))
+        }).collect();
+
+    GeneratorInfo {
+        state_field: layout.state_field as u32,
+        saved_locals,
+        states: Some(GeneratorStates { resumes, suspends }),
+    }
+}
+
+/// Is `place` the state discriminant of the generator passed as the first argument? Depending on
+/// how far the transformation got, the generator is accessed either by value or through a pointer.
+fn is_state_field(place: &Place, state_field: usize) -> bool {
+    if let Place::Projection(ref proj) = *place {
+        if let ProjectionElem::Field(field, _) = proj.elem {
+            let base_is_self = match proj.base {
+                Place::Local(local) => local == Local::new(1),
+                Place::Projection(ref base_proj) => match (&base_proj.base, &base_proj.elem) {
+                    (&Place::Local(local), &ProjectionElem::Deref) => local == Local::new(1),
+                    _ => false,
+                },
+                _ => false,
+            };
+            return base_is_self && field.index() == state_field;
+        }
+    }
+    false
+}
+
+/// Writes a version 1 section body: for each block one CFG edge record, plus the generator
+/// records, each prefixed with a header identifying the function and block.
+fn write_v1(fh: &mut TempFile, tcx: &TyCtxt, funcs: &[FuncCfg]) {
+    for func in funcs {
+        let blocks = match func.blocks {
+            Some(ref blocks) => blocks,
+            None => {
+                fh.write_u8(NO_MIR).unwrap();
+                fh.write_u64::<NativeEndian>(tcx.crate_hash(func.def_id.krate).as_u64()).unwrap();
+                fh.write_u32::<NativeEndian>(func.def_id.index.as_raw_u32()).unwrap();
+                continue;
+            },
+        };
+
+        for (bb, edge) in blocks.iter().enumerate() {
+            write_rec_header(fh, tcx, edge.kind(), &func.def_id, bb as u32);
+            match *edge {
+                Edge::Goto(target_bb) | Edge::FalseEdges(target_bb)
+                    | Edge::FalseUnwind(target_bb) => {
+                    fh.write_u32::<NativeEndian>(target_bb).unwrap();
+                },
+                Edge::SwitchInt(ref targets) => {
+                    if cfg!(target_pointer_width = "64") {
+                        fh.write_u64::<NativeEndian>(targets.len() as u64).unwrap();
+                    } else {
+                        panic!("unknown pointer width");
+                    }
+
+                    for target_bb in targets {
+                        fh.write_u32::<NativeEndian>(*target_bb).unwrap();
+                    }
+                },
+                Edge::Resume | Edge::Abort | Edge::Return | Edge::Unreachable
+                    | Edge::GeneratorDrop => {},
+                Edge::Drop { target, unwind, ref glue }
+                    | Edge::DropAndReplace { target, unwind, ref glue } => {
+                    fh.write_u32::<NativeEndian>(target).unwrap();
+                    if let Some(unwind_bb) = unwind {
+                        fh.write_u32::<NativeEndian>(unwind_bb).unwrap();
+                    }
+                    match *glue {
+                        DropGlue::None => fh.write_u8(DROP_GLUE_NONE).unwrap(),
+                        DropGlue::Unresolved => fh.write_u8(DROP_GLUE_UNRESOLVED).unwrap(),
+                        DropGlue::Symbol { ref sym, ref ty } => {
+                            fh.write_u8(DROP_GLUE_SYMBOL).unwrap();
+                            for s in &[sym, ty] {
+                                fh.write_u32::<NativeEndian>(s.len() as u32).unwrap();
+                                fh.write_all(s.as_bytes()).unwrap();
+                            }
+                        },
+                    }
+                },
+                Edge::Call { callee, cleanup } => {
+                    if let Some(target_def_id) = callee {
+                        fh.write_u64::<NativeEndian>(
+                            tcx.crate_hash(target_def_id.krate).as_u64()).unwrap();
+                        fh.write_u32::<NativeEndian>(target_def_id.index.as_raw_u32()).unwrap();
+                    }
+                    if let Some(cleanup_bb) = cleanup {
+                        fh.write_u32::<NativeEndian>(cleanup_bb).unwrap();
+                    }
+                },
+                Edge::Assert { target, cleanup } => {
+                    fh.write_u32::<NativeEndian>(target).unwrap();
+                    if let Some(cleanup_bb) = cleanup {
+                        fh.write_u32::<NativeEndian>(cleanup_bb).unwrap();
+                    }
+                },
+                Edge::Yield { resume, drop } => {
+                    fh.write_u32::<NativeEndian>(resume).unwrap();
+                    if let Some(drop_bb) = drop {
+                        fh.write_u32::<NativeEndian>(drop_bb).unwrap();
+                    }
+                },
+            }
+        }
+
+        if let Some(ref gen) = func.generator {
+            let write_pairs = |fh: &mut TempFile, pairs: &[(u32, u32)]| {
+                fh.write_u32::<NativeEndian>(pairs.len() as u32).unwrap();
+                for &(a, b) in pairs {
+                    fh.write_u32::<NativeEndian>(a).unwrap();
+                    fh.write_u32::<NativeEndian>(b).unwrap();
+                }
+            };
+            write_rec_header(fh, tcx, GENERATOR_LAYOUT, &func.def_id, 0);
+            fh.write_u32::<NativeEndian>(gen.state_field).unwrap();
+            write_pairs(fh, &gen.saved_locals);
+            if let Some(ref states) = gen.states {
+                write_rec_header(fh, tcx, GENERATOR_STATES, &func.def_id, 0);
+                write_pairs(fh, &states.resumes);
+                write_pairs(fh, &states.suspends);
+            }
         }
     }
+
+    // Write end-of-section sentinal.
+    fh.write_u8(SENTINAL).unwrap();
 }
 
-/// Writes the "header" of a record, which is common to all record types.
-fn write_rec_header(fh: &mut TempFile, tcx: &TyCtxt, kind: u8, def_id: &DefId, bb: BasicBlock) {
+/// Writes the "header" of a version 1 record, which is common to all record types.
+fn write_rec_header(fh: &mut TempFile, tcx: &TyCtxt, kind: u8, def_id: &DefId, bb: u32) {
     fh.write_u8(kind).unwrap();
     fh.write_u64::<NativeEndian>(tcx.crate_hash(def_id.krate).as_u64()).unwrap();
     fh.write_u32::<NativeEndian>(def_id.index.as_raw_u32()).unwrap();
-    fh.write_u32::<NativeEndian>(bb.index() as u32).unwrap();
+    fh.write_u32::<NativeEndian>(bb).unwrap();
+}
+
+/// Interns the crates, functions and strings referenced by a version 2 section.
+#[derive(Default)]
+struct Tables {
+    crates: Vec<CrateNum>,
+    crate_idxs: FxHashMap<CrateNum, usize>,
+    funcs: Vec<DefId>,
+    func_idxs: FxHashMap<DefId, usize>,
+    strs: Vec<String>,
+    str_idxs: FxHashMap<String, usize>,
+}
+
+impl Tables {
+    fn func(&mut self, def_id: DefId) -> usize {
+        if let Some(idx) = self.func_idxs.get(&def_id) {
+            return *idx;
+        }
+        if !self.crate_idxs.contains_key(&def_id.krate) {
+            self.crate_idxs.insert(def_id.krate, self.crates.len());
+            self.crates.push(def_id.krate);
+        }
+        let idx = self.funcs.len();
+        self.funcs.push(def_id);
+        self.func_idxs.insert(def_id, idx);
+        idx
+    }
+
+    fn str(&mut self, s: &str) -> usize {
+        if let Some(idx) = self.str_idxs.get(s) {
+            return *idx;
+        }
+        let idx = self.strs.len();
+        self.strs.push(s.to_owned());
+        self.str_idxs.insert(s.to_owned(), idx);
+        idx
+    }
+}
+
+/// Encodes the (uncompressed) body of a version 2 section, which follows the version and flags:
+///
+///  - The crate table: for each crate its hash (native endian `u64`) and name.
+///  - The string table: the drop glue symbol names and the types they drop.
+///  - The function table: for each function its crate table index and def index.
+///  - One group per serialised function: its function table index, a MIR flag and, if there is
+///    MIR, the edges of each block in block order. Then a `GENERATOR_INFO_*` kind and the
+///    generator information it calls for.
+///
+/// Other integers are unsigned LEB128 and strings are a length followed by UTF-8 bytes. Calls
+/// refer to their callee by function table index and drops refer to the symbol and type of their
+/// glue by string table index.
+fn encode_v2(tcx: &TyCtxt, funcs: &[FuncCfg]) -> Vec<u8> {
+    let mut tables = Tables::default();
+    let mut groups = Vec::new();
+    write_leb(&mut groups, funcs.len());
+    for func in funcs {
+        write_leb(&mut groups, tables.func(func.def_id));
+        let blocks = match func.blocks {
+            Some(ref blocks) => blocks,
+            None => {
+                groups.push(0);
+                continue;
+            },
+        };
+        groups.push(1);
+        write_leb(&mut groups, blocks.len());
+        for edge in blocks {
+            groups.push(edge.kind());
+            match *edge {
+                Edge::Goto(target_bb) | Edge::FalseEdges(target_bb)
+                    | Edge::FalseUnwind(target_bb) => write_leb(&mut groups, target_bb as usize),
+                Edge::SwitchInt(ref targets) => {
+                    write_leb(&mut groups, targets.len());
+                    for target_bb in targets {
+                        write_leb(&mut groups, *target_bb as usize);
+                    }
+                },
+                Edge::Resume | Edge::Abort | Edge::Return | Edge::Unreachable
+                    | Edge::GeneratorDrop => {},
+                Edge::Drop { target, unwind, ref glue }
+                    | Edge::DropAndReplace { target, unwind, ref glue } => {
+                    write_leb(&mut groups, target as usize);
+                    if let Some(unwind_bb) = unwind {
+                        write_leb(&mut groups, unwind_bb as usize);
+                    }
+                    match *glue {
+                        DropGlue::None => groups.push(DROP_GLUE_NONE),
+                        DropGlue::Unresolved => groups.push(DROP_GLUE_UNRESOLVED),
+                        DropGlue::Symbol { ref sym, ref ty } => {
+                            groups.push(DROP_GLUE_SYMBOL);
+                            let (sym_idx, ty_idx) = (tables.str(sym), tables.str(ty));
+                            write_leb(&mut groups, sym_idx);
+                            write_leb(&mut groups, ty_idx);
+                        },
+                    }
+                },
+                Edge::Call { callee, cleanup } => {
+                    if let Some(target_def_id) = callee {
+                        let idx = tables.func(target_def_id);
+                        write_leb(&mut groups, idx);
+                    }
+                    if let Some(cleanup_bb) = cleanup {
+                        write_leb(&mut groups, cleanup_bb as usize);
+                    }
+                },
+                Edge::Assert { target, cleanup } => {
+                    write_leb(&mut groups, target as usize);
+                    if let Some(cleanup_bb) = cleanup {
+                        write_leb(&mut groups, cleanup_bb as usize);
+                    }
+                },
+                Edge::Yield { resume, drop } => {
+                    write_leb(&mut groups, resume as usize);
+                    if let Some(drop_bb) = drop {
+                        write_leb(&mut groups, drop_bb as usize);
+                    }
+                },
+            }
+        }
+
+        let write_pairs = |groups: &mut Vec<u8>, pairs: &[(u32, u32)]| {
+            write_leb(groups, pairs.len());
+            for &(a, b) in pairs {
+                write_leb(groups, a as usize);
+                write_leb(groups, b as usize);
+            }
+        };
+        match func.generator {
+            Some(GeneratorInfo { state_field, ref saved_locals, states: Some(ref states) }) => {
+                groups.push(GENERATOR_INFO_FULL);
+                write_leb(&mut groups, state_field as usize);
+                write_pairs(&mut groups, saved_locals);
+                write_pairs(&mut groups, &states.resumes);
+                write_pairs(&mut groups, &states.suspends);
+            },
+            Some(GeneratorInfo { state_field, ref saved_locals, states: None }) => {
+                groups.push(GENERATOR_INFO_LAYOUT);
+                write_leb(&mut groups, state_field as usize);
+                write_pairs(&mut groups, saved_locals);
+            },
+            None => groups.push(GENERATOR_INFO_NONE),
+        }
+    }
+
+    let mut out = Vec::new();
+    write_leb(&mut out, tables.crates.len());
+    for krate in &tables.crates {
+        out.write_u64::<NativeEndian>(tcx.crate_hash(*krate).as_u64()).unwrap();
+        write_str(&mut out, &tcx.crate_name(*krate).as_str());
+    }
+    write_leb(&mut out, tables.strs.len());
+    for s in &tables.strs {
+        write_str(&mut out, s);
+    }
+    write_leb(&mut out, tables.funcs.len());
+    for def_id in &tables.funcs {
+        write_leb(&mut out, tables.crate_idxs[&def_id.krate]);
+        write_leb(&mut out, def_id.index.as_raw_u32() as usize);
+    }
+    out.extend_from_slice(&groups);
+    out
+}
+
+fn write_leb(out: &mut Vec<u8>, value: usize) {
+    leb128::write_usize_leb128(out, value);
+}
+
+fn write_str(out: &mut Vec<u8>, s: &str) {
+    write_leb(out, s.len());
+    out.extend_from_slice(s.as_bytes());
 }
@@ -0,0 +1,82 @@
+/// Serialiser for the value promotion section.
+///
+/// Records the MIR location of every call to the `yk_promote` intrinsic, so that the tracer can
+/// insert a guard on the promoted value and specialise the trace for it. The section is:
+///
+///  - A native endian `u16` version number.
+///  - The number of call sites, as unsigned LEB128.
+///  - For each call site: the crate hash of the containing function (native endian `u64`), then
+///    its def index and the block whose terminator is the call, both as unsigned LEB128.
+///
+/// Call sites are sorted, so the section is the same from one compilation to the next.
+
+use rustc::ty::{self, TyCtxt};
+use rustc::hir::def_id::DefId;
+use rustc::mir::{Mir, TerminatorKind, Operand, BasicBlock};
+use rustc::util::nodemap::DefIdSet;
+use rustc_target::spec::abi::Abi;
+use serialize::leb128;
+use std::path::PathBuf;
+use mkstemp::TempFile;
+use rustc_yk_link::YkExtraLinkObject;
+use std::fs;
+use std::io::Write;
+use byteorder::{NativeEndian, WriteBytesExt};
+
+const PROMOTE_SECTION_NAME: &'static str = ".yk_promote";
+const PROMOTE_TEMPLATE: &'static str = ".ykpromote.XXXXXXXX";
+const SECTION_VERSION: u16 = 1;
+
+/// The name of the intrinsic whose call sites are recorded.
+const PROMOTE_INTRINSIC: &'static str = "yk_promote";
+
+/// Serialises the promotion call sites in the given `DefId`s into a ELF object file and returns a
+/// handle for linking.
+pub fn emit_promote_section<'a, 'tcx>(tcx: &'a TyCtxt<'a, 'tcx, 'tcx>, def_ids: &DefIdSet)
+                                      -> YkExtraLinkObject {
+    let mut sites: Vec<(DefId, BasicBlock)> = Vec::new();
+    for def_id in def_ids {
+        if tcx.is_mir_available(*def_id) {
+            find_call_sites(tcx, *def_id, tcx.optimized_mir(*def_id), &mut sites);
+        }
+    }
+    sites.sort_by_key(|&(def_id, bb)| {
+        (tcx.crate_hash(def_id.krate).as_u64(), def_id.index.as_raw_u32(), bb)
+    });
+
+    let mut buf = Vec::new();
+    leb128::write_usize_leb128(&mut buf, sites.len());
+    for (def_id, bb) in sites {
+        buf.write_u64::<NativeEndian>(tcx.crate_hash(def_id.krate).as_u64()).unwrap();
+        leb128::write_usize_leb128(&mut buf, def_id.index.as_raw_u32() as usize);
+        leb128::write_usize_leb128(&mut buf, bb.index());
+    }
+
+    let mut template = std::env::temp_dir();
+    template.push(PROMOTE_TEMPLATE);
+    let mut fh = TempFile::new(template.to_str().unwrap(), false).unwrap();
+    fh.write_u16::<NativeEndian>(SECTION_VERSION).unwrap();
+    fh.write_all(&buf).unwrap();
+
+    let path = PathBuf::from(fh.path());
+    let ret = YkExtraLinkObject::new(&path, PROMOTE_SECTION_NAME);
+    fs::remove_file(path).unwrap();
+
+    ret
+}
+
+/// Finds the blocks of `mir` which end in a call to the promotion intrinsic.
+fn find_call_sites<'a, 'tcx>(tcx: &TyCtxt<'a, 'tcx, 'tcx>, def_id: DefId, mir: &Mir<'tcx>,
+                             sites: &mut Vec<(DefId, BasicBlock)>) {
+    for (bb, bb_data) in mir.basic_blocks().iter_enumerated() {
+        if let TerminatorKind::Call{func: Operand::Constant(ref func), ..} =
+            bb_data.terminator().kind {
+            if let ty::FnDef(callee, _) = func.ty.sty {
+                if tcx.fn_sig(callee).abi() == Abi::RustIntrinsic
+                    && tcx.item_name(callee) == PROMOTE_INTRINSIC {
+                    sites.push((def_id, bb));
+                }
+            }
+        }
+    }
+}
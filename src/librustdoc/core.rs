@@ -31,18 +31,21 @@ use syntax::source_map;
 use syntax::edition::Edition;
 use syntax::feature_gate::UnstableFeatures;
 use syntax::json::JsonEmitter;
+use syntax::sarif::{SarifEmitter, SarifLog, SharedSarifLog};
 use syntax::ptr::P;
 use syntax::symbol::keywords;
 use syntax_pos::DUMMY_SP;
 use errors;
 use errors::emitter::{Emitter, EmitterWriter};
 use parking_lot::ReentrantMutex;
+use getopts;
 
 use std::cell::RefCell;
 use std::mem;
 use rustc_data_structures::sync::{self, Lrc};
 use std::rc::Rc;
-use std::sync::Arc;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
 use std::path::PathBuf;
 
 use visit_ast::RustdocVisitor;
@@ -265,18 +268,101 @@ impl DocAccessLevels for AccessLevels<DefId> {
     }
 }
 
+/// Where `-Z diagnostic-output=PATH` sends diagnostics, rather than stderr.
+///
+/// The file is opened once, and the emitters of all the handlers of a rustdoc
+/// run write to it in turn. With `--error-format=sarif` they share a single
+/// log, which is written once the last of them is done, to stderr if no file
+/// is given.
+#[derive(Clone)]
+pub struct DiagnosticOutput {
+    dst: Arc<Mutex<Box<dyn Write + Send>>>,
+    sarif_log: Option<SharedSarifLog>,
+    /// Whether to also print each diagnostic to stderr, on one line, as asked
+    /// with `-Z diagnostic-output-summary`.
+    pub summary: bool,
+}
+
+impl DiagnosticOutput {
+    /// Parses the `-Z` flags, and opens the file.
+    pub fn from_matches(matches: &getopts::Matches,
+                        error_format: ErrorOutputType) -> Option<DiagnosticOutput> {
+        let flags = matches.opt_strs("Z");
+        let path = flags.iter()
+            .filter(|x| x.starts_with("diagnostic-output="))
+            .map(|x| PathBuf::from(&x["diagnostic-output=".len()..]))
+            .last();
+        let file: Box<dyn Write + Send> = match path {
+            Some(path) => session::open_diagnostic_output(&path, false, error_format),
+            None if error_format == ErrorOutputType::Sarif => Box::new(io::stderr()),
+            None => return None,
+        };
+        let dst = Arc::new(Mutex::new(file));
+        let sarif_log = match error_format {
+            ErrorOutputType::Sarif => Some(SarifLog::shared(Box::new(SharedOutput(dst.clone())))),
+            _ => None,
+        };
+        Some(DiagnosticOutput {
+            dst,
+            sarif_log,
+            summary: flags.iter().any(|x| *x == "diagnostic-output-summary"),
+        })
+    }
+}
+
+/// Writes to the file of a `DiagnosticOutput`.
+struct SharedOutput(Arc<Mutex<Box<dyn Write + Send>>>);
+
+impl Write for SharedOutput {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(data)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.lock().unwrap().flush()
+    }
+}
+
 /// Creates a new diagnostic `Handler` that can be used to emit warnings and errors.
 ///
-/// If the given `error_format` is `ErrorOutputType::Json` and no `SourceMap` is given, a new one
-/// will be created for the handler.
+/// If the given `error_format` is `ErrorOutputType::Json` or `ErrorOutputType::Sarif` and no
+/// `SourceMap` is given, a new one will be created for the handler.
 pub fn new_handler(error_format: ErrorOutputType,
                    source_map: Option<Lrc<source_map::SourceMap>>,
                    treat_err_as_bug: bool,
                    ui_testing: bool,
+                   diagnostic_output: Option<&DiagnosticOutput>,
 ) -> errors::Handler {
     // rustdoc doesn't override (or allow to override) anything from this that is relevant here, so
     // stick to the defaults
     let sessopts = Options::default();
+    if let Some(output) = diagnostic_output {
+        let source_map = source_map.unwrap_or_else(
+            || Lrc::new(source_map::SourceMap::new(sessopts.file_path_mapping())));
+        let dst: Box<dyn Write + Send> = Box::new(SharedOutput(output.dst.clone()));
+        let emitter: Box<dyn Emitter + sync::Send> = match error_format {
+            ErrorOutputType::HumanReadable(_) => Box::new(
+                EmitterWriter::new(dst, Some(source_map.clone()), false, false)
+                    .ui_testing(ui_testing)
+            ),
+            ErrorOutputType::Json(pretty) => Box::new(
+                JsonEmitter::new(dst, None, source_map.clone(), pretty).ui_testing(ui_testing)
+            ),
+            ErrorOutputType::Short(_) => Box::new(
+                EmitterWriter::new(dst, Some(source_map.clone()), true, false)
+            ),
+            ErrorOutputType::Sarif => {
+                let log = output.sarif_log.clone().expect("no SARIF log for the SARIF format");
+                Box::new(SarifEmitter::with_log(log, None, source_map.clone()))
+            }
+        };
+        let emitter = if output.summary {
+            session::with_diagnostic_summary(emitter, error_format, source_map)
+        } else {
+            emitter
+        };
+        return handler_with_emitter(emitter, treat_err_as_bug);
+    }
+
     let emitter: Box<dyn Emitter + sync::Send> = match error_format {
         ErrorOutputType::HumanReadable(color_config) => Box::new(
             EmitterWriter::stderr(
@@ -304,8 +390,18 @@ pub fn new_handler(error_format: ErrorOutputType,
                 true,
                 false)
         ),
+        ErrorOutputType::Sarif => {
+            let source_map = source_map.unwrap_or_else(
+                || Lrc::new(source_map::SourceMap::new(sessopts.file_path_mapping())));
+            Box::new(SarifEmitter::stderr(None, source_map))
+        },
     };
 
+    handler_with_emitter(emitter, treat_err_as_bug)
+}
+
+fn handler_with_emitter(emitter: Box<dyn Emitter + sync::Send>,
+                        treat_err_as_bug: bool) -> errors::Handler {
     errors::Handler::with_emitter_and_flags(
         emitter,
         errors::HandlerFlags {
@@ -337,6 +433,7 @@ pub fn run_core(search_paths: SearchPaths,
                 mut default_passes: passes::DefaultPassOption,
                 treat_err_as_bug: bool,
                 ui_testing: bool,
+                diagnostic_output: Option<DiagnosticOutput>,
 ) -> (clean::Crate, RenderInfo, Vec<String>) {
     // Parse, resolve, and typecheck the given crate.
 
@@ -405,7 +502,8 @@ pub fn run_core(search_paths: SearchPaths,
         let diagnostic_handler = new_handler(error_format,
                                              Some(source_map.clone()),
                                              treat_err_as_bug,
-                                             ui_testing);
+                                             ui_testing,
+                                             diagnostic_output.as_ref());
 
         let mut sess = session::build_session_(
             sessopts, cpath, diagnostic_handler, source_map,
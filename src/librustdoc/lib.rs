@@ -305,7 +305,7 @@ fn opts() -> Vec<RustcOptGroup> {
             o.optopt("",
                      "error-format",
                      "How errors and other messages are produced",
-                     "human|json|short")
+                     "human|json|short|sarif")
         }),
         unstable("disable-minification", |o| {
              o.optflag("",
@@ -399,11 +399,12 @@ fn main_args(args: &[String]) -> isize {
         Some("json") => ErrorOutputType::Json(false),
         Some("pretty-json") => ErrorOutputType::Json(true),
         Some("short") => ErrorOutputType::Short(color),
+        Some("sarif") => ErrorOutputType::Sarif,
         None => ErrorOutputType::HumanReadable(color),
         Some(arg) => {
             early_error(ErrorOutputType::default(),
-                        &format!("argument for --error-format must be `human`, `json` or \
-                                  `short` (instead was `{}`)", arg));
+                        &format!("argument for --error-format must be `human`, `json`, \
+                                  `short` or `sarif` (instead was `{}`)", arg));
         }
     };
     let treat_err_as_bug = matches.opt_strs("Z").iter().any(|x| {
@@ -413,7 +414,11 @@ fn main_args(args: &[String]) -> isize {
         *x == "ui-testing"
     });
 
-    let diag = core::new_handler(error_format, None, treat_err_as_bug, ui_testing);
+    // Shared by all the handlers of the run.
+    let diagnostic_output = core::DiagnosticOutput::from_matches(&matches, error_format);
+
+    let diag = core::new_handler(error_format, None, treat_err_as_bug, ui_testing,
+                                 diagnostic_output.as_ref());
 
     // check for deprecated options
     check_deprecated_options(&matches, &diag);
@@ -554,7 +559,8 @@ fn main_args(args: &[String]) -> isize {
         }
         (true, false) => {
             return test::run(Path::new(input), cfgs, libs, externs, test_args, crate_name,
-                             maybe_sysroot, display_warnings, linker, edition, cg)
+                             maybe_sysroot, display_warnings, linker, edition, cg,
+                             error_format, diagnostic_output.as_ref())
         }
         (false, true) => return markdown::render(Path::new(input),
                                                  output.unwrap_or(PathBuf::from("doc")),
@@ -566,9 +572,10 @@ fn main_args(args: &[String]) -> isize {
     let output_format = matches.opt_str("w");
 
     let res = acquire_input(PathBuf::from(input), externs, edition, cg, &matches, error_format,
-                            move |out| {
+                            diagnostic_output.clone(), move |out| {
         let Output { krate, passes, renderinfo } = out;
-        let diag = core::new_handler(error_format, None, treat_err_as_bug, ui_testing);
+        let diag = core::new_handler(error_format, None, treat_err_as_bug, ui_testing,
+                                     diagnostic_output.as_ref());
         info!("going to format");
         match output_format.as_ref().map(|s| &**s) {
             Some("html") | None => {
@@ -604,13 +611,16 @@ fn acquire_input<R, F>(input: PathBuf,
                        cg: CodegenOptions,
                        matches: &getopts::Matches,
                        error_format: ErrorOutputType,
+                       diagnostic_output: Option<core::DiagnosticOutput>,
                        f: F)
                        -> Result<R, String>
 where R: 'static + Send, F: 'static + Send + FnOnce(Output) -> R {
     match matches.opt_str("r").as_ref().map(|s| &**s) {
-        Some("rust") => Ok(rust_input(input, externs, edition, cg, matches, error_format, f)),
+        Some("rust") => Ok(rust_input(input, externs, edition, cg, matches, error_format,
+                                      diagnostic_output, f)),
         Some(s) => Err(format!("unknown input format: {}", s)),
-        None => Ok(rust_input(input, externs, edition, cg, matches, error_format, f))
+        None => Ok(rust_input(input, externs, edition, cg, matches, error_format,
+                              diagnostic_output, f))
     }
 }
 
@@ -662,6 +672,7 @@ fn rust_input<R, F>(cratefile: PathBuf,
                     cg: CodegenOptions,
                     matches: &getopts::Matches,
                     error_format: ErrorOutputType,
+                    diagnostic_output: Option<core::DiagnosticOutput>,
                     f: F) -> R
 where R: 'static + Send,
       F: 'static + Send + FnOnce(Output) -> R
@@ -721,7 +732,7 @@ where R: 'static + Send,
                            display_warnings, crate_name.clone(),
                            force_unstable_if_unmarked, edition, cg, error_format,
                            lint_opts, lint_cap, describe_lints, manual_passes, default_passes,
-                           treat_err_as_bug, ui_testing);
+                           treat_err_as_bug, ui_testing, diagnostic_output);
 
         info!("finished with rustc");
 
@@ -24,7 +24,7 @@ use rustc_lint;
 use rustc::hir;
 use rustc::hir::intravisit;
 use rustc::session::{self, CompileIncomplete, config};
-use rustc::session::config::{OutputType, OutputTypes, Externs, CodegenOptions};
+use rustc::session::config::{OutputType, OutputTypes, Externs, CodegenOptions, ErrorOutputType};
 use rustc::session::search_paths::{SearchPaths, PathKind};
 use rustc_metadata::dynamic_lib::DynamicLibrary;
 use tempfile::Builder as TempFileBuilder;
@@ -39,7 +39,7 @@ use syntax::feature_gate::UnstableFeatures;
 use syntax::with_globals;
 use syntax_pos::{BytePos, DUMMY_SP, Pos, Span, FileName};
 use errors;
-use errors::emitter::ColorConfig;
+use core;
 
 use clean::Attributes;
 use html::markdown::{self, ErrorCodes, LangString};
@@ -65,7 +65,9 @@ pub fn run(input_path: &Path,
            display_warnings: bool,
            linker: Option<PathBuf>,
            edition: Edition,
-           cg: CodegenOptions)
+           cg: CodegenOptions,
+           error_format: ErrorOutputType,
+           diagnostic_output: Option<&core::DiagnosticOutput>)
            -> isize {
     let input = config::Input::File(input_path.to_owned());
 
@@ -87,10 +89,8 @@ pub fn run(input_path: &Path,
     };
     driver::spawn_thread_pool(sessopts, |sessopts| {
         let source_map = Lrc::new(SourceMap::new(sessopts.file_path_mapping()));
-        let handler =
-            errors::Handler::with_tty_emitter(ColorConfig::Auto,
-                                            true, false,
-                                            Some(source_map.clone()));
+        let handler = core::new_handler(error_format, Some(source_map.clone()), false, false,
+                                        diagnostic_output);
 
         let mut sess = session::build_session_(
             sessopts, Some(input_path.to_owned()), handler, source_map.clone(),
@@ -553,6 +553,8 @@ impl Collector {
                 // compiler failures are test failures
                 should_panic: testing::ShouldPanic::No,
                 allow_fail: config.allow_fail,
+                timeout: None,
+                retries: None,
             },
             testfn: testing::DynTestFn(box move || {
                 let panic = io::set_panic(None);
@@ -469,6 +469,7 @@ pub mod path;
 pub mod process;
 pub mod sync;
 pub mod time;
+pub mod yk;
 
 #[unstable(feature = "futures_api",
            reason = "futures in libcore are unstable",
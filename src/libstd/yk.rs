@@ -0,0 +1,243 @@
+// Copyright 2018 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Access to the Yorick sections embedded in the running program.
+//!
+//! When ykrustc links an executable it grafts a number of extra ELF sections
+//! into it (e.g. `.yk_mir_cfg`). Each such section is bracketed by a pair of
+//! exported symbols, named by `start_symbol` and `stop_symbol`. This module
+//! uses those symbols to find the raw bytes of the sections in memory, for
+//! the executable and for every loaded shared object that carries them.
+//!
+//! The contents of the sections are not interpreted here.
+
+#![unstable(feature = "yk_sections", issue = "0")]
+
+use path::{Path, PathBuf};
+
+/// The name of the section holding the serialised MIR control flow graphs.
+pub const MIR_CFG_SECTION: &str = ".yk_mir_cfg";
+
+/// The name of the section holding the call sites of the `yk_promote` intrinsic.
+pub const PROMOTE_SECTION: &str = ".yk_promote";
+
+/// A Yorick section found in the memory of the running program.
+#[derive(Debug, Clone)]
+pub struct Section {
+    object: PathBuf,
+    data: &'static [u8],
+}
+
+impl Section {
+    /// The path of the object that the section was loaded from. For the
+    /// executable itself this is the empty path.
+    pub fn object(&self) -> &Path {
+        &self.object
+    }
+
+    /// The raw bytes of the section.
+    ///
+    /// The data remains valid for as long as the object it belongs to is
+    /// loaded. Sections belonging to the executable are never unloaded.
+    pub fn data(&self) -> &'static [u8] {
+        self.data
+    }
+}
+
+/// Returns the name of the symbol marking the start of the section
+/// `section_name`, i.e. `__yk_start_<name>`, where `<name>` is the section
+/// name without its leading dot.
+///
+/// The linker's own `__start_` symbols can't be used, as section names
+/// starting with a dot are not valid C identifiers.
+pub fn start_symbol(section_name: &str) -> String {
+    symbol("start", section_name)
+}
+
+/// Returns the name of the symbol marking the end of the section
+/// `section_name`, i.e. `__yk_stop_<name>`.
+pub fn stop_symbol(section_name: &str) -> String {
+    symbol("stop", section_name)
+}
+
+fn symbol(edge: &str, section_name: &str) -> String {
+    format!("__yk_{}_{}", edge, section_name.trim_left_matches('.'))
+}
+
+/// Returns every section named `section_name` (including the leading dot),
+/// in the order that the dynamic linker reports the objects, starting with
+/// the executable.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(yk_sections)]
+/// use std::yk;
+///
+/// for sec in yk::sections(yk::MIR_CFG_SECTION) {
+///     println!("{}: {} bytes", sec.object().display(), sec.data().len());
+/// }
+/// ```
+pub fn sections(section_name: &str) -> Vec<Section> {
+    imp::sections(section_name)
+}
+
+/// Returns the MIR CFG sections of the running program.
+pub fn mir_cfg_sections() -> Vec<Section> {
+    sections(MIR_CFG_SECTION)
+}
+
+/// Returns the value promotion sections of the running program.
+pub fn promote_sections() -> Vec<Section> {
+    sections(PROMOTE_SECTION)
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use super::{Section, start_symbol, stop_symbol};
+
+    use ffi::{CStr, CString, OsStr};
+    use libc::{self, c_char, c_int, c_void, size_t};
+    use os::unix::ffi::OsStrExt;
+    use path::PathBuf;
+    use ptr;
+    use slice;
+
+    const PT_LOAD: u32 = 1;
+
+    #[cfg(target_pointer_width = "64")]
+    #[repr(C)]
+    struct Phdr {
+        p_type: u32,
+        p_flags: u32,
+        p_offset: u64,
+        p_vaddr: u64,
+        p_paddr: u64,
+        p_filesz: u64,
+        p_memsz: u64,
+        p_align: u64,
+    }
+
+    #[cfg(target_pointer_width = "32")]
+    #[repr(C)]
+    struct Phdr {
+        p_type: u32,
+        p_offset: u32,
+        p_vaddr: u32,
+        p_paddr: u32,
+        p_filesz: u32,
+        p_memsz: u32,
+        p_flags: u32,
+        p_align: u32,
+    }
+
+    #[repr(C)]
+    struct DlPhdrInfo {
+        dlpi_addr: usize,
+        dlpi_name: *const c_char,
+        dlpi_phdr: *const Phdr,
+        dlpi_phnum: u16,
+    }
+
+    extern {
+        fn dl_iterate_phdr(callback: extern fn(*mut DlPhdrInfo, size_t, *mut c_void) -> c_int,
+                           data: *mut c_void) -> c_int;
+    }
+
+    struct State {
+        start_sym: CString,
+        stop_sym: CString,
+        found: Vec<Section>,
+    }
+
+    pub fn sections(section_name: &str) -> Vec<Section> {
+        let (start_sym, stop_sym) = match (CString::new(start_symbol(section_name)),
+                                           CString::new(stop_symbol(section_name))) {
+            (Ok(start), Ok(stop)) => (start, stop),
+            _ => return Vec::new(),
+        };
+        let mut state = State { start_sym, stop_sym, found: Vec::new() };
+        unsafe {
+            dl_iterate_phdr(callback, &mut state as *mut State as *mut c_void);
+        }
+        state.found
+    }
+
+    extern fn callback(info: *mut DlPhdrInfo, _size: size_t, data: *mut c_void) -> c_int {
+        unsafe {
+            let info = &*info;
+            let state = &mut *(data as *mut State);
+
+            // The executable is reported with an empty name, and is reached by passing a null
+            // name to `dlopen`.
+            let name = if info.dlpi_name.is_null() {
+                &[][..]
+            } else {
+                CStr::from_ptr(info.dlpi_name).to_bytes()
+            };
+            let handle = if name.is_empty() {
+                libc::dlopen(ptr::null(), libc::RTLD_LAZY | libc::RTLD_NOLOAD)
+            } else {
+                libc::dlopen(info.dlpi_name, libc::RTLD_LAZY | libc::RTLD_NOLOAD)
+            };
+            if handle.is_null() {
+                return 0;
+            }
+
+            let start = libc::dlsym(handle, state.start_sym.as_ptr()) as usize;
+            let stop = libc::dlsym(handle, state.stop_sym.as_ptr()) as usize;
+            libc::dlclose(handle);
+
+            // `dlsym` also searches the dependencies of the object, so only accept symbols which
+            // live in one of this object's own segments.
+            if start != 0 && stop >= start && owns(info, start, stop) {
+                state.found.push(Section {
+                    object: PathBuf::from(OsStr::from_bytes(name)),
+                    data: slice::from_raw_parts(start as *const u8, stop - start),
+                });
+            }
+        }
+        0
+    }
+
+    unsafe fn owns(info: &DlPhdrInfo, start: usize, stop: usize) -> bool {
+        let phdrs = slice::from_raw_parts(info.dlpi_phdr, info.dlpi_phnum as usize);
+        phdrs.iter().filter(|p| p.p_type == PT_LOAD).any(|p| {
+            let seg_start = info.dlpi_addr.wrapping_add(p.p_vaddr as usize);
+            let seg_end = seg_start.wrapping_add(p.p_memsz as usize);
+            start >= seg_start && stop <= seg_end
+        })
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod imp {
+    use super::Section;
+
+    pub fn sections(_section_name: &str) -> Vec<Section> {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sections, start_symbol, stop_symbol};
+
+    #[test]
+    fn missing_section() {
+        assert!(sections(".yk_no_such_section").is_empty());
+    }
+
+    #[test]
+    fn symbol_names() {
+        assert_eq!(start_symbol(".yk_mir_cfg"), "__yk_start_yk_mir_cfg");
+        assert_eq!(stop_symbol(".yk_mir_cfg"), "__yk_stop_yk_mir_cfg");
+    }
+}
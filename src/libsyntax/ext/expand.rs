@@ -1589,6 +1589,7 @@ impl<'feat> ExpansionConfig<'feat> {
         fn enable_quotes = quote,
         fn enable_asm = asm,
         fn enable_custom_test_frameworks = custom_test_frameworks,
+        fn enable_test_fixtures = test_fixtures,
         fn enable_global_asm = global_asm,
         fn enable_log_syntax = log_syntax,
         fn enable_concat_idents = concat_idents,
@@ -502,6 +502,15 @@ declare_features! (
 
     // Allows `const _: TYPE = VALUE`
     (active, underscore_const_names, "1.31.0", Some(54912), None),
+
+    // Allows a test to set its own timeout with #[test_timeout(secs = N)]
+    (active, test_timeout, "1.31.0", None, None),
+
+    // Allows a test to be retried if it fails with #[test_flaky(retries = N)]
+    (active, test_flaky, "1.31.0", None, None),
+
+    // Allows #[test_fixture(setup)] and friends
+    (active, test_fixtures, "1.31.0", None, None),
 );
 
 declare_features! (
@@ -989,6 +998,11 @@ pub const BUILTIN_ATTRIBUTES: &'static [(&'static str, AttributeType, AttributeG
                                      "the `#[rustc_test_marker]` attribute \
                                       is used internally to track tests",
                                      cfg_fn!(rustc_attrs))),
+    ("rustc_test_fixture_marker", Normal, Gated(Stability::Unstable,
+                                             "rustc_attrs",
+                                             "the `#[rustc_test_fixture_marker]` attribute \
+                                              is used internally to track test fixtures",
+                                             cfg_fn!(rustc_attrs))),
     ("rustc_transparent_macro", Whitelisted, Gated(Stability::Unstable,
                                                    "rustc_attrs",
                                                    "used internally for testing macro hygiene",
@@ -1128,6 +1142,16 @@ pub const BUILTIN_ATTRIBUTES: &'static [(&'static str, AttributeType, AttributeG
                                  "allow_fail attribute is currently unstable",
                                  cfg_fn!(allow_fail))),
 
+    ("test_timeout", Normal, Gated(Stability::Unstable,
+                                   "test_timeout",
+                                   "the test_timeout attribute is currently unstable",
+                                   cfg_fn!(test_timeout))),
+
+    ("test_flaky", Normal, Gated(Stability::Unstable,
+                                 "test_flaky",
+                                 "the test_flaky attribute is currently unstable",
+                                 cfg_fn!(test_flaky))),
+
     ("rustc_std_internal_symbol", Whitelisted, Gated(Stability::Unstable,
                                      "rustc_attrs",
                                      "this is an internal attribute that will \
@@ -1403,6 +1427,9 @@ pub const EXPLAIN_GLOBAL_ASM: &'static str =
 pub const EXPLAIN_CUSTOM_TEST_FRAMEWORKS: &'static str =
     "custom test frameworks are an unstable feature";
 
+pub const EXPLAIN_TEST_FIXTURES: &'static str =
+    "test fixtures are an unstable feature";
+
 pub const EXPLAIN_LOG_SYNTAX: &'static str =
     "`log_syntax!` is not stable enough for use and is subject to change";
 
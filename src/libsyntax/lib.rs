@@ -137,6 +137,7 @@ pub mod util {
 }
 
 pub mod json;
+pub mod sarif;
 
 pub mod syntax {
     pub use ext;
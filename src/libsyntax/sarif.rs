@@ -0,0 +1,296 @@
+// Copyright 2018 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A SARIF 2.1.0 emitter for errors.
+//!
+//! Unlike the JSON emitter, which writes each diagnostic as it is emitted,
+//! this one collects the diagnostics of a session into the results of a
+//! single SARIF log, which is written when the emitter is dropped. Emitters
+//! can share a log, see `SarifEmitter::with_log`, which is then written when
+//! the last of them is dropped. A log without diagnostics is still written,
+//! with no results, so that a clean compile can be told apart from one which
+//! didn't run.
+//!
+//! * Each diagnostic is a result, whose rule is its error code or lint name.
+//! * Primary spans are the locations of a result; other spans, and child
+//!   notes and helps which have spans, are its related locations. Notes and
+//!   helps without spans are appended to the message.
+//! * Each substitution of a suggestion is a fix of its result.
+
+use source_map::{SourceMap, FilePathMapping};
+use syntax_pos::{Span, SpanLabel};
+use errors::registry::Registry;
+use errors::{DiagnosticBuilder, CodeSuggestion, SourceMapper};
+use errors::{DiagnosticId, Level};
+use errors::emitter::Emitter;
+
+use rustc_data_structures::sync::{self, Lrc};
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+use rustc_serialize::json::Json;
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// The results of one or more emitters, written to `dst` when dropped.
+pub struct SarifLog {
+    dst: Box<dyn Write + Send>,
+    results: Vec<Json>,
+    /// The error codes and lints of the results, with their explanations.
+    rules: BTreeMap<String, Option<&'static str>>,
+}
+
+pub type SharedSarifLog = Arc<Mutex<SarifLog>>;
+
+impl SarifLog {
+    pub fn shared(dst: Box<dyn Write + Send>) -> SharedSarifLog {
+        Arc::new(Mutex::new(SarifLog {
+            dst,
+            results: Vec::new(),
+            rules: BTreeMap::new(),
+        }))
+    }
+
+    fn to_json(&mut self) -> Json {
+        let rules = ::std::mem::replace(&mut self.rules, BTreeMap::new())
+            .into_iter()
+            .map(|(id, explanation)| {
+                let mut rule = vec![("id", Json::String(id))];
+                if let Some(explanation) = explanation {
+                    rule.push(("fullDescription", text(explanation.to_string())));
+                }
+                object(rule)
+            })
+            .collect();
+        let results = ::std::mem::replace(&mut self.results, Vec::new());
+        object(vec![
+            ("$schema", Json::String(SARIF_SCHEMA.to_string())),
+            ("version", Json::String("2.1.0".to_string())),
+            ("runs", Json::Array(vec![object(vec![
+                ("tool", object(vec![("driver", object(vec![
+                    ("name", Json::String("rustc".to_string())),
+                    ("informationUri", Json::String("https://www.rust-lang.org/".to_string())),
+                    ("rules", Json::Array(rules)),
+                ]))])),
+                ("columnKind", Json::String("unicodeCodePoints".to_string())),
+                ("results", Json::Array(results)),
+            ])])),
+        ])
+    }
+}
+
+impl Drop for SarifLog {
+    fn drop(&mut self) {
+        let log = self.to_json();
+        if let Err(e) = writeln!(&mut self.dst, "{}", log.pretty()) {
+            eprintln!("failed to write the SARIF log: {}", e);
+        }
+    }
+}
+
+pub struct SarifEmitter {
+    registry: Option<Registry>,
+    cm: Lrc<dyn SourceMapper + sync::Send + sync::Sync>,
+    log: SharedSarifLog,
+}
+
+impl SarifEmitter {
+    pub fn stderr(registry: Option<Registry>,
+                  code_map: Lrc<SourceMap>) -> SarifEmitter {
+        SarifEmitter::new(Box::new(io::stderr()), registry, code_map)
+    }
+
+    pub fn basic() -> SarifEmitter {
+        let file_path_mapping = FilePathMapping::empty();
+        SarifEmitter::stderr(None, Lrc::new(SourceMap::new(file_path_mapping)))
+    }
+
+    pub fn new(dst: Box<dyn Write + Send>,
+               registry: Option<Registry>,
+               code_map: Lrc<SourceMap>) -> SarifEmitter {
+        SarifEmitter::with_log(SarifLog::shared(dst), registry, code_map)
+    }
+
+    /// An emitter which adds its results to `log`, along with the other
+    /// emitters sharing it, e.g. those of the several handlers of a rustdoc run.
+    pub fn with_log(log: SharedSarifLog,
+                    registry: Option<Registry>,
+                    code_map: Lrc<SourceMap>) -> SarifEmitter {
+        SarifEmitter {
+            registry,
+            cm: code_map,
+            log,
+        }
+    }
+
+    fn result(&self, db: &DiagnosticBuilder) -> Json {
+        let mut message = db.message();
+        let mut related_locations = Vec::new();
+        for span_label in db.span.span_labels() {
+            if !span_label.is_primary {
+                let label = span_label.label.clone();
+                related_locations.push(self.location(span_label.span, label));
+            }
+        }
+        for child in &db.children {
+            let span = child.render_span.as_ref().unwrap_or(&child.span);
+            let child_message = format!("{}: {}", child.level.to_str(), child.message());
+            match span.primary_span() {
+                Some(sp) => related_locations.push(self.location(sp, Some(child_message))),
+                None => {
+                    message.push('\n');
+                    message.push_str(&child_message);
+                }
+            }
+        }
+
+        let locations = db.span.span_labels()
+            .into_iter()
+            .filter(|span_label| span_label.is_primary)
+            .map(|SpanLabel { span, label, .. }| self.location(span, label))
+            .collect();
+        let fixes = db.suggestions.iter().flat_map(|sugg| self.fixes(sugg)).collect();
+
+        let mut result = vec![
+            ("level", Json::String(sarif_level(db.level).to_string())),
+            ("message", text(message)),
+            ("locations", Json::Array(locations)),
+            ("relatedLocations", Json::Array(related_locations)),
+            ("fixes", Json::Array(fixes)),
+        ];
+        if let Some(ref code) = db.code {
+            let code = match *code {
+                DiagnosticId::Error(ref s) | DiagnosticId::Lint(ref s) => s.clone(),
+            };
+            let explanation = self.registry
+                .as_ref()
+                .and_then(|registry| registry.find_description(&code));
+            self.log.lock().unwrap().rules.insert(code.clone(), explanation);
+            result.push(("ruleId", Json::String(code)));
+        }
+        object(result)
+    }
+
+    fn location(&self, span: Span, label: Option<String>) -> Json {
+        let mut location = vec![("physicalLocation", object(vec![
+            ("artifactLocation", self.artifact_location(span)),
+            ("region", self.region(span)),
+        ]))];
+        if let Some(label) = label {
+            location.push(("message", text(label)));
+        }
+        object(location)
+    }
+
+    fn artifact_location(&self, span: Span) -> Json {
+        let file_name = self.cm.lookup_char_pos(span.lo()).file.name.to_string();
+        object(vec![("uri", Json::String(file_uri(&file_name)))])
+    }
+
+    fn region(&self, span: Span) -> Json {
+        let start = self.cm.lookup_char_pos(span.lo());
+        let end = self.cm.lookup_char_pos(span.hi());
+        object(vec![
+            ("startLine", Json::U64(start.line as u64)),
+            ("startColumn", Json::U64(start.col.0 as u64 + 1)),
+            ("endLine", Json::U64(end.line as u64)),
+            ("endColumn", Json::U64(end.col.0 as u64 + 1)),
+            ("byteOffset", Json::U64((span.lo().0 - start.file.start_pos.0) as u64)),
+            ("byteLength", Json::U64((span.hi().0 - span.lo().0) as u64)),
+        ])
+    }
+
+    /// A fix for each of the alternative substitutions of a suggestion.
+    fn fixes(&self, sugg: &CodeSuggestion) -> Vec<Json> {
+        sugg.substitutions.iter().map(|substitution| {
+            // The replacements of a substitution may span several files.
+            let mut changes: BTreeMap<String, Vec<Json>> = BTreeMap::new();
+            for part in &substitution.parts {
+                let file_name = self.cm.lookup_char_pos(part.span.lo()).file.name.to_string();
+                changes.entry(file_name).or_insert_with(Vec::new).push(object(vec![
+                    ("deletedRegion", self.region(part.span)),
+                    ("insertedContent", text(part.snippet.clone())),
+                ]));
+            }
+            let changes = changes.into_iter().map(|(file_name, replacements)| {
+                object(vec![
+                    ("artifactLocation", object(vec![
+                        ("uri", Json::String(file_uri(&file_name))),
+                    ])),
+                    ("replacements", Json::Array(replacements)),
+                ])
+            }).collect();
+            object(vec![
+                ("description", text(sugg.msg.clone())),
+                ("artifactChanges", Json::Array(changes)),
+                ("properties", object(vec![
+                    ("applicability",
+                     Json::String(format!("{:?}", sugg.applicability))),
+                ])),
+            ])
+        }).collect()
+    }
+}
+
+impl Emitter for SarifEmitter {
+    fn emit(&mut self, db: &DiagnosticBuilder) {
+        // "For more information about this error, try ..." isn't a result.
+        if db.level.is_failure_note() {
+            return;
+        }
+        let result = self.result(db);
+        self.log.lock().unwrap().results.push(result);
+    }
+
+    fn should_show_explain(&self) -> bool {
+        false
+    }
+}
+
+fn sarif_level(level: Level) -> &'static str {
+    match level {
+        Level::Bug | Level::Fatal | Level::PhaseFatal | Level::Error => "error",
+        Level::Warning => "warning",
+        Level::Note | Level::Help => "note",
+        Level::Cancelled | Level::FailureNote => "none",
+    }
+}
+
+fn object(fields: Vec<(&str, Json)>) -> Json {
+    Json::Object(fields.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+}
+
+/// A SARIF message or artifact content.
+fn text(text: String) -> Json {
+    object(vec![("text", Json::String(text))])
+}
+
+/// Turns a file name into a URI reference: absolute paths become `file` URIs,
+/// and relative paths stay relative, to be resolved against the working
+/// directory.
+fn file_uri(file_name: &str) -> String {
+    let path = file_name.replace('\\', "/");
+    let mut uri = String::new();
+    if path.starts_with('/') {
+        uri.push_str("file://");
+    } else if path.as_bytes().get(1) == Some(&b':') {
+        // A Windows path with a drive letter.
+        uri.push_str("file:///");
+    }
+    for byte in path.bytes() {
+        match byte {
+            b'a' ..= b'z' | b'A' ..= b'Z' | b'0' ..= b'9' |
+            b'-' | b'.' | b'_' | b'~' | b'/' | b':' => uri.push(byte as char),
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
@@ -105,6 +105,10 @@ impl FileLoader for RealFileLoader {
 // This is a SourceFile identifier that is used to correlate SourceFiles between
 // subsequent compilation sessions (which is something we need to do during
 // incremental compilation).
+//
+// The path of a file which was remapped with `--remap-path-prefix` is left out,
+// so that the incremental compilation cache stays valid when the source tree
+// is moved, or is built on another machine with the same remapping.
 #[derive(Copy, Clone, PartialEq, Eq, Hash, RustcEncodable, RustcDecodable, Debug)]
 pub struct StableFilemapId(u128);
 
@@ -114,7 +118,12 @@ impl StableFilemapId {
 
         source_file.name.hash(&mut hasher);
         source_file.name_was_remapped.hash(&mut hasher);
-        source_file.unmapped_path.hash(&mut hasher);
+        if source_file.name_was_remapped {
+            // Still tell local files apart from those imported from other crates.
+            source_file.unmapped_path.is_some().hash(&mut hasher);
+        } else {
+            source_file.unmapped_path.hash(&mut hasher);
+        }
 
         StableFilemapId(hasher.finish())
     }
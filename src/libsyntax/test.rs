@@ -52,6 +52,9 @@ struct TestCtxt<'a> {
     path: Vec<Ident>,
     ext_cx: ExtCtxt<'a>,
     test_cases: Vec<Test>,
+    /// The `#[test_fixture]`s, which are test cases as far as re-exporting
+    /// goes but are handed to the runner apart from the tests.
+    test_fixtures: Vec<Test>,
     reexport_test_harness_main: Option<Symbol>,
     is_libtest: bool,
     ctxt: SyntaxContext,
@@ -127,7 +130,11 @@ impl<'a> fold::Folder for TestHarnessGenerator<'a> {
                 span: item.span,
                 path: self.cx.path.clone(),
             };
-            self.cx.test_cases.push(test);
+            if is_test_fixture(&item) {
+                self.cx.test_fixtures.push(test);
+            } else {
+                self.cx.test_cases.push(test);
+            }
             self.tests.push(item.ident);
         }
 
@@ -280,6 +287,7 @@ fn generate_test_harness(sess: &ParseSess,
         ext_cx: ExtCtxt::new(sess, econfig, resolver),
         path: Vec::new(),
         test_cases: Vec::new(),
+        test_fixtures: Vec::new(),
         reexport_test_harness_main,
         // NB: doesn't consider the value of `--crate-name` passed on the command line.
         is_libtest: attr::find_crate_name(&krate.attrs).map(|s| s == "test").unwrap_or(false),
@@ -334,21 +342,40 @@ fn mk_main(cx: &mut TestCtxt) -> P<ast::Item> {
     //            #![main]
     //            test::test_main_static(::std::os::args().as_slice(), &[..tests]);
     //        }
+    // or, if there are fixtures,
+    //            test::test_main_static_with_fixtures(&[..tests], &[..fixtures]);
     let sp = ignored_span(cx, DUMMY_SP);
     let ecx = &cx.ext_cx;
     let test_id = ecx.ident_of("test").gensym();
 
+    // A custom test runner only gets the tests.
+    if cx.test_runner.is_some() {
+        if let Some(fixture) = cx.test_fixtures.first() {
+            cx.span_diagnostic.span_err(fixture.span,
+                "`#[test_fixture]` is not supported with a custom `#![test_runner]`");
+        }
+    }
+    let with_fixtures = cx.test_runner.is_none() && !cx.test_fixtures.is_empty();
+
     // test::test_main_static(...)
     let mut test_runner = cx.test_runner.clone().unwrap_or(
         ecx.path(sp, vec![
-            test_id, ecx.ident_of("test_main_static")
+            test_id, ecx.ident_of(if with_fixtures {
+                "test_main_static_with_fixtures"
+            } else {
+                "test_main_static"
+            })
         ]));
 
     test_runner.span = sp;
 
+    let mut test_main_args = vec![mk_tests_slice(cx, &cx.test_cases)];
+    if with_fixtures {
+        test_main_args.push(mk_tests_slice(cx, &cx.test_fixtures));
+    }
+
     let test_main_path_expr = ecx.expr_path(test_runner.clone());
-    let call_test_main = ecx.expr_call(sp, test_main_path_expr,
-                                       vec![mk_tests_slice(cx)]);
+    let call_test_main = ecx.expr_call(sp, test_main_path_expr, test_main_args);
     let call_test_main = ecx.stmt_expr(call_test_main);
 
     // #![main]
@@ -408,12 +435,12 @@ fn path_name_i(idents: &[Ident]) -> String {
 
 /// Creates a slice containing every test like so:
 /// &[path::to::test1, path::to::test2]
-fn mk_tests_slice(cx: &TestCtxt) -> P<ast::Expr> {
-    debug!("building test vector from {} tests", cx.test_cases.len());
+fn mk_tests_slice(cx: &TestCtxt, tests: &[Test]) -> P<ast::Expr> {
+    debug!("building test vector from {} tests", tests.len());
     let ref ecx = cx.ext_cx;
 
     ecx.expr_vec_slice(DUMMY_SP,
-        cx.test_cases.iter().map(|test| {
+        tests.iter().map(|test| {
             ecx.expr_addr_of(test.span,
                 ecx.expr_path(ecx.path(test.span, visible_path(cx, &test.path))))
         }).collect())
@@ -436,6 +463,10 @@ fn is_test_case(i: &ast::Item) -> bool {
     attr::contains_name(&i.attrs, "rustc_test_marker")
 }
 
+fn is_test_fixture(i: &ast::Item) -> bool {
+    attr::contains_name(&i.attrs, "rustc_test_fixture_marker")
+}
+
 fn get_test_runner(sd: &errors::Handler, krate: &ast::Crate) -> Option<ast::Path> {
     let test_attr = attr::find_by_name(&krate.attrs, "test_runner")?;
     if let Some(meta_list) = test_attr.meta_item_list() {
@@ -135,6 +135,7 @@ pub fn register_builtins(resolver: &mut dyn syntax::ext::base::Resolver,
 
     register(Symbol::intern("test_case"), MultiModifier(Box::new(test_case::expand)));
     register(Symbol::intern("test"), MultiModifier(Box::new(test::expand_test)));
+    register(Symbol::intern("test_fixture"), MultiModifier(Box::new(test::expand_test_fixture)));
     register(Symbol::intern("bench"), MultiModifier(Box::new(test::expand_bench)));
 
     // format_args uses `unstable` things internally.
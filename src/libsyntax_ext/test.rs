@@ -17,9 +17,11 @@ use syntax::ext::hygiene::{self, Mark, SyntaxContext};
 use syntax::attr;
 use syntax::ast;
 use syntax::print::pprust;
+use syntax::ptr::P;
 use syntax::symbol::Symbol;
 use syntax_pos::{DUMMY_SP, Span};
 use syntax::source_map::{ExpnInfo, MacroAttribute};
+use syntax::feature_gate;
 use std::iter;
 
 pub fn expand_test(
@@ -40,6 +42,30 @@ pub fn expand_bench(
     expand_test_or_bench(cx, attr_sp, item, true)
 }
 
+pub fn expand_test_fixture(
+    cx: &mut ExtCtxt,
+    attr_sp: Span,
+    meta_item: &ast::MetaItem,
+    item: Annotatable,
+) -> Vec<Annotatable> {
+    if !cx.ecfg.enable_test_fixtures() {
+        feature_gate::emit_feature_err(&cx.parse_sess,
+                                       "test_fixtures",
+                                       attr_sp,
+                                       feature_gate::GateIssue::Language,
+                                       feature_gate::EXPLAIN_TEST_FIXTURES);
+
+        return vec![item];
+    }
+
+    if !cx.ecfg.should_test { return vec![]; }
+
+    match fixture_kind(cx, meta_item) {
+        Some(kind) => expand(cx, attr_sp, item, TestType::Fixture(kind)),
+        None => vec![item],
+    }
+}
+
 pub fn expand_test_or_bench(
     cx: &mut ExtCtxt,
     attr_sp: Span,
@@ -49,6 +75,25 @@ pub fn expand_test_or_bench(
     // If we're not in test configuration, remove the annotated item
     if !cx.ecfg.should_test { return vec![]; }
 
+    expand(cx, attr_sp, item, if is_bench { TestType::Bench } else { TestType::Test })
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum TestType {
+    Test,
+    Bench,
+    /// A setup or teardown function, with the name of its `test::FixtureKind`.
+    Fixture(&'static str),
+}
+
+fn expand(
+    cx: &mut ExtCtxt,
+    attr_sp: Span,
+    item: Annotatable,
+    test_type: TestType,
+) -> Vec<Annotatable> {
+    let is_bench = test_type == TestType::Bench;
+
     let item =
         if let Annotatable::Item(i) = item { i }
         else {
@@ -75,7 +120,10 @@ pub fn expand_test_or_bench(
         mark.set_expn_info(ExpnInfo {
             call_site: DUMMY_SP,
             def_site: None,
-            format: MacroAttribute(Symbol::intern("test")),
+            format: MacroAttribute(Symbol::intern(match test_type {
+                TestType::Fixture(_) => "test_fixture",
+                _ => "test",
+            })),
             allow_internal_unstable: true,
             allow_internal_unsafe: false,
             local_inner_macros: false,
@@ -101,6 +149,50 @@ pub fn expand_test_or_bench(
     // creates $name: $expr
     let field = |name, expr| cx.field_imm(sp, cx.ident_of(name), expr);
 
+    // "path::to::item"
+    let item_name = cx.expr_str(sp, Symbol::intern(&item_path(
+        // skip the name of the root module
+        &cx.current_expansion.module.mod_path[1..],
+        &item.ident
+    )));
+
+    let mut attrs = vec![
+        // #[cfg(test)]
+        cx.attribute(attr_sp, cx.meta_list(attr_sp, Symbol::intern("cfg"), vec![
+            cx.meta_list_item_word(attr_sp, Symbol::intern("test"))
+        ])),
+        // #[rustc_test_marker]
+        cx.attribute(attr_sp, cx.meta_word(attr_sp, Symbol::intern("rustc_test_marker")))
+    ];
+
+    if let TestType::Fixture(kind) = test_type {
+        // #[rustc_test_fixture_marker]
+        attrs.push(cx.attribute(attr_sp,
+            cx.meta_word(attr_sp, Symbol::intern("rustc_test_fixture_marker"))));
+
+        // const $ident: test::TestFixture =
+        let fixture_const = cx.item(sp, item.ident.gensym(), attrs,
+            ast::ItemKind::Const(cx.ty(sp, ast::TyKind::Path(None, test_path("TestFixture"))),
+                // test::TestFixture {
+                cx.expr_struct(sp, test_path("TestFixture"), vec![
+                    // name: "path::to::fixture",
+                    field("name", item_name),
+                    // kind: test::FixtureKind::$kind,
+                    field("kind", cx.expr_path(cx.path(sp, vec![
+                        test_id, cx.ident_of("FixtureKind"), cx.ident_of(kind)
+                    ]))),
+                    // fixture_fn: || test::assert_test_result($fixture_fn())
+                    field("fixture_fn", cx.lambda0(sp,
+                        cx.expr_call(sp, cx.expr_path(test_path("assert_test_result")), vec![
+                            cx.expr_call(sp, cx.expr_path(cx.path(sp, vec![item.ident])), vec![])
+                        ])
+                    ))
+                // }
+                ])
+            ));
+        return finish_expansion(cx, sp, test_id, fixture_const, item);
+    }
+
     let test_fn = if is_bench {
         // A simple ident for a lambda
         let b = cx.ident_of("b");
@@ -134,15 +226,7 @@ pub fn expand_test_or_bench(
         ])
     };
 
-    let mut test_const = cx.item(sp, item.ident.gensym(),
-        vec![
-            // #[cfg(test)]
-            cx.attribute(attr_sp, cx.meta_list(attr_sp, Symbol::intern("cfg"), vec![
-                cx.meta_list_item_word(attr_sp, Symbol::intern("test"))
-            ])),
-            // #[rustc_test_marker]
-            cx.attribute(attr_sp, cx.meta_word(attr_sp, Symbol::intern("rustc_test_marker")))
-        ],
+    let test_const = cx.item(sp, item.ident.gensym(), attrs,
         // const $ident: test::TestDescAndFn =
         ast::ItemKind::Const(cx.ty(sp, ast::TyKind::Path(None, test_path("TestDescAndFn"))),
             // test::TestDescAndFn {
@@ -151,17 +235,22 @@ pub fn expand_test_or_bench(
                 field("desc", cx.expr_struct(sp, test_path("TestDesc"), vec![
                     // name: "path::to::test"
                     field("name", cx.expr_call(sp, cx.expr_path(test_path("StaticTestName")),
-                        vec![
-                            cx.expr_str(sp, Symbol::intern(&item_path(
-                                // skip the name of the root module
-                                &cx.current_expansion.module.mod_path[1..],
-                                &item.ident
-                            )))
-                        ])),
+                        vec![item_name])),
                     // ignore: true | false
                     field("ignore", cx.expr_bool(sp, should_ignore(&item))),
                     // allow_fail: true | false
                     field("allow_fail", cx.expr_bool(sp, should_fail(&item))),
+                    // timeout: None | Some(secs)
+                    field("timeout", match test_timeout(cx, &item) {
+                        None => cx.expr_none(sp),
+                        Some(secs) => cx.expr_some(sp, cx.expr_lit(sp, ast::LitKind::Int(
+                            secs as u128, ast::LitIntType::Unsigned(ast::UintTy::U64)))),
+                    }),
+                    // retries: None | Some(n)
+                    field("retries", match flaky_retries(cx, &item) {
+                        None => cx.expr_none(sp),
+                        Some(n) => cx.expr_some(sp, cx.expr_u32(sp, n)),
+                    }),
                     // should_panic: ...
                     field("should_panic", match should_panic(cx, &item) {
                         // test::ShouldPanic::No
@@ -181,7 +270,20 @@ pub fn expand_test_or_bench(
             ])
         // }
         ));
-    test_const = test_const.map(|mut tc| { tc.vis.node = ast::VisibilityKind::Public; tc});
+
+    finish_expansion(cx, sp, test_id, test_const, item)
+}
+
+/// Returns the generated `test_const` along with the original item and the
+/// `extern crate test` it refers to.
+fn finish_expansion(
+    cx: &ExtCtxt,
+    sp: Span,
+    test_id: ast::Ident,
+    test_const: P<ast::Item>,
+    item: P<ast::Item>,
+) -> Vec<Annotatable> {
+    let test_const = test_const.map(|mut tc| { tc.vis.node = ast::VisibilityKind::Public; tc});
 
     // extern crate test as test_gensym
     let test_extern = cx.item(sp,
@@ -212,6 +314,31 @@ enum ShouldPanic {
     Yes(Option<Symbol>),
 }
 
+fn fixture_kind(cx: &ExtCtxt, meta_item: &ast::MetaItem) -> Option<&'static str> {
+    // Handle #[test_fixture(setup)] and the like
+    let kind = meta_item.meta_item_list().and_then(|list| {
+        if list.len() != 1 {
+            return None;
+        }
+        let word = list[0].word()?;
+        Some(match &*word.name().as_str() {
+            "setup" => "Setup",
+            "teardown" => "Teardown",
+            "module_setup" => "ModuleSetup",
+            "module_teardown" => "ModuleTeardown",
+            _ => return None,
+        })
+    });
+    if kind.is_none() {
+        cx.parse_sess.span_diagnostic.span_err(
+            meta_item.span,
+            "attribute must be of the form: `#[test_fixture(KIND)]`, where KIND is one of \
+             `setup`, `teardown`, `module_setup` or `module_teardown`"
+        );
+    }
+    kind
+}
+
 fn should_ignore(i: &ast::Item) -> bool {
     attr::contains_name(&i.attrs, "ignore")
 }
@@ -220,6 +347,48 @@ fn should_fail(i: &ast::Item) -> bool {
     attr::contains_name(&i.attrs, "allow_fail")
 }
 
+fn test_timeout(cx: &ExtCtxt, i: &ast::Item) -> Option<u64> {
+    parse_single_uint_attr(cx, i, "test_timeout", "secs", u64::max_value() as u128)
+        .map(|secs| secs as u64)
+}
+
+fn flaky_retries(cx: &ExtCtxt, i: &ast::Item) -> Option<u32> {
+    parse_single_uint_attr(cx, i, "test_flaky", "retries", u32::max_value() as u128)
+        .map(|n| n as u32)
+}
+
+/// Parses `#[attr(key = N)]`, where N is between 1 and `max`. Returns `None` if
+/// the item has no such attribute, and reports an error if it has one of any
+/// other form.
+fn parse_single_uint_attr(cx: &ExtCtxt, item: &ast::Item, attr: &str, key: &str, max: u128)
+                          -> Option<u128> {
+    let found = attr::find_by_name(&item.attrs, attr)?;
+    let n = found.meta_item_list().and_then(|list| {
+        if list.len() != 1 {
+            return None;
+        }
+        let mi = list[0].meta_item()?;
+        if !mi.check_name(key) {
+            return None;
+        }
+        match mi.node {
+            ast::MetaItemKind::NameValue(ref lit) => match lit.node {
+                ast::LitKind::Int(n, _) if n > 0 && n <= max => Some(n),
+                _ => None,
+            },
+            _ => None,
+        }
+    });
+    if n.is_none() {
+        cx.parse_sess.span_diagnostic.span_err(
+            found.span(),
+            &format!("attribute must be of the form: `#[{}({} = N)]`, where N is at least 1",
+                     attr, key)
+        );
+    }
+    n
+}
+
 fn should_panic(cx: &ExtCtxt, i: &ast::Item) -> ShouldPanic {
     match attr::find_by_name(&i.attrs, "should_panic") {
         Some(attr) => {
@@ -0,0 +1,351 @@
+// Copyright 2018 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Setup and teardown functions declared with `#[test_fixture(..)]`.
+//!
+//! Fixtures reach libtest apart from the tests, as `TestFixture`s in
+//! `Options::fixtures`, and apply to the tests in the module which declares
+//! them and in its submodules. Per-test fixtures are run in the test's own
+//! thread (or process), around the test. Module fixtures are run by
+//! `run_tests`, before the first test of the module starts and after the last
+//! one finishes.
+
+use std::any::Any;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use super::{Sink, TestDesc, TestDescAndFn, TestName};
+use super::TestFn::*;
+use super::TestName::*;
+
+/// A setup or teardown function, as declared by `#[test_fixture(..)]`.
+#[derive(Clone, Copy, Debug)]
+pub struct TestFixture {
+    /// The path of the function, e.g. `a::b::setup`.
+    pub name: &'static str,
+    pub kind: FixtureKind,
+    pub fixture_fn: fn(),
+}
+
+/// When a fixture is run.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum FixtureKind {
+    /// Before each test.
+    Setup,
+    /// After each test, whether it passed or not.
+    Teardown,
+    /// Once, before the first test.
+    ModuleSetup,
+    /// Once, after the last test.
+    ModuleTeardown,
+}
+
+impl FixtureKind {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            FixtureKind::Setup => "setup",
+            FixtureKind::Teardown => "teardown",
+            FixtureKind::ModuleSetup => "module setup",
+            FixtureKind::ModuleTeardown => "module teardown",
+        }
+    }
+}
+
+/// A fixture which panicked. These are reported apart from test results: a
+/// failing setup also fails the tests it was for, but a failing teardown
+/// doesn't.
+#[derive(Clone, Debug)]
+pub struct FixtureFailure {
+    pub kind: FixtureKind,
+    pub fixture: TestName,
+    /// The test a per-test fixture was run for.
+    pub test: Option<TestName>,
+    pub message: String,
+    /// Output captured while running a module fixture. The output of a
+    /// per-test fixture is part of the test's.
+    pub stdout: Vec<u8>,
+}
+
+/// Where the failures of fixtures run for a test are collected.
+pub(crate) type FailureSink = Arc<Mutex<Vec<FixtureFailure>>>;
+
+/// The panic payload with which a test whose setup failed is ended, so that
+/// `calc_result` fails it even if it should panic.
+pub(crate) struct SetupFailed(pub String);
+
+#[derive(Clone)]
+struct Fixture {
+    name: TestName,
+    /// The module declaring the fixture, e.g. `a::b`, or `` for the crate root.
+    module: String,
+    kind: FixtureKind,
+    f: fn(),
+}
+
+impl Fixture {
+    fn applies_to(&self, desc: &TestDesc) -> bool {
+        contains(&self.module, desc.name.as_slice())
+    }
+}
+
+fn contains(module: &str, test: &str) -> bool {
+    module.is_empty() || (test.starts_with(module) && test[module.len()..].starts_with("::"))
+}
+
+fn depth(module: &str) -> usize {
+    if module.is_empty() { 0 } else { module.matches("::").count() + 1 }
+}
+
+/// Runs the fixtures of a test run.
+pub(crate) struct FixtureRunner {
+    fixtures: Vec<Fixture>,
+    nocapture: bool,
+    /// The number of tests yet to finish in each module with module fixtures.
+    remaining: HashMap<String, usize>,
+    /// Modules whose module setup has been run.
+    started: HashSet<String>,
+    /// Modules whose module setup failed, with the message to fail their
+    /// tests with.
+    failed_setups: HashMap<String, String>,
+    failures: FailureSink,
+}
+
+impl FixtureRunner {
+    pub fn new(fixtures: &[&TestFixture], nocapture: bool) -> FixtureRunner {
+        let mut fixtures: Vec<_> = fixtures.iter().map(|fixture| {
+            let module = match fixture.name.rfind("::") {
+                Some(idx) => fixture.name[..idx].to_owned(),
+                None => String::new(),
+            };
+            Fixture {
+                name: StaticTestName(fixture.name),
+                module,
+                kind: fixture.kind,
+                f: fixture.fixture_fn,
+            }
+        }).collect();
+        // Outer modules' fixtures come first, in the order they were declared.
+        fixtures.sort_by_key(|fixture| depth(&fixture.module));
+        FixtureRunner {
+            fixtures,
+            nocapture,
+            remaining: HashMap::new(),
+            started: HashSet::new(),
+            failed_setups: HashMap::new(),
+            failures: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    fn module_fixtures<'a>(&'a self, desc: &'a TestDesc, kind: FixtureKind)
+        -> impl Iterator<Item = &'a Fixture> + 'a {
+        self.fixtures.iter().filter(move |f| f.kind == kind && f.applies_to(desc))
+    }
+
+    /// Counts the tests which will be run, so that module teardowns can be
+    /// run once the last of them finishes.
+    pub fn expect(&mut self, desc: &TestDesc) {
+        let modules: HashSet<String> = self.fixtures
+            .iter()
+            .filter(|f| f.kind == FixtureKind::ModuleTeardown && f.applies_to(desc))
+            .map(|f| f.module.clone())
+            .collect();
+        for module in modules {
+            *self.remaining.entry(module).or_insert(0) += 1;
+        }
+    }
+
+    /// Wraps a test in its per-test fixtures.
+    pub fn wrap(&self, test: TestDescAndFn) -> TestDescAndFn {
+        let setups: Vec<_> =
+            self.module_fixtures(&test.desc, FixtureKind::Setup).cloned().collect();
+        let mut teardowns: Vec<_> =
+            self.module_fixtures(&test.desc, FixtureKind::Teardown).cloned().collect();
+        // Inner modules tear down first.
+        teardowns.reverse();
+        if setups.is_empty() && teardowns.is_empty() {
+            return test;
+        }
+        let TestDescAndFn { desc, testfn } = test;
+        let mut run: Box<dyn FnMut() + Send> = match testfn {
+            StaticTestFn(f) => Box::new(f),
+            DynTestFn(f) => {
+                let mut f = Some(f);
+                Box::new(move || (f.take().unwrap())())
+            }
+            // Benchmarks are measured without fixtures.
+            testfn => return TestDescAndFn { desc, testfn },
+        };
+        let failures = self.failures.clone();
+        let name = desc.name.clone();
+        let testfn = DynTestFn(Box::new(move || {
+            for setup in &setups {
+                if let Err(e) = catch_unwind(setup.f) {
+                    resume_unwind(Box::new(SetupFailed(format!(
+                        "setup `{}` failed: {}", setup.name, panic_message(&*e)
+                    ))));
+                }
+            }
+            let result = catch_unwind(AssertUnwindSafe(|| run()));
+            for teardown in &teardowns {
+                if let Err(e) = catch_unwind(teardown.f) {
+                    failures.lock().unwrap().push(FixtureFailure {
+                        kind: FixtureKind::Teardown,
+                        fixture: teardown.name.clone(),
+                        test: Some(name.clone()),
+                        message: panic_message(&*e),
+                        stdout: Vec::new(),
+                    });
+                }
+            }
+            if let Err(e) = result {
+                resume_unwind(e);
+            }
+        }));
+        TestDescAndFn { desc, testfn }
+    }
+
+    /// Runs the module setups a test needs which haven't run yet. Returns the
+    /// message to fail the test with if one of them failed.
+    pub fn before(&mut self, desc: &TestDesc) -> Result<(), String> {
+        // All of a module's setups are run for its first test.
+        let setups: Vec<_> = self.module_fixtures(desc, FixtureKind::ModuleSetup)
+            .filter(|f| !self.started.contains(&f.module))
+            .cloned()
+            .collect();
+        for setup in setups {
+            self.started.insert(setup.module.clone());
+            if self.failed_setups.contains_key(&setup.module) {
+                continue;
+            }
+            if let Err(failure) = self.run_module_fixture(&setup) {
+                let msg = format!("module setup `{}` failed: {}", setup.name, failure.message);
+                self.failed_setups.insert(setup.module.clone(), msg);
+                self.failures.lock().unwrap().push(failure);
+            }
+        }
+        for (module, msg) in &self.failed_setups {
+            if contains(module, desc.name.as_slice()) {
+                return Err(msg.clone());
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs the module teardowns of the modules whose last test `desc` was.
+    pub fn after(&mut self, desc: &TestDesc) {
+        let mut finished = Vec::new();
+        for (module, remaining) in self.remaining.iter_mut() {
+            if contains(module, desc.name.as_slice()) {
+                *remaining -= 1;
+                if *remaining == 0 {
+                    finished.push(module.clone());
+                }
+            }
+        }
+        // Inner modules tear down first.
+        finished.sort_by_key(|module| Reverse(depth(module)));
+        for module in finished {
+            self.remaining.remove(&module);
+            if self.failed_setups.contains_key(&module) {
+                continue;
+            }
+            let teardowns: Vec<_> = self.fixtures
+                .iter()
+                .filter(|f| f.kind == FixtureKind::ModuleTeardown && f.module == module)
+                .cloned()
+                .collect();
+            for teardown in teardowns.iter().rev() {
+                if let Err(failure) = self.run_module_fixture(teardown) {
+                    self.failures.lock().unwrap().push(failure);
+                }
+            }
+        }
+    }
+
+    /// Fixture failures since the last call.
+    pub fn take_failures(&self) -> Vec<FixtureFailure> {
+        ::std::mem::replace(&mut *self.failures.lock().unwrap(), Vec::new())
+    }
+
+    /// Where the teardown failures of tests run in child processes are
+    /// added, to be reported by `take_failures`.
+    pub fn failure_sink(&self) -> FailureSink {
+        self.failures.clone()
+    }
+
+    fn run_module_fixture(&self, fixture: &Fixture) -> Result<(), FixtureFailure> {
+        let data = Arc::new(Mutex::new(Vec::new()));
+        let oldio = if !self.nocapture {
+            Some((
+                io::set_print(Some(Box::new(Sink(data.clone())))),
+                io::set_panic(Some(Box::new(Sink(data.clone())))),
+            ))
+        } else {
+            None
+        };
+
+        let result = catch_unwind(fixture.f);
+
+        if let Some((printio, panicio)) = oldio {
+            io::set_print(printio);
+            io::set_panic(panicio);
+        }
+
+        result.map_err(|e| FixtureFailure {
+            kind: fixture.kind,
+            fixture: fixture.name.clone(),
+            test: None,
+            message: panic_message(&*e),
+            stdout: data.lock().unwrap().clone(),
+        })
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload.downcast_ref::<String>()
+        .map(|s| s.clone())
+        .or_else(|| payload.downcast_ref::<&'static str>().map(|s| s.to_string()))
+        .unwrap_or_else(|| "panicked".to_string())
+}
+
+/// Writes the failed teardowns of a test run in a child process to `path`, for
+/// the parent to read with `read_teardown_report`. Each failure is the name of
+/// the fixture and its message, each followed by a NUL byte.
+pub(crate) fn write_teardown_report(path: &Path, failures: &[FixtureFailure]) -> io::Result<()> {
+    let mut report = Vec::new();
+    for failure in failures {
+        write!(report, "{}\0{}\0", failure.fixture, failure.message)?;
+    }
+    fs::File::create(path)?.write_all(&report)
+}
+
+/// Reads and removes the report of the child process which ran `test`, if it
+/// wrote one.
+pub(crate) fn read_teardown_report(path: &Path, test: &TestName) -> Vec<FixtureFailure> {
+    let mut report = String::new();
+    let read = fs::File::open(path).and_then(|mut file| file.read_to_string(&mut report));
+    let _ = fs::remove_file(path);
+    if read.is_err() {
+        return Vec::new();
+    }
+    let fields: Vec<&str> = report.split('\0').collect();
+    fields.chunks(2).filter(|pair| pair.len() == 2).map(|pair| FixtureFailure {
+        kind: FixtureKind::Teardown,
+        fixture: DynTestName(pair[0].to_owned()),
+        test: Some(test.clone()),
+        message: pair[1].to_owned(),
+        stdout: Vec::new(),
+    }).collect()
+}
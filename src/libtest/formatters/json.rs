@@ -8,15 +8,65 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+//! JSON output, one event object per line.
+//!
+//! Without `--json-schema-version` the events are the original, unversioned
+//! ones. With `--json-schema-version 1` every event has a `type` and an
+//! `event`, names are escaped, and:
+//!
+//! * `suite` `started` has the `schema_version`, the `test_count`, a
+//!   `timestamp` and, if the tests were shuffled, the `shuffle_seed`.
+//! * `test` `started` has the `name` and a `timestamp`.
+//! * `test` `ok`, `failed`, `ignored`, `allowed_failure` and `flaky` have the
+//!   `name`, the `exec_time` (except when ignored), the captured `stdout` if
+//!   there was any, whatever the outcome, a `message` if the test failed with
+//!   one and, if flaky, the number of `failures` before it passed.
+//! * `test` `timeout` has the `name` and a `timestamp`.
+//! * `bench` `measured` has the `name`, the `exec_time`, the `median` and
+//!   `deviation` of an iteration in nanoseconds, the `mib_per_second` if
+//!   known and the captured `stdout` if there was any.
+//! * `bench` `compared` has the `name`, the `baseline` and the `change`, as
+//!   well as `change_pct` and `noise_pct` unless the benchmark was added or
+//!   removed.
+//! * `fixture` `failed` has the `name` and `kind` of the fixture, the `test`
+//!   a per-test fixture was run for, the `message` and any captured `stdout`.
+//! * `suite` `ok` and `failed` have the counts of tests by outcome and the
+//!   `exec_time` of the whole run.
+//!
+//! Timestamps are seconds since the Unix epoch and times are in seconds, both
+//! to the millisecond. New fields may be added to a version of the schema,
+//! but none are removed or changed.
+
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use super::*;
 
+/// The latest version of the schema, see the module documentation.
+pub(crate) const JSON_SCHEMA_VERSION: u32 = 1;
+
 pub(crate) struct JsonFormatter<T> {
     out: OutputLocation<T>,
+    /// The version of the schema to follow, if any.
+    schema_version: Option<u32>,
+    /// When each running test was started, to compute its `exec_time`.
+    started: HashMap<TestDesc, Instant>,
+    run_start: Instant,
 }
 
 impl<T: Write> JsonFormatter<T> {
-    pub fn new(out: OutputLocation<T>) -> Self {
-        Self { out }
+    pub fn new(out: OutputLocation<T>, schema_version: Option<u32>) -> Self {
+        Self {
+            out,
+            schema_version,
+            started: HashMap::new(),
+            run_start: Instant::now(),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn output_location(&self) -> &OutputLocation<T> {
+        &self.out
     }
 
     fn write_message(&mut self, s: &str) -> io::Result<()> {
@@ -45,21 +95,105 @@ impl<T: Write> JsonFormatter<T> {
             ))
         }
     }
-}
 
-impl<T: Write> OutputFormatter for JsonFormatter<T> {
-    fn write_run_start(&mut self, test_count: usize) -> io::Result<()> {
+    /// Writes the outcome of a test in the versioned schema.
+    fn write_versioned_result(
+        &mut self,
+        desc: &TestDesc,
+        result: &TestResult,
+        stdout: &[u8],
+        exec_time: Duration,
+    ) -> io::Result<()> {
+        let (ty, evt, details) = match *result {
+            TrOk => ("test", "ok", String::new()),
+            TrFailed => ("test", "failed", String::new()),
+            TrFailedMsg(ref m) => {
+                ("test", "failed", format!(r#", "message": "{}""#, EscapedString(m)))
+            }
+            TrIgnored => ("test", "ignored", String::new()),
+            TrAllowedFail => ("test", "allowed_failure", String::new()),
+            TrFlaky(failures) => ("test", "flaky", format!(r#", "failures": {}"#, failures)),
+            TrBench(ref bs) => {
+                let mbps = if bs.mb_s == 0 {
+                    String::new()
+                } else {
+                    format!(r#", "mib_per_second": {}"#, bs.mb_s)
+                };
+                let details = format!(
+                    r#", "median": {}, "deviation": {}{}"#,
+                    bs.ns_iter_summ.median as usize,
+                    (bs.ns_iter_summ.max - bs.ns_iter_summ.min) as usize,
+                    mbps
+                );
+                ("bench", "measured", details)
+            }
+        };
+        let exec_time = match *result {
+            TrIgnored => String::new(),
+            _ => format!(r#", "exec_time": {}"#, fmt_secs(exec_time)),
+        };
+        let stdout = if stdout.is_empty() {
+            String::new()
+        } else {
+            format!(r#", "stdout": "{}""#, EscapedString(String::from_utf8_lossy(stdout)))
+        };
         self.write_message(&*format!(
-            r#"{{ "type": "suite", "event": "started", "test_count": {} }}"#,
-            test_count
+            r#"{{ "type": "{}", "event": "{}", "name": "{}"{}{}{} }}"#,
+            ty,
+            evt,
+            EscapedString(desc.name.as_slice()),
+            exec_time,
+            details,
+            stdout
         ))
     }
+}
+
+/// The current time, in seconds since the Unix epoch.
+fn timestamp() -> String {
+    fmt_secs(SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or(Duration::new(0, 0)))
+}
+
+impl<T: Write> OutputFormatter for JsonFormatter<T> {
+    fn write_run_start(&mut self, test_count: usize, shuffle_seed: Option<u64>)
+        -> io::Result<()> {
+        self.run_start = Instant::now();
+        let shuffle_seed = match shuffle_seed {
+            Some(seed) => format!(r#", "shuffle_seed": {}"#, seed),
+            None => String::new(),
+        };
+        match self.schema_version {
+            Some(version) => self.write_message(&*format!(
+                "{{ \"type\": \"suite\", \
+                 \"event\": \"started\", \
+                 \"schema_version\": {}, \
+                 \"test_count\": {}, \
+                 \"timestamp\": {}{} }}",
+                version,
+                test_count,
+                timestamp(),
+                shuffle_seed
+            )),
+            None => self.write_message(&*format!(
+                r#"{{ "type": "suite", "event": "started", "test_count": {}{} }}"#,
+                test_count, shuffle_seed
+            )),
+        }
+    }
 
     fn write_test_start(&mut self, desc: &TestDesc) -> io::Result<()> {
-        self.write_message(&*format!(
-            r#"{{ "type": "test", "event": "started", "name": "{}" }}"#,
-            desc.name
-        ))
+        self.started.insert(desc.clone(), Instant::now());
+        match self.schema_version {
+            Some(_) => self.write_message(&*format!(
+                r#"{{ "type": "test", "event": "started", "name": "{}", "timestamp": {} }}"#,
+                EscapedString(desc.name.as_slice()),
+                timestamp()
+            )),
+            None => self.write_message(&*format!(
+                r#"{{ "type": "test", "event": "started", "name": "{}" }}"#,
+                desc.name
+            )),
+        }
     }
 
     fn write_result(
@@ -68,6 +202,14 @@ impl<T: Write> OutputFormatter for JsonFormatter<T> {
         result: &TestResult,
         stdout: &[u8],
     ) -> io::Result<()> {
+        let exec_time = self.started
+            .remove(desc)
+            .map(|start| start.elapsed())
+            .unwrap_or(Duration::new(0, 0));
+        if self.schema_version.is_some() {
+            return self.write_versioned_result(desc, result, stdout, exec_time);
+        }
+
         match *result {
             TrOk => self.write_event("test", desc.name.as_slice(), "ok", None),
 
@@ -97,6 +239,13 @@ impl<T: Write> OutputFormatter for JsonFormatter<T> {
                 self.write_event("test", desc.name.as_slice(), "allowed_failure", None)
             }
 
+            TrFlaky(failures) => self.write_event(
+                "test",
+                desc.name.as_slice(),
+                "flaky",
+                Some(format!(r#""failures": {}"#, failures)),
+            ),
+
             TrBench(ref bs) => {
                 let median = bs.ns_iter_summ.median as usize;
                 let deviation = (bs.ns_iter_summ.max - bs.ns_iter_summ.min) as usize;
@@ -121,32 +270,103 @@ impl<T: Write> OutputFormatter for JsonFormatter<T> {
     }
 
     fn write_timeout(&mut self, desc: &TestDesc) -> io::Result<()> {
-        self.write_message(&*format!(
-            r#"{{ "type": "test", "event": "timeout", "name": "{}" }}"#,
-            desc.name
-        ))
+        match self.schema_version {
+            Some(_) => self.write_message(&*format!(
+                r#"{{ "type": "test", "event": "timeout", "name": "{}", "timestamp": {} }}"#,
+                EscapedString(desc.name.as_slice()),
+                timestamp()
+            )),
+            None => self.write_message(&*format!(
+                r#"{{ "type": "test", "event": "timeout", "name": "{}" }}"#,
+                desc.name
+            )),
+        }
+    }
+
+    fn write_fixture_failure(&mut self, failure: &FixtureFailure) -> io::Result<()> {
+        let test = match failure.test {
+            Some(ref test) => format!(r#""test": "{}", "#, EscapedString(test.as_slice())),
+            None => String::new(),
+        };
+        let stdout = if failure.stdout.is_empty() {
+            String::new()
+        } else {
+            format!(
+                r#", "stdout": "{}""#,
+                EscapedString(String::from_utf8_lossy(&failure.stdout))
+            )
+        };
+        self.write_event(
+            "fixture",
+            &EscapedString(failure.fixture.as_slice()).to_string(),
+            "failed",
+            Some(format!(
+                r#""kind": "{}", {}"message": "{}"{}"#,
+                failure.kind.as_str(),
+                test,
+                EscapedString(&failure.message),
+                stdout
+            )),
+        )
     }
 
     fn write_run_finish(&mut self, state: &ConsoleTestState) -> io::Result<bool> {
+        let success = state.failed == 0 && state.fixture_failures.is_empty();
+        if let Some((ref baseline, ref diff)) = state.baseline_changes {
+            for (name, change) in diff {
+                let (kind, figures) = match *change {
+                    MetricChange::MetricAdded => ("added", None),
+                    MetricChange::MetricRemoved => ("removed", None),
+                    MetricChange::LikelyNoise(pct, noise) => ("noise", Some((pct, noise))),
+                    MetricChange::Improvement(pct, noise) => ("improvement", Some((pct, noise))),
+                    MetricChange::Regression(pct, noise) => ("regression", Some((pct, noise))),
+                };
+                let figures = match figures {
+                    Some((pct, noise)) => {
+                        format!(r#", "change_pct": {}, "noise_pct": {}"#, pct, noise)
+                    }
+                    None => String::new(),
+                };
+                self.write_message(&*format!(
+                    "{{ \"type\": \"bench\", \
+                     \"event\": \"compared\", \
+                     \"name\": \"{}\", \
+                     \"baseline\": \"{}\", \
+                     \"change\": \"{}\"{} }}",
+                    EscapedString(name),
+                    EscapedString(baseline),
+                    kind,
+                    figures
+                ))?;
+            }
+        }
+
+        let exec_time = match self.schema_version {
+            Some(_) => format!(r#", "exec_time": {}"#, fmt_secs(self.run_start.elapsed())),
+            None => String::new(),
+        };
         self.write_message(&*format!(
             "{{ \"type\": \"suite\", \
              \"event\": \"{}\", \
              \"passed\": {}, \
              \"failed\": {}, \
              \"allowed_fail\": {}, \
+             \"flaky\": {}, \
              \"ignored\": {}, \
              \"measured\": {}, \
-             \"filtered_out\": {} }}",
-            if state.failed == 0 { "ok" } else { "failed" },
+             \"filtered_out\": {}{} }}",
+            if success { "ok" } else { "failed" },
             state.passed,
             state.failed + state.allowed_fail,
             state.allowed_fail,
+            state.flaky.len(),
             state.ignored,
             state.measured,
-            state.filtered_out
+            state.filtered_out,
+            exec_time
         ))?;
 
-        Ok(state.failed == 0)
+        Ok(success)
     }
 }
 
@@ -0,0 +1,250 @@
+// Copyright 2018 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::HashMap;
+
+use super::*;
+
+/// Writes a JUnit XML report. JUnit is a single document rather than a
+/// stream, so results are buffered and written once the run is finished.
+pub(crate) struct JunitFormatter<T> {
+    out: OutputLocation<T>,
+    /// When each running test was started, to compute its duration.
+    started: HashMap<TestDesc, Instant>,
+    results: Vec<(TestDesc, TestResult, Duration, Vec<u8>)>,
+    run_start: Instant,
+    shuffle_seed: Option<u64>,
+}
+
+impl<T: Write> JunitFormatter<T> {
+    pub fn new(out: OutputLocation<T>) -> Self {
+        Self {
+            out,
+            started: HashMap::new(),
+            results: Vec::new(),
+            run_start: Instant::now(),
+            shuffle_seed: None,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn output_location(&self) -> &OutputLocation<T> {
+        &self.out
+    }
+
+    fn write_testcase(
+        &mut self,
+        desc: &TestDesc,
+        result: &TestResult,
+        duration: Duration,
+        stdout: &[u8],
+    ) -> io::Result<()> {
+        let (class_name, test_name) = split_name(desc.name.as_slice());
+        write!(
+            self.out,
+            r#"    <testcase classname="{}" name="{}" time="{}""#,
+            XmlEscaped(class_name),
+            XmlEscaped(test_name),
+            fmt_secs(duration)
+        )?;
+
+        let failure = match *result {
+            TrOk | TrBench(_) => None,
+            TrIgnored => {
+                return self.out.write_all(b">\n      <skipped/>\n    </testcase>\n");
+            }
+            TrFailed => Some(match desc.should_panic {
+                ShouldPanic::No => "test failed".to_owned(),
+                _ => "test did not panic as expected".to_owned(),
+            }),
+            TrFailedMsg(ref msg) => Some(msg.clone()),
+            TrAllowedFail | TrFlaky(_) => None,
+        };
+        // Passing after a rerun is recorded the way Maven Surefire does.
+        let flaky = match *result {
+            TrFlaky(failures) => Some(failures),
+            _ => None,
+        };
+
+        // The panic message of a should-panic test is kept, as it shows what was
+        // actually caught.
+        let show_stdout = (failure.is_some() || flaky.is_some()
+            || desc.should_panic != ShouldPanic::No) && !stdout.is_empty();
+        if failure.is_none() && flaky.is_none() && !show_stdout {
+            return self.out.write_all(b"/>\n");
+        }
+
+        self.out.write_all(b">\n")?;
+        if let Some(msg) = failure {
+            writeln!(
+                self.out,
+                r#"      <failure type="{}" message="{}"/>"#,
+                if desc.should_panic == ShouldPanic::No { "failure" } else { "should_panic" },
+                XmlEscaped(&msg)
+            )?;
+        }
+        if let Some(failures) = flaky {
+            writeln!(
+                self.out,
+                r#"      <flakyFailure type="flaky" message="failed {} times before passing"/>"#,
+                failures
+            )?;
+        }
+        if show_stdout {
+            writeln!(
+                self.out,
+                "      <system-out>{}</system-out>",
+                XmlEscaped(&String::from_utf8_lossy(stdout))
+            )?;
+        }
+        self.out.write_all(b"    </testcase>\n")
+    }
+
+    /// Fixtures aren't tests, so their failures are recorded as errors of a
+    /// testcase named after the fixture.
+    fn write_fixture_error(&mut self, failure: &FixtureFailure) -> io::Result<()> {
+        let (class_name, fixture_name) = split_name(failure.fixture.as_slice());
+        writeln!(
+            self.out,
+            r#"    <testcase classname="{}" name="{}" time="0">"#,
+            XmlEscaped(class_name),
+            XmlEscaped(fixture_name)
+        )?;
+        let message = match failure.test {
+            Some(ref test) => format!("{} (for {})", failure.message, test),
+            None => failure.message.clone(),
+        };
+        writeln!(
+            self.out,
+            r#"      <error type="{}" message="{}"/>"#,
+            failure.kind.as_str(),
+            XmlEscaped(&message)
+        )?;
+        if !failure.stdout.is_empty() {
+            writeln!(
+                self.out,
+                "      <system-out>{}</system-out>",
+                XmlEscaped(&String::from_utf8_lossy(&failure.stdout))
+            )?;
+        }
+        self.out.write_all(b"    </testcase>\n")
+    }
+}
+
+impl<T: Write> OutputFormatter for JunitFormatter<T> {
+    fn write_run_start(&mut self, _test_count: usize, shuffle_seed: Option<u64>)
+        -> io::Result<()> {
+        self.run_start = Instant::now();
+        self.shuffle_seed = shuffle_seed;
+        Ok(())
+    }
+
+    fn write_test_start(&mut self, desc: &TestDesc) -> io::Result<()> {
+        self.started.insert(desc.clone(), Instant::now());
+        Ok(())
+    }
+
+    fn write_result(
+        &mut self,
+        desc: &TestDesc,
+        result: &TestResult,
+        stdout: &[u8],
+    ) -> io::Result<()> {
+        let duration = match *result {
+            // Benchmarks report the time of one iteration, which is more useful
+            // than the time taken to measure it.
+            TrBench(ref bs) => Duration::from_nanos(bs.ns_iter_summ.median as u64),
+            _ => self.started
+                .remove(desc)
+                .map(|start| start.elapsed())
+                .unwrap_or(Duration::new(0, 0)),
+        };
+        self.results.push((desc.clone(), result.clone(), duration, stdout.to_vec()));
+        Ok(())
+    }
+
+    fn write_timeout(&mut self, _desc: &TestDesc) -> io::Result<()> {
+        // The test's eventual result is reported as usual.
+        Ok(())
+    }
+
+    fn write_fixture_failure(&mut self, _failure: &FixtureFailure) -> io::Result<()> {
+        // Fixture failures are written as errors once the run is finished.
+        Ok(())
+    }
+
+    fn write_run_finish(&mut self, state: &ConsoleTestState) -> io::Result<bool> {
+        self.out.write_all(b"<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n")?;
+        self.out.write_all(b"<testsuites>\n")?;
+        writeln!(
+            self.out,
+            "  <testsuite name=\"test\" package=\"test\" id=\"0\" errors=\"{}\" \
+             failures=\"{}\" tests=\"{}\" skipped=\"{}\" time=\"{}\">",
+            state.fixture_failures.len(),
+            state.failed,
+            self.results.len(),
+            state.ignored,
+            fmt_secs(self.run_start.elapsed())
+        )?;
+        if let Some(seed) = self.shuffle_seed {
+            writeln!(
+                self.out,
+                "    <properties>\n      \
+                 <property name=\"shuffle_seed\" value=\"{}\"/>\n    \
+                 </properties>",
+                seed
+            )?;
+        }
+
+        let results = ::std::mem::replace(&mut self.results, Vec::new());
+        for (desc, result, duration, stdout) in results {
+            self.write_testcase(&desc, &result, duration, &stdout)?;
+        }
+        for failure in &state.fixture_failures {
+            self.write_fixture_error(failure)?;
+        }
+
+        self.out.write_all(b"  </testsuite>\n")?;
+        self.out.write_all(b"</testsuites>\n")?;
+
+        Ok(state.failed == 0 && state.fixture_failures.is_empty())
+    }
+}
+
+/// Splits a test name into the module path, which JUnit calls the class, and
+/// the name of the test itself. Tests at the crate root get the class `crate`.
+fn split_name(name: &str) -> (&str, &str) {
+    match name.rfind("::") {
+        Some(idx) => (&name[..idx], &name[idx + 2..]),
+        None => ("crate", name),
+    }
+}
+
+/// A formatting utility used to print strings as XML text or attribute
+/// values. Characters which XML 1.0 doesn't allow are dropped.
+struct XmlEscaped<'a>(&'a str);
+
+impl<'a> ::std::fmt::Display for XmlEscaped<'a> {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '&' => f.write_str("&amp;")?,
+                '<' => f.write_str("&lt;")?,
+                '>' => f.write_str("&gt;")?,
+                '"' => f.write_str("&quot;")?,
+                '\'' => f.write_str("&apos;")?,
+                '\t' | '\n' | '\r' => f.write_fmt(format_args!("&#{};", c as u32))?,
+                c if c < ' ' || c == '\u{fffe}' || c == '\u{ffff}' => {}
+                c => f.write_fmt(format_args!("{}", c))?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -13,15 +13,79 @@ use super::*;
 mod pretty;
 mod json;
 mod terse;
+mod junit;
 
 pub(crate) use self::pretty::PrettyFormatter;
-pub(crate) use self::json::JsonFormatter;
+pub(crate) use self::json::{JsonFormatter, JSON_SCHEMA_VERSION};
 pub(crate) use self::terse::TerseFormatter;
+pub(crate) use self::junit::JunitFormatter;
+
+/// Formats a duration as seconds, to the millisecond.
+fn fmt_secs(d: Duration) -> String {
+    format!("{}.{:03}", d.as_secs(), d.subsec_millis())
+}
+
+/// Describes the test a per-test fixture failed for, if any.
+fn fixture_test_suffix(failure: &FixtureFailure) -> String {
+    match failure.test {
+        Some(ref test) => format!(" (for {})", test),
+        None => String::new(),
+    }
+}
+
+/// Lists the tests which passed after failing, for the human readable
+/// formatters.
+fn flaky_tests_report(state: &ConsoleTestState) -> String {
+    let mut flaky: Vec<_> = state.flaky
+        .iter()
+        .map(|&(ref f, n)| (f.name.to_string(), n))
+        .collect();
+    flaky.sort();
+    let mut report = String::from("\nflaky tests:\n");
+    for (name, failures) in flaky {
+        let noun = if failures != 1 { "times" } else { "time" };
+        report.push_str(&format!("    {} (failed {} {})\n", name, failures, noun));
+    }
+    report
+}
+
+/// Writes how the benchmarks compare with `baseline`, for the human readable
+/// formatters. `write` prints some text, in the given colour if there is one.
+fn write_baseline_changes<F>(baseline: &str, diff: &MetricDiff, mut write: F) -> io::Result<()>
+where
+    F: FnMut(&str, Option<term::color::Color>) -> io::Result<()>,
+{
+    write(&format!("\nbenchmark changes since baseline `{}`:\n", baseline), None)?;
+    for (name, change) in diff {
+        let (verdict, color, pct, noise_pct) = match *change {
+            // Benchmarks which weren't run this time, e.g. because of a
+            // filter, aren't worth mentioning.
+            MetricChange::MetricRemoved => continue,
+            MetricChange::MetricAdded => {
+                write(&format!("    {}: not in baseline\n", name), None)?;
+                continue;
+            }
+            MetricChange::LikelyNoise(pct, noise) => ("no change", None, pct, noise),
+            MetricChange::Improvement(pct, noise) => {
+                ("improved", Some(term::color::GREEN), pct, noise)
+            }
+            MetricChange::Regression(pct, noise) => {
+                ("regressed", Some(term::color::RED), pct, noise)
+            }
+        };
+        write(&format!("    {}: {:+.2}% (+/- {:.2}%) ", name, pct, noise_pct), None)?;
+        write(verdict, color)?;
+        write("\n", None)?;
+    }
+    Ok(())
+}
 
 pub(crate) trait OutputFormatter {
-    fn write_run_start(&mut self, test_count: usize) -> io::Result<()>;
+    fn write_run_start(&mut self, test_count: usize, shuffle_seed: Option<u64>)
+        -> io::Result<()>;
     fn write_test_start(&mut self, desc: &TestDesc) -> io::Result<()>;
     fn write_timeout(&mut self, desc: &TestDesc) -> io::Result<()>;
+    fn write_fixture_failure(&mut self, failure: &FixtureFailure) -> io::Result<()>;
     fn write_result(
         &mut self,
         desc: &TestDesc,
@@ -54,6 +54,10 @@ impl<T: Write> TerseFormatter<T> {
         self.write_short_result("a", term::color::YELLOW)
     }
 
+    pub fn write_flaky(&mut self) -> io::Result<()> {
+        self.write_short_result("f", term::color::YELLOW)
+    }
+
     pub fn write_bench(&mut self) -> io::Result<()> {
         self.write_pretty("bench", term::color::CYAN)
     }
@@ -127,6 +131,24 @@ impl<T: Write> TerseFormatter<T> {
         Ok(())
     }
 
+    pub fn write_fixture_failures(&mut self, state: &ConsoleTestState) -> io::Result<()> {
+        self.write_plain("\nfixture failures:\n")?;
+        for failure in &state.fixture_failures {
+            self.write_plain(&format!(
+                "\n---- {} {}{} ----\n",
+                failure.kind.as_str(),
+                failure.fixture,
+                fixture_test_suffix(failure)
+            ))?;
+            if !failure.stdout.is_empty() {
+                self.write_plain(&String::from_utf8_lossy(&failure.stdout))?;
+                self.write_plain("\n")?;
+            }
+            self.write_plain(&format!("{}\n", failure.message))?;
+        }
+        Ok(())
+    }
+
     pub fn write_failures(&mut self, state: &ConsoleTestState) -> io::Result<()> {
         self.write_plain("\nfailures:\n")?;
         let mut failures = Vec::new();
@@ -162,10 +184,15 @@ impl<T: Write> TerseFormatter<T> {
 }
 
 impl<T: Write> OutputFormatter for TerseFormatter<T> {
-    fn write_run_start(&mut self, test_count: usize) -> io::Result<()> {
+    fn write_run_start(&mut self, test_count: usize, shuffle_seed: Option<u64>)
+        -> io::Result<()> {
         self.total_test_count = test_count;
         let noun = if test_count != 1 { "tests" } else { "test" };
-        self.write_plain(&format!("\nrunning {} {}\n", test_count, noun))
+        self.write_plain(&format!("\nrunning {} {}\n", test_count, noun))?;
+        if let Some(seed) = shuffle_seed {
+            self.write_plain(&format!("shuffled with --shuffle-seed {}\n", seed))?;
+        }
+        Ok(())
     }
 
     fn write_test_start(&mut self, desc: &TestDesc) -> io::Result<()> {
@@ -186,6 +213,7 @@ impl<T: Write> OutputFormatter for TerseFormatter<T> {
             TrFailed | TrFailedMsg(_) => self.write_failed(),
             TrIgnored => self.write_ignored(),
             TrAllowedFail => self.write_allowed_fail(),
+            TrFlaky(_) => self.write_flaky(),
             TrBench(ref bs) => {
                 if self.is_multithreaded {
                     self.write_test_name(desc)?;
@@ -203,14 +231,31 @@ impl<T: Write> OutputFormatter for TerseFormatter<T> {
         ))
     }
 
+    fn write_fixture_failure(&mut self, _failure: &FixtureFailure) -> io::Result<()> {
+        // Details are written with the failures, at the end of the run.
+        self.write_pretty("E", term::color::RED)
+    }
+
     fn write_run_finish(&mut self, state: &ConsoleTestState) -> io::Result<bool> {
         if state.options.display_output {
             self.write_outputs(state)?;
         }
-        let success = state.failed == 0;
-        if !success {
+        let success = state.failed == 0 && state.fixture_failures.is_empty();
+        if state.failed != 0 {
             self.write_failures(state)?;
         }
+        if !state.fixture_failures.is_empty() {
+            self.write_fixture_failures(state)?;
+        }
+        if !state.flaky.is_empty() {
+            self.write_plain(&flaky_tests_report(state))?;
+        }
+        if let Some((ref baseline, ref diff)) = state.baseline_changes {
+            write_baseline_changes(baseline, diff, |s, color| match color {
+                Some(color) => self.write_pretty(s, color),
+                None => self.write_plain(s),
+            })?;
+        }
 
         self.write_plain("\ntest result: ")?;
 
@@ -221,10 +266,15 @@ impl<T: Write> OutputFormatter for TerseFormatter<T> {
             self.write_pretty("FAILED", term::color::RED)?;
         }
 
+        let passed = if state.flaky.is_empty() {
+            state.passed.to_string()
+        } else {
+            format!("{} ({} flaky)", state.passed, state.flaky.len())
+        };
         let s = if state.allowed_fail > 0 {
             format!(
                 ". {} passed; {} failed ({} allowed); {} ignored; {} measured; {} filtered out\n\n",
-                state.passed,
+                passed,
                 state.failed + state.allowed_fail,
                 state.allowed_fail,
                 state.ignored,
@@ -234,7 +284,7 @@ impl<T: Write> OutputFormatter for TerseFormatter<T> {
         } else {
             format!(
                 ". {} passed; {} failed; {} ignored; {} measured; {} filtered out\n\n",
-                state.passed, state.failed, state.ignored, state.measured, state.filtered_out
+                passed, state.failed, state.ignored, state.measured, state.filtered_out
             )
         };
 
@@ -68,15 +68,16 @@ use std::panic::{catch_unwind, AssertUnwindSafe};
 use std::any::Any;
 use std::boxed::FnBox;
 use std::cmp;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
 use std::fmt;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::prelude::*;
 use std::io;
-use std::path::PathBuf;
-use std::process::Termination;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio, Termination};
 use std::sync::mpsc::{channel, Sender};
+use std::sync::atomic::{self, AtomicUsize};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
@@ -86,18 +87,44 @@ use std::process;
 const TEST_WARN_TIMEOUT_S: u64 = 60;
 const QUIET_MODE_MAX_COLUMN: usize = 100; // insert a '\n' after 100 tests in quiet mode
 
+// Set in the environment of a child process to make it run only the named test. See
+// `run_test_in_process`.
+const TEST_INVOKE_VAR: &str = "__RUST_TEST_INVOKE";
+// Exit codes with which a child process reports the result of its test. Any other exit means
+// the test failed, and is reported as such.
+const TR_OK_EXIT: i32 = 50;
+const TR_ALLOWED_FAIL_EXIT: i32 = 51;
+const TR_FAILED_EXIT: i32 = 101;
+// Names the file in which a child process reports failed teardowns, see
+// `run_test_in_child`.
+const TEARDOWN_REPORT_VAR: &str = "__RUST_TEST_TEARDOWN_REPORT";
+
+// Overrides the directory in which benchmark baselines are kept. See `baseline_path`.
+const BASELINE_DIR_VAR: &str = "RUST_BENCH_BASELINE_DIR";
+// The number of samples `iter` summarises for each benchmark.
+const BENCH_SAMPLES: usize = 50;
+
 // to be used by rustc to compile tests in libtest
 pub mod test {
     pub use {assert_test_result, filter_tests, parse_opts, run_test, test_main, test_main_static,
-             Bencher, DynTestFn, DynTestName, Metric, MetricMap, Options, ShouldPanic,
-             StaticBenchFn, StaticTestFn, StaticTestName, TestDesc, TestDescAndFn, TestName,
-             TestOpts, TestResult, TrFailed, TrFailedMsg, TrIgnored, TrOk};
+             test_main_static_with_fixtures,
+             Bencher, DynTestFn, DynTestName, Metric, MetricChange, MetricDiff, MetricMap,
+             Options, ShouldPanic,
+             StaticBenchFn, StaticTestFn, StaticTestName, TestDesc,
+             TestDescAndFn, TestName, FixtureKind, TestFixture,
+             TestOpts, TestResult, TrFailed, TrFailedMsg, TrFlaky, TrIgnored, TrOk};
 }
 
 pub mod stats;
 mod formatters;
+mod fixtures;
+
+pub use fixtures::{FixtureFailure, FixtureKind, TestFixture};
+use fixtures::{read_teardown_report, write_teardown_report, FailureSink, FixtureRunner,
+               SetupFailed};
 
-use formatters::{JsonFormatter, OutputFormatter, PrettyFormatter, TerseFormatter};
+use formatters::{JsonFormatter, JunitFormatter, OutputFormatter, PrettyFormatter,
+                 TerseFormatter, JSON_SCHEMA_VERSION};
 
 // The name of a test. By convention this follows the rules for rust
 // paths; i.e. it should be a series of identifiers separated by double
@@ -234,6 +261,10 @@ pub struct TestDesc {
     pub ignore: bool,
     pub should_panic: ShouldPanic,
     pub allow_fail: bool,
+    // Seconds after which the test is killed and fails, overriding `--test-timeout`.
+    pub timeout: Option<u64>,
+    // How many times the test is re-run if it fails, overriding `--retries`.
+    pub retries: Option<u32>,
 }
 
 #[derive(Debug)]
@@ -258,12 +289,14 @@ impl Metric {
 #[derive(Copy, Clone, Debug)]
 pub struct Options {
     display_output: bool,
+    fixtures: &'static [&'static TestFixture],
 }
 
 impl Options {
     pub fn new() -> Options {
         Options {
             display_output: false,
+            fixtures: &[],
         }
     }
 
@@ -271,11 +304,23 @@ impl Options {
         self.display_output = display_output;
         self
     }
+
+    /// The setup and teardown functions to run around the tests, see the
+    /// `fixtures` module.
+    pub fn fixtures(mut self, fixtures: &'static [&'static TestFixture]) -> Options {
+        self.fixtures = fixtures;
+        self
+    }
 }
 
 // The default console test runner. It accepts the command line
 // arguments and a vector of test_descs.
 pub fn test_main(args: &[String], tests: Vec<TestDescAndFn>, options: Options) {
+    if let Some(name) = env::var_os(TEST_INVOKE_VAR) {
+        env::remove_var(TEST_INVOKE_VAR);
+        run_test_in_child(&name.to_string_lossy(), tests, options.fixtures);
+    }
+
     let mut opts = match parse_opts(args) {
         Some(Ok(o)) => o,
         Some(Err(msg)) => {
@@ -311,6 +356,14 @@ pub fn test_main(args: &[String], tests: Vec<TestDescAndFn>, options: Options) {
 // semantics into parallel test runners, which in turn requires a Vec<>
 // rather than a &[].
 pub fn test_main_static(tests: &[&TestDescAndFn]) {
+    test_main_static_with_fixtures(tests, &[])
+}
+
+/// Like `test_main_static`, running `fixtures` around the tests.
+pub fn test_main_static_with_fixtures(
+    tests: &[&TestDescAndFn],
+    fixtures: &'static [&'static TestFixture],
+) {
     let args = env::args().collect::<Vec<_>>();
     let owned_tests = tests
         .iter()
@@ -326,7 +379,7 @@ pub fn test_main_static(tests: &[&TestDescAndFn]) {
             _ => panic!("non-static tests passed to test::test_main_static"),
         })
         .collect();
-    test_main(&args, owned_tests, Options::new())
+    test_main(&args, owned_tests, Options::new().fixtures(fixtures))
 }
 
 /// Invoked when unit tests terminate. Should panic if the unit
@@ -355,6 +408,7 @@ pub enum OutputFormat {
     Pretty,
     Terse,
     Json,
+    Junit,
 }
 
 #[derive(Debug)]
@@ -369,7 +423,20 @@ pub struct TestOpts {
     pub nocapture: bool,
     pub color: ColorConfig,
     pub format: OutputFormat,
+    /// Follow this version of the JSON event schema, with `--format json`.
+    pub json_schema_version: Option<u32>,
     pub test_threads: Option<usize>,
+    pub test_timeout: Option<u64>,
+    pub isolate: bool,
+    pub retries: u32,
+    /// Run only the tests of shard `.0` of `.1`, chosen by hashing test names.
+    pub shard: Option<(u64, u64)>,
+    /// Run the tests in an order shuffled with this seed.
+    pub shuffle_seed: Option<u64>,
+    /// Save the benchmark results as the baseline with this name.
+    pub save_baseline: Option<String>,
+    /// Compare the benchmark results to the baseline with this name.
+    pub baseline: Option<String>,
     pub skip: Vec<String>,
     pub options: Options,
 }
@@ -388,7 +455,15 @@ impl TestOpts {
             nocapture: false,
             color: AutoColor,
             format: OutputFormat::Pretty,
+            json_schema_version: None,
             test_threads: None,
+            test_timeout: None,
+            isolate: false,
+            retries: 0,
+            shard: None,
+            shuffle_seed: None,
+            save_baseline: None,
+            baseline: None,
             skip: vec![],
             options: Options::new(),
         }
@@ -425,6 +500,66 @@ fn optgroups() -> getopts::Options {
              in parallel",
             "n_threads",
         )
+        .optopt(
+            "",
+            "test-timeout",
+            "Fail tests which run for longer than this many seconds. \
+             Each test is run in its own process so that it can be killed",
+            "SECS",
+        )
+        .optflag(
+            "",
+            "isolate",
+            "Run each test in its own process, so that a test which \
+             exits, aborts or crashes only fails itself",
+        )
+        .optopt(
+            "",
+            "retries",
+            "Re-run failed tests up to this many times, reporting tests \
+             which then pass as flaky",
+            "N",
+        )
+        .optopt(
+            "",
+            "shard-index",
+            "Run only the tests in this shard, numbered from 0 \
+             (requires --shard-count)",
+            "N",
+        )
+        .optopt(
+            "",
+            "shard-count",
+            "Split the tests into this many shards by name \
+             (requires --shard-index)",
+            "M",
+        )
+        .optflag(
+            "",
+            "shuffle",
+            "Run tests in a random order, printing the seed used",
+        )
+        .optopt(
+            "",
+            "shuffle-seed",
+            "Run tests in the random order given by this seed \
+             (implies --shuffle)",
+            "SEED",
+        )
+        .optopt(
+            "",
+            "save-baseline",
+            "Save the benchmark results under this name, for later \
+             runs to compare against",
+            "NAME",
+        )
+        .optopt(
+            "",
+            "baseline",
+            "Report how the benchmark results changed since they were \
+             saved under this name",
+            "NAME",
+        )
         .optmulti(
             "",
             "skip",
@@ -458,8 +593,16 @@ fn optgroups() -> getopts::Options {
             "Configure formatting of output:
             pretty = Print verbose output;
             terse  = Display one character per test;
-            json   = Output a json document",
-            "pretty|terse|json",
+            json   = Output a json document
+            junit  = Output a JUnit XML report",
+            "pretty|terse|json|junit",
+        )
+        .optopt(
+            "",
+            "json-schema-version",
+            "Emit JSON events following this version of the schema \
+             (requires --format json)",
+            "VERSION",
         )
         .optopt(
             "Z",
@@ -487,6 +630,20 @@ All tests have their standard output and standard error captured by default.
 This can be overridden with the --nocapture flag or setting RUST_TEST_NOCAPTURE
 environment variable to a value other than "0". Logging is not captured by default.
 
+Tests run as threads of the test process, so a test which exits, aborts or
+crashes ends the whole run. The --isolate flag runs each test in a fresh copy
+of the test binary instead, and reports such a test as failed.
+
+The events of --format json follow a versioned schema, with test timings and
+captured output, when --json-schema-version is given. Version {json_version}
+is the latest. A version only gains new fields, so consumers should ignore
+fields they don't know.
+
+Benchmark results can be saved with --save-baseline and compared against with
+--baseline. Baselines are kept in a `bench-baselines` directory next to the
+test binary, unless the RUST_BENCH_BASELINE_DIR environment variable names
+another directory.
+
 Test Attributes:
 
     #[test]        - Indicates a function is a test to be run. This function
@@ -500,8 +657,20 @@ Test Attributes:
     #[ignore]      - When applied to a function which is already attributed as a
                      test, then the test runner will ignore these tests during
                      normal test runs. Running with --ignored will run these
-                     tests."#,
-        usage = options.usage(&message)
+                     tests.
+    #[test_timeout(secs = N)] - Fail the test if it runs for longer than N
+                     seconds, regardless of --test-timeout. Timeouts are
+                     not enforced on targets without processes, such as
+                     emscripten and wasm32.
+    #[test_flaky(retries = N)] - Re-run the test up to N times if it
+                     fails, regardless of --retries.
+    #[test_fixture(setup|teardown)] - Run this function before or after each
+                     test in its module, including submodules. A failing
+                     setup fails the test.
+    #[test_fixture(module_setup|module_teardown)] - Run this function once,
+                     before the first or after the last test in its module."#,
+        usage = options.usage(&message),
+        json_version = JSON_SCHEMA_VERSION
     );
 }
 
@@ -587,6 +756,99 @@ pub fn parse_opts(args: &[String]) -> Option<OptRes> {
         None => None,
     };
 
+    let test_timeout = match matches.opt_str("test-timeout") {
+        Some(n_str) => match n_str.parse::<u64>() {
+            Ok(0) => return Some(Err("argument for --test-timeout must not be 0".to_string())),
+            Ok(n) => Some(n),
+            Err(e) => {
+                return Some(Err(format!(
+                    "argument for --test-timeout must be a number > 0 \
+                     (error: {})",
+                    e
+                )))
+            }
+        },
+        None => None,
+    };
+    // A test can only be killed if it runs in a process of its own.
+    if test_timeout.is_some() && (cfg!(target_os = "emscripten") || cfg!(target_arch = "wasm32")) {
+        return Some(Err("--test-timeout is not supported on this platform".to_string()));
+    }
+
+    let retries = match matches.opt_str("retries") {
+        Some(n_str) => match n_str.parse::<u32>() {
+            Ok(n) => n,
+            Err(e) => {
+                return Some(Err(format!(
+                    "argument for --retries must be a number (error: {})",
+                    e
+                )))
+            }
+        },
+        None => 0,
+    };
+
+    let shard = match (matches.opt_str("shard-index"), matches.opt_str("shard-count")) {
+        (None, None) => None,
+        (Some(index), Some(count)) => {
+            let index = match index.parse::<u64>() {
+                Ok(n) => n,
+                Err(e) => {
+                    return Some(Err(format!(
+                        "argument for --shard-index must be a number (error: {})",
+                        e
+                    )))
+                }
+            };
+            let count = match count.parse::<u64>() {
+                Ok(0) => return Some(Err("argument for --shard-count must not be 0".to_string())),
+                Ok(n) => n,
+                Err(e) => {
+                    return Some(Err(format!(
+                        "argument for --shard-count must be a number > 0 \
+                         (error: {})",
+                        e
+                    )))
+                }
+            };
+            if index >= count {
+                return Some(Err(format!(
+                    "argument for --shard-index must be less than --shard-count ({})",
+                    count
+                )));
+            }
+            Some((index, count))
+        }
+        _ => {
+            return Some(Err(
+                "--shard-index and --shard-count must be given together".to_string(),
+            ))
+        }
+    };
+
+    let shuffle_seed = match matches.opt_str("shuffle-seed") {
+        Some(seed) => match seed.parse::<u64>() {
+            Ok(n) => Some(n),
+            Err(e) => {
+                return Some(Err(format!(
+                    "argument for --shuffle-seed must be a number (error: {})",
+                    e
+                )))
+            }
+        },
+        None if matches.opt_present("shuffle") => Some(random_seed()),
+        None => None,
+    };
+
+    let save_baseline = matches.opt_str("save-baseline");
+    let baseline = matches.opt_str("baseline");
+    for name in save_baseline.iter().chain(baseline.iter()) {
+        let has_separator = name.contains(|c: char| c == '/' || c == '\\');
+        if name.is_empty() || name.starts_with('.') || has_separator {
+            return Some(Err(format!("`{}` is not a valid baseline name", name)));
+        }
+    }
+
     let color = match matches.opt_str("color").as_ref().map(|s| &**s) {
         Some("auto") | None => AutoColor,
         Some("always") => AlwaysColor,
@@ -613,16 +875,43 @@ pub fn parse_opts(args: &[String]) -> Option<OptRes> {
             }
             OutputFormat::Json
         }
+        Some("junit") => {
+            if !allow_unstable {
+                return Some(Err(
+                    "The \"junit\" format is only accepted on the nightly compiler".into(),
+                ));
+            }
+            OutputFormat::Junit
+        }
 
         Some(v) => {
             return Some(Err(format!(
-                "argument for --format must be pretty, terse, or json (was \
+                "argument for --format must be pretty, terse, json, or junit (was \
                  {})",
                 v
             )))
         }
     };
 
+    let json_schema_version = match matches.opt_str("json-schema-version") {
+        Some(v) => match v.parse::<u32>() {
+            Ok(n) if n >= 1 && n <= JSON_SCHEMA_VERSION => {
+                if format != OutputFormat::Json {
+                    return Some(Err("--json-schema-version requires --format json".into()));
+                }
+                Some(n)
+            }
+            _ => {
+                return Some(Err(format!(
+                    "argument for --json-schema-version must be a version from 1 to {} (was {})",
+                    JSON_SCHEMA_VERSION,
+                    v
+                )))
+            }
+        },
+        None => None,
+    };
+
     let test_opts = TestOpts {
         list,
         filter,
@@ -634,7 +923,15 @@ pub fn parse_opts(args: &[String]) -> Option<OptRes> {
         nocapture,
         color,
         format,
+        json_schema_version,
         test_threads,
+        test_timeout,
+        isolate: matches.opt_present("isolate"),
+        retries,
+        shard,
+        shuffle_seed,
+        save_baseline,
+        baseline,
         skip: matches.opt_strs("skip"),
         options: Options::new(),
     };
@@ -655,6 +952,8 @@ pub enum TestResult {
     TrFailedMsg(String),
     TrIgnored,
     TrAllowedFail,
+    /// Passed after failing the given number of times.
+    TrFlaky(u32),
     TrBench(BenchSamples),
 }
 
@@ -693,6 +992,12 @@ struct ConsoleTestState {
     metrics: MetricMap,
     failures: Vec<(TestDesc, Vec<u8>)>,
     not_failures: Vec<(TestDesc, Vec<u8>)>,
+    /// Tests which passed after failing, with the number of failures.
+    flaky: Vec<(TestDesc, u32)>,
+    fixture_failures: Vec<FixtureFailure>,
+    shuffle_seed: Option<u64>,
+    /// The name of the baseline compared against, and how the benchmarks changed.
+    baseline_changes: Option<(String, MetricDiff)>,
     options: Options,
 }
 
@@ -715,6 +1020,10 @@ impl ConsoleTestState {
             metrics: MetricMap::new(),
             failures: Vec::new(),
             not_failures: Vec::new(),
+            flaky: Vec::new(),
+            fixture_failures: Vec::new(),
+            shuffle_seed: opts.shuffle_seed,
+            baseline_changes: None,
             options: opts.options,
         })
     }
@@ -736,6 +1045,7 @@ impl ConsoleTestState {
                 TrFailedMsg(ref msg) => format!("failed: {}", msg),
                 TrIgnored => "ignored".to_owned(),
                 TrAllowedFail => "failed (allowed)".to_owned(),
+                TrFlaky(failures) => format!("ok (flaky, failed {} times)", failures),
                 TrBench(ref bs) => fmt_bench_samples(bs),
             },
             test.name
@@ -793,6 +1103,26 @@ pub fn fmt_bench_samples(bs: &BenchSamples) -> String {
     output
 }
 
+/// The half-width of a 95% confidence interval for the median time of a
+/// benchmark. The standard error of the median of normally distributed
+/// samples is sqrt(pi / 2) times that of their mean, and the median absolute
+/// deviation is a robust estimate of their standard deviation.
+fn median_confidence(summ: &stats::Summary) -> f64 {
+    1.96 * 1.2533 * summ.median_abs_dev / (BENCH_SAMPLES as f64).sqrt()
+}
+
+/// Where the baseline called `name` is kept for this test binary.
+fn baseline_path(name: &str) -> io::Result<PathBuf> {
+    let exe = env::current_exe()?;
+    let dir = match env::var_os(BASELINE_DIR_VAR) {
+        Some(dir) => PathBuf::from(dir),
+        None => exe.with_file_name("bench-baselines"),
+    };
+    // Test binaries share the directory, so each baseline is per binary.
+    let binary = exe.file_stem().unwrap_or_default().to_owned();
+    Ok(dir.join(name).join(binary).with_extension("txt"))
+}
+
 // List the tests to console, and optionally to logfile. Filters are honored.
 pub fn list_tests_console(opts: &TestOpts, tests: Vec<TestDescAndFn>) -> io::Result<()> {
     let mut output = match term::stdout() {
@@ -862,11 +1192,22 @@ pub fn run_tests_console(opts: &TestOpts, tests: Vec<TestDescAndFn>) -> io::Resu
         match (*event).clone() {
             TeFiltered(ref filtered_tests) => {
                 st.total = filtered_tests.len();
-                out.write_run_start(filtered_tests.len())
+                out.write_run_start(filtered_tests.len(), st.shuffle_seed)
             }
             TeFilteredOut(filtered_out) => Ok(st.filtered_out = filtered_out),
             TeWait(ref test) => out.write_test_start(test),
             TeTimeout(ref test) => out.write_timeout(test),
+            TeFixtureFailed(failure) => {
+                st.write_log(format!(
+                    "{} {} failed: {}\n",
+                    failure.kind.as_str(),
+                    failure.fixture,
+                    failure.message
+                ))?;
+                out.write_fixture_failure(&failure)?;
+                st.fixture_failures.push(failure);
+                Ok(())
+            }
             TeResult(test, result, stdout) => {
                 st.write_log_result(&test, &result)?;
                 out.write_result(&test, &result, &*stdout)?;
@@ -877,11 +1218,16 @@ pub fn run_tests_console(opts: &TestOpts, tests: Vec<TestDescAndFn>) -> io::Resu
                     }
                     TrIgnored => st.ignored += 1,
                     TrAllowedFail => st.allowed_fail += 1,
+                    TrFlaky(failures) => {
+                        st.passed += 1;
+                        st.flaky.push((test.clone(), failures));
+                        st.not_failures.push((test, stdout));
+                    }
                     TrBench(bs) => {
                         st.metrics.insert_metric(
                             test.name.as_slice(),
                             bs.ns_iter_summ.median,
-                            bs.ns_iter_summ.max - bs.ns_iter_summ.min,
+                            median_confidence(&bs.ns_iter_summ),
                         );
                         st.measured += 1
                     }
@@ -927,7 +1273,8 @@ pub fn run_tests_console(opts: &TestOpts, tests: Vec<TestDescAndFn>) -> io::Resu
             max_name_len,
             is_multithreaded,
         )),
-        OutputFormat::Json => Box::new(JsonFormatter::new(output)),
+        OutputFormat::Json => Box::new(JsonFormatter::new(output, opts.json_schema_version)),
+        OutputFormat::Junit => Box::new(JunitFormatter::new(output)),
     };
     let mut st = ConsoleTestState::new(opts)?;
     fn len_if_padded(t: &TestDescAndFn) -> usize {
@@ -937,10 +1284,39 @@ pub fn run_tests_console(opts: &TestOpts, tests: Vec<TestDescAndFn>) -> io::Resu
         }
     }
 
+    // Load the baseline up front, so that a missing one is reported before
+    // the benchmarks are run.
+    let baseline = match opts.baseline {
+        Some(ref name) => {
+            let path = baseline_path(name)?;
+            let metrics = MetricMap::load(&path).map_err(|e| io::Error::new(
+                e.kind(),
+                format!("could not read baseline `{}` from {}: {}", name, path.display(), e),
+            ))?;
+            Some((name.clone(), metrics))
+        }
+        None => None,
+    };
+
     run_tests(opts, tests, |x| callback(&x, &mut st, &mut *out))?;
 
     assert!(st.current_test_count() == st.total);
 
+    if let Some((name, old)) = baseline {
+        st.baseline_changes = Some((name, st.metrics.compare_to_old(&old)));
+    }
+    // A run which measured nothing, e.g. because it didn't run benchmarks,
+    // doesn't replace the saved baseline.
+    if let Some(ref name) = opts.save_baseline {
+        if st.measured > 0 {
+            let path = baseline_path(name)?;
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            st.metrics.save(&path)?;
+        }
+    }
+
     return out.write_run_finish(&st);
 }
 
@@ -951,6 +1327,8 @@ fn should_sort_failures_before_printing_them() {
         ignore: false,
         should_panic: ShouldPanic::No,
         allow_fail: false,
+        timeout: None,
+        retries: None,
     };
 
     let test_b = TestDesc {
@@ -958,6 +1336,8 @@ fn should_sort_failures_before_printing_them() {
         ignore: false,
         should_panic: ShouldPanic::No,
         allow_fail: false,
+        timeout: None,
+        retries: None,
     };
 
     let mut out = PrettyFormatter::new(Raw(Vec::new()), false, 10, false);
@@ -975,6 +1355,10 @@ fn should_sort_failures_before_printing_them() {
         failures: vec![(test_b, Vec::new()), (test_a, Vec::new())],
         options: Options::new(),
         not_failures: Vec::new(),
+        flaky: Vec::new(),
+        fixture_failures: Vec::new(),
+        shuffle_seed: None,
+        baseline_changes: None,
     };
 
     out.write_failures(&st).unwrap();
@@ -988,6 +1372,129 @@ fn should_sort_failures_before_printing_them() {
     assert!(apos < bpos);
 }
 
+#[test]
+fn junit_report_contains_outcomes() {
+    let desc = |name, should_panic| TestDesc {
+        name: StaticTestName(name),
+        ignore: false,
+        should_panic,
+        allow_fail: false,
+        timeout: None,
+        retries: None,
+    };
+    let passed = desc("m::passed", ShouldPanic::No);
+    let failed = desc("m::failed", ShouldPanic::No);
+    let ignored = desc("ignored", ShouldPanic::No);
+    let panicked = desc("m::panicked", ShouldPanic::Yes);
+
+    let mut out = JunitFormatter::new(Raw(Vec::new()));
+    out.write_run_start(4, None).unwrap();
+    for test in &[&passed, &failed, &ignored, &panicked] {
+        out.write_test_start(test).unwrap();
+    }
+    out.write_result(&passed, &TrOk, b"not shown").unwrap();
+    out.write_result(&failed, &TrFailed, b"a < b").unwrap();
+    out.write_result(&ignored, &TrIgnored, b"").unwrap();
+    out.write_result(&panicked, &TrOk, b"boom").unwrap();
+
+    let st = ConsoleTestState {
+        log_out: None,
+        total: 4,
+        passed: 2,
+        failed: 1,
+        ignored: 1,
+        allowed_fail: 0,
+        filtered_out: 0,
+        measured: 0,
+        metrics: MetricMap::new(),
+        failures: Vec::new(),
+        options: Options::new(),
+        not_failures: Vec::new(),
+        flaky: Vec::new(),
+        fixture_failures: Vec::new(),
+        shuffle_seed: None,
+        baseline_changes: None,
+    };
+    assert!(!out.write_run_finish(&st).unwrap());
+    let s = match out.output_location() {
+        &Raw(ref m) => String::from_utf8_lossy(&m[..]).into_owned(),
+        &Pretty(_) => unreachable!(),
+    };
+
+    assert!(s.contains(r#"failures="1" tests="4" skipped="1""#));
+    assert!(s.contains(r#"<testcase classname="m" name="passed" time="#));
+    assert!(!s.contains("not shown"));
+    assert!(s.contains(r#"<failure type="failure" message="test failed"/>"#));
+    assert!(s.contains("<system-out>a &lt; b</system-out>"));
+    assert!(s.contains(r#"<testcase classname="crate" name="ignored""#));
+    assert!(s.contains("<skipped/>"));
+    assert!(s.contains("<system-out>boom</system-out>"));
+}
+
+#[test]
+fn json_events_follow_schema() {
+    let desc = |name| TestDesc {
+        name: StaticTestName(name),
+        ignore: false,
+        should_panic: ShouldPanic::No,
+        allow_fail: false,
+        timeout: None,
+        retries: None,
+    };
+    let passed = desc("m::passed");
+    let failed = desc("m::\"quoted\"");
+
+    let mut out = JsonFormatter::new(Raw(Vec::new()), Some(1));
+    out.write_run_start(2, None).unwrap();
+    out.write_test_start(&passed).unwrap();
+    out.write_test_start(&failed).unwrap();
+    out.write_result(&passed, &TrOk, b"hello\n").unwrap();
+    out.write_result(&failed, &TrFailedMsg("boom".to_string()), b"").unwrap();
+
+    let st = ConsoleTestState {
+        log_out: None,
+        total: 2,
+        passed: 1,
+        failed: 1,
+        ignored: 0,
+        allowed_fail: 0,
+        filtered_out: 0,
+        measured: 0,
+        metrics: MetricMap::new(),
+        failures: Vec::new(),
+        options: Options::new(),
+        not_failures: Vec::new(),
+        flaky: Vec::new(),
+        fixture_failures: Vec::new(),
+        shuffle_seed: None,
+        baseline_changes: None,
+    };
+    assert!(!out.write_run_finish(&st).unwrap());
+    let s = match out.output_location() {
+        &Raw(ref m) => String::from_utf8_lossy(&m[..]).into_owned(),
+        &Pretty(_) => unreachable!(),
+    };
+    let lines: Vec<&str> = s.lines().collect();
+
+    assert_eq!(lines.len(), 6);
+    assert!(lines[0].starts_with(
+        r#"{ "type": "suite", "event": "started", "schema_version": 1, "test_count": 2, "#
+    ));
+    assert!(lines[1].starts_with(
+        r#"{ "type": "test", "event": "started", "name": "m::passed", "timestamp": "#
+    ));
+    assert!(lines[3].starts_with(
+        r#"{ "type": "test", "event": "ok", "name": "m::passed", "exec_time": "#
+    ));
+    assert!(lines[3].ends_with(r#", "stdout": "hello\n" }"#));
+    assert!(lines[4].starts_with(
+        r#"{ "type": "test", "event": "failed", "name": "m::\"quoted\"", "exec_time": "#
+    ));
+    assert!(lines[4].ends_with(r#", "message": "boom" }"#));
+    assert!(lines[5].starts_with(r#"{ "type": "suite", "event": "failed", "passed": 1, "#));
+    assert!(lines[5].contains(r#", "exec_time": "#));
+}
+
 fn use_color(opts: &TestOpts) -> bool {
     match opts.color {
         AutoColor => !opts.nocapture && stdout_isatty(),
@@ -1031,6 +1538,7 @@ pub enum TestEvent {
     TeResult(TestDesc, TestResult, Vec<u8>),
     TeTimeout(TestDesc),
     TeFilteredOut(usize),
+    TeFixtureFailed(FixtureFailure),
 }
 
 pub type MonitorMsg = (TestDesc, TestResult, Vec<u8>);
@@ -1049,9 +1557,9 @@ pub fn run_tests<F>(opts: &TestOpts, tests: Vec<TestDescAndFn>, mut callback: F)
 where
     F: FnMut(TestEvent) -> io::Result<()>,
 {
-    use std::collections::HashMap;
     use std::sync::mpsc::RecvTimeoutError;
 
+    let mut fixtures = FixtureRunner::new(opts.options.fixtures, opts.nocapture);
     let tests_len = tests.len();
 
     let mut filtered_tests = filter_tests(opts, tests);
@@ -1064,6 +1572,9 @@ where
         for test in filtered_tests.iter_mut() {
             test.desc.name = test.desc.name.with_padding(test.testfn.padding());
         }
+        if let Some(seed) = opts.shuffle_seed {
+            shuffle(&mut filtered_tests, seed);
+        }
 
         filtered_tests
     };
@@ -1083,13 +1594,79 @@ where
 
     let concurrency = opts.test_threads.unwrap_or_else(get_concurrency);
 
-    let mut remaining = filtered_tests;
+    let mut remaining: Vec<_> = filtered_tests
+        .into_iter()
+        .map(|test| {
+            if opts.run_tests && !test.desc.ignore {
+                fixtures.expect(&test.desc);
+            }
+            fixtures.wrap(test)
+        })
+        .collect();
     remaining.reverse();
     let mut pending = 0;
 
     let (tx, rx) = channel::<MonitorMsg>();
 
     let mut running_tests: HashMap<TestDesc, Instant> = HashMap::new();
+    let mut retrying: HashMap<TestDesc, Retry> = HashMap::new();
+
+    // Runs a test's module setups, and then the test unless one of them failed.
+    fn start_test<F>(
+        opts: &TestOpts,
+        test: TestDescAndFn,
+        fixtures: &mut FixtureRunner,
+        retrying: &mut HashMap<TestDesc, Retry>,
+        monitor_ch: &Sender<MonitorMsg>,
+        callback: &mut F,
+    ) -> io::Result<()>
+    where
+        F: FnMut(TestEvent) -> io::Result<()>,
+    {
+        let setup = if opts.run_tests && !test.desc.ignore {
+            fixtures.before(&test.desc)
+        } else {
+            Ok(())
+        };
+        for failure in fixtures.take_failures() {
+            callback(TeFixtureFailed(failure))?;
+        }
+        match setup {
+            Ok(()) => {
+                Retry::register(opts, &test, retrying);
+                run_test_with_fixtures(
+                    opts,
+                    !opts.run_tests,
+                    test,
+                    monitor_ch.clone(),
+                    Some(fixtures.failure_sink()),
+                );
+            }
+            Err(msg) => monitor_ch.send((test.desc, TrFailedMsg(msg), Vec::new())).unwrap(),
+        }
+        Ok(())
+    }
+
+    // Reports a test's result, and then runs its module teardowns.
+    fn finish_test<F>(
+        opts: &TestOpts,
+        (desc, result, stdout): MonitorMsg,
+        fixtures: &mut FixtureRunner,
+        callback: &mut F,
+    ) -> io::Result<()>
+    where
+        F: FnMut(TestEvent) -> io::Result<()>,
+    {
+        let ran = opts.run_tests && !desc.ignore;
+        callback(TeResult(desc.clone(), result, stdout))?;
+        if ran {
+            fixtures.after(&desc);
+        }
+        for failure in fixtures.take_failures() {
+            callback(TeFixtureFailed(failure))?;
+        }
+        Ok(())
+    }
 
     fn get_timed_out_tests(running_tests: &mut HashMap<TestDesc, Instant>) -> Vec<TestDesc> {
         let now = Instant::now();
@@ -1123,10 +1700,18 @@ where
     if concurrency == 1 {
         while !remaining.is_empty() {
             let test = remaining.pop().unwrap();
-            callback(TeWait(test.desc.clone()))?;
-            run_test(opts, !opts.run_tests, test, tx.clone());
-            let (test, result, stdout) = rx.recv().unwrap();
-            callback(TeResult(test, result, stdout))?;
+            let desc = test.desc.clone();
+            // Module setups are reported before the test is, as the pretty
+            // formatter prints the name of a test when it starts.
+            start_test(opts, test, &mut fixtures, &mut retrying, &tx, &mut callback)?;
+            callback(TeWait(desc))?;
+            loop {
+                let msg = rx.recv().unwrap();
+                if let Some(msg) = Retry::check(opts, msg, &fixtures, &mut retrying, &tx) {
+                    finish_test(opts, msg, &mut fixtures, &mut callback)?;
+                    break;
+                }
+            }
         }
     } else {
         while pending > 0 || !remaining.is_empty() {
@@ -1134,8 +1719,9 @@ where
                 let test = remaining.pop().unwrap();
                 let timeout = Instant::now() + Duration::from_secs(TEST_WARN_TIMEOUT_S);
                 running_tests.insert(test.desc.clone(), timeout);
-                callback(TeWait(test.desc.clone()))?; //here no pad
-                run_test(opts, !opts.run_tests, test, tx.clone());
+                let desc = test.desc.clone();
+                start_test(opts, test, &mut fixtures, &mut retrying, &tx, &mut callback)?;
+                callback(TeWait(desc))?; //here no pad
                 pending += 1;
             }
 
@@ -1155,10 +1741,15 @@ where
                 }
             }
 
-            let (desc, result, stdout) = res.unwrap();
+            let msg = Retry::check(opts, res.unwrap(), &fixtures, &mut retrying, &tx);
+            let (desc, result, stdout) = match msg {
+                Some(msg) => msg,
+                // The test is being run again.
+                None => continue,
+            };
             running_tests.remove(&desc);
 
-            callback(TeResult(desc, result, stdout))?;
+            finish_test(opts, (desc, result, stdout), &mut fixtures, &mut callback)?;
             pending -= 1;
         }
     }
@@ -1175,6 +1766,90 @@ where
     Ok(())
 }
 
+/// A test which is re-run if it fails, see `TestOpts::retries`.
+struct Retry {
+    /// How many more times the test may be run.
+    left: u32,
+    failures: u32,
+    /// Output of the failed runs, which is reported along with the last.
+    stdout: Vec<u8>,
+    /// `None` for a `DynTestFn`, which can't be called twice: those are re-run
+    /// in a child process instead.
+    testfn: Option<fn()>,
+}
+
+impl Retry {
+    fn register(opts: &TestOpts, test: &TestDescAndFn, retrying: &mut HashMap<TestDesc, Retry>) {
+        let left = test.desc.retries.unwrap_or(opts.retries);
+        if left == 0 || test.desc.ignore || !opts.run_tests {
+            return;
+        }
+        let supports_processes =
+            !cfg!(target_os = "emscripten") && !cfg!(target_arch = "wasm32");
+        let testfn = match test.testfn {
+            StaticTestFn(f) => Some(f),
+            DynTestFn(..) if supports_processes => None,
+            _ => return,
+        };
+        retrying.insert(test.desc.clone(), Retry { left, failures: 0, stdout: Vec::new(), testfn });
+    }
+
+    /// Re-runs the test `msg` is the result of, if it failed and may be
+    /// retried. Otherwise returns the final result, which is `TrFlaky` for a
+    /// test which passed after failing.
+    fn check(
+        opts: &TestOpts,
+        msg: MonitorMsg,
+        fixtures: &FixtureRunner,
+        retrying: &mut HashMap<TestDesc, Retry>,
+        monitor_ch: &Sender<MonitorMsg>,
+    ) -> Option<MonitorMsg> {
+        let (desc, result, stdout) = msg;
+        let mut retry = match retrying.remove(&desc) {
+            Some(retry) => retry,
+            None => return Some((desc, result, stdout)),
+        };
+        retry.stdout.extend_from_slice(&stdout);
+        let failed = match result {
+            TrFailed | TrFailedMsg(_) => true,
+            _ => false,
+        };
+        if !failed {
+            let result = match result {
+                TrOk if retry.failures > 0 => TrFlaky(retry.failures),
+                result => result,
+            };
+            return Some((desc, result, retry.stdout));
+        }
+        if retry.left == 0 {
+            return Some((desc, result, retry.stdout));
+        }
+
+        retry.left -= 1;
+        retry.failures += 1;
+        if let TrFailedMsg(ref msg) = result {
+            retry.stdout.extend_from_slice(format!("note: {}\n", msg).as_bytes());
+        }
+        retry.stdout.extend_from_slice(
+            format!("note: retrying after failure {}\n", retry.failures).as_bytes());
+        match retry.testfn {
+            Some(f) => {
+                let test = TestDescAndFn { desc: desc.clone(), testfn: StaticTestFn(f) };
+                retrying.insert(desc, retry);
+                let failures = Some(fixtures.failure_sink());
+                run_test_with_fixtures(opts, false, test, monitor_ch.clone(), failures);
+            }
+            None => {
+                let timeout = desc.timeout.or(opts.test_timeout).map(Duration::from_secs);
+                retrying.insert(desc.clone(), retry);
+                let failures = Some(fixtures.failure_sink());
+                run_test_in_process(desc, opts.nocapture, timeout, monitor_ch.clone(), failures);
+            }
+        }
+        None
+    }
+}
+
 #[allow(deprecated)]
 fn get_concurrency() -> usize {
     return match env::var("RUST_TEST_THREADS") {
@@ -1355,12 +2030,56 @@ pub fn filter_tests(opts: &TestOpts, tests: Vec<TestDescAndFn>) -> Vec<TestDescA
         filtered.into_iter().filter_map(filter).collect()
     };
 
+    // Keep only the tests in our shard. Hashing the name, rather than taking
+    // every nth test, keeps a test in the same shard as others are added.
+    if let Some((index, count)) = opts.shard {
+        filtered.retain(|t| fnv1a(t.desc.name.as_slice().as_bytes()) % count == index);
+    }
+
     // Sort the tests alphabetically
     filtered.sort_by(|t1, t2| t1.desc.name.as_slice().cmp(t2.desc.name.as_slice()));
 
     filtered
 }
 
+/// A hash of `bytes` which, unlike `DefaultHasher`, is guaranteed to be the
+/// same on every platform and in every release, so that shards are stable.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash = 0xcbf29ce484222325u64;
+    for b in bytes {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// Picks a seed for `--shuffle`. `RandomState` is seeded from the OS, which
+/// saves depending on a random number crate.
+fn random_seed() -> u64 {
+    use std::collections::hash_map::RandomState;
+    use std::hash::{BuildHasher, Hasher};
+
+    RandomState::new().build_hasher().finish()
+}
+
+/// Shuffles `items` with the splitmix64 generator seeded with `seed`, so that
+/// a given seed always gives the same order.
+fn shuffle<T>(items: &mut [T], seed: u64) {
+    let mut state = seed;
+    let mut next = || {
+        state = state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    };
+    // Fisher-Yates
+    for i in (1..items.len()).rev() {
+        let j = (next() % (i as u64 + 1)) as usize;
+        items.swap(i, j);
+    }
+}
+
 pub fn convert_benchmarks_to_tests(tests: Vec<TestDescAndFn>) -> Vec<TestDescAndFn> {
     // convert benchmarks to tests, if we're not benchmarking them
     tests
@@ -1388,6 +2107,18 @@ pub fn run_test(
     force_ignore: bool,
     test: TestDescAndFn,
     monitor_ch: Sender<MonitorMsg>,
+) {
+    run_test_with_fixtures(opts, force_ignore, test, monitor_ch, None)
+}
+
+/// Runs a test like `run_test`. If the test is run in a child process, the
+/// failures of its teardowns there are added to `teardown_failures`.
+fn run_test_with_fixtures(
+    opts: &TestOpts,
+    force_ignore: bool,
+    test: TestDescAndFn,
+    monitor_ch: Sender<MonitorMsg>,
+    teardown_failures: Option<FailureSink>,
 ) {
     let TestDescAndFn { desc, testfn } = test;
 
@@ -1446,6 +2177,25 @@ pub fn run_test(
         }
     }
 
+    // Tests can only be killed if they run in a process of their own.
+    let supports_processes = !cfg!(target_os = "emscripten") && !cfg!(target_arch = "wasm32");
+    let timeout = desc.timeout.or(opts.test_timeout);
+    if supports_processes && (opts.isolate || timeout.is_some()) {
+        match testfn {
+            StaticTestFn(..) | DynTestFn(..) => {
+                let timeout = timeout.map(Duration::from_secs);
+                return run_test_in_process(
+                    desc,
+                    opts.nocapture,
+                    timeout,
+                    monitor_ch,
+                    teardown_failures,
+                );
+            }
+            StaticBenchFn(..) | DynBenchFn(..) => {}
+        }
+    }
+
     match testfn {
         DynBenchFn(bencher) => {
             ::bench::benchmark(desc, monitor_ch, opts.nocapture, |harness| {
@@ -1470,6 +2220,200 @@ pub fn run_test(
     }
 }
 
+/// Runs a test by spawning a copy of the test binary which runs only that test
+/// (see `run_test_in_child`), killing it if it outlives `timeout`. A child
+/// which exits in any other way than `run_test_in_child` does, e.g. because
+/// the test called `process::exit` or was killed by a signal, fails the test.
+/// The child gets the same arguments as this process, so that programs which
+/// generate their tests (e.g. rustdoc) find the same tests. The teardowns
+/// which failed in the child are added to `teardown_failures`, before the
+/// result is sent.
+fn run_test_in_process(
+    desc: TestDesc,
+    nocapture: bool,
+    timeout: Option<Duration>,
+    monitor_ch: Sender<MonitorMsg>,
+    teardown_failures: Option<FailureSink>,
+) {
+    let name = desc.name.clone();
+    let runtest = move || {
+        let report = teardown_report_path();
+        let (test_result, stdout) = match spawn_test_process(&desc, nocapture, timeout, &report) {
+            Ok(res) => res,
+            Err(e) => (
+                TrFailedMsg(format!("could not run the test in a child process: {}", e)),
+                Vec::new(),
+            ),
+        };
+        let failures = read_teardown_report(&report, &desc.name);
+        if let Some(teardown_failures) = teardown_failures {
+            teardown_failures.lock().unwrap().extend(failures);
+        }
+        monitor_ch.send((desc.clone(), test_result, stdout)).unwrap();
+    };
+    let cfg = thread::Builder::new().name(name.as_slice().to_owned());
+    cfg.spawn(runtest).unwrap();
+}
+
+/// A file, unique to this run of a test, for a child process to report failed
+/// teardowns in.
+fn teardown_report_path() -> PathBuf {
+    static NEXT_REPORT: AtomicUsize = AtomicUsize::new(0);
+    let n = NEXT_REPORT.fetch_add(1, atomic::Ordering::SeqCst);
+    env::temp_dir().join(format!("rust-test-teardowns-{}-{}", process::id(), n))
+}
+
+fn spawn_test_process(
+    desc: &TestDesc,
+    nocapture: bool,
+    timeout: Option<Duration>,
+    report: &Path,
+) -> io::Result<(TestResult, Vec<u8>)> {
+    let mut cmd = Command::new(env::current_exe()?);
+    cmd.args(env::args_os().skip(1))
+        .env(TEST_INVOKE_VAR, desc.name.as_slice())
+        .env(TEARDOWN_REPORT_VAR, report)
+        .stdin(Stdio::null());
+    if !nocapture {
+        cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+    }
+    let mut child = cmd.spawn()?;
+
+    // Both pipes are drained into the same buffer, so that the output is
+    // interleaved roughly as the test wrote it.
+    let data = Arc::new(Mutex::new(Vec::new()));
+    let pipes: Vec<Box<dyn Read + Send>> = vec![
+        child.stdout.take().map(|p| Box::new(p) as Box<dyn Read + Send>),
+        child.stderr.take().map(|p| Box::new(p) as Box<dyn Read + Send>),
+    ].into_iter().filter_map(|p| p).collect();
+    let readers: Vec<_> = pipes.into_iter().map(|mut pipe| {
+        let data = data.clone();
+        thread::spawn(move || {
+            let mut buf = [0; 4096];
+            loop {
+                match pipe.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => data.lock().unwrap().extend_from_slice(&buf[..n]),
+                }
+            }
+        })
+    }).collect();
+
+    let deadline = timeout.map(|t| Instant::now() + t);
+    let status = match deadline {
+        None => Some(child.wait()?),
+        Some(deadline) => loop {
+            if let Some(status) = child.try_wait()? {
+                break Some(status);
+            }
+            if Instant::now() >= deadline {
+                // The child may have exited since `try_wait`, in which case
+                // there is nothing left to kill.
+                if let Err(e) = child.kill() {
+                    match child.try_wait()? {
+                        Some(status) => break Some(status),
+                        None => return Err(e),
+                    }
+                }
+                child.wait()?;
+                break None;
+            }
+            thread::sleep(Duration::from_millis(10));
+        },
+    };
+
+    let test_result = match status {
+        Some(status) => {
+            // Only wait for the output of tests which exited by themselves: a
+            // killed test may have left processes behind which hold the pipes.
+            for reader in readers {
+                let _ = reader.join();
+            }
+            match status.code() {
+                Some(TR_OK_EXIT) => TrOk,
+                Some(TR_ALLOWED_FAIL_EXIT) => TrAllowedFail,
+                _ if desc.allow_fail => TrAllowedFail,
+                Some(TR_FAILED_EXIT) => TrFailed,
+                _ => TrFailedMsg(abnormal_exit_msg(status)),
+            }
+        }
+        None => {
+            let msg = format!("test did not finish within {} seconds", timeout.unwrap().as_secs());
+            if desc.allow_fail { TrAllowedFail } else { TrFailedMsg(msg) }
+        }
+    };
+    let stdout = data.lock().unwrap().clone();
+    Ok((test_result, stdout))
+}
+
+/// Describes how a test process ended, when it didn't report a result.
+fn abnormal_exit_msg(status: process::ExitStatus) -> String {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return format!("test process was killed by signal {}", signal);
+        }
+    }
+    match status.code() {
+        Some(code) => format!("test process exited unexpectedly with code {}", code),
+        None => format!("test process exited unexpectedly ({})", status),
+    }
+}
+
+/// Runs the test named by `TEST_INVOKE_VAR` in a process spawned by
+/// `run_test_in_process`, and exits with its result. Output isn't captured, as
+/// the parent collects it. Failed teardowns don't change the result: they are
+/// reported to the parent in the file named by `TEARDOWN_REPORT_VAR`.
+fn run_test_in_child(name: &str, tests: Vec<TestDescAndFn>, fixtures: &[&TestFixture]) -> ! {
+    let report = env::var_os(TEARDOWN_REPORT_VAR);
+    env::remove_var(TEARDOWN_REPORT_VAR);
+    // Benchmarks are only run in a child process when they're run once, as
+    // tests. Module fixtures are run by the parent.
+    let fixtures = FixtureRunner::new(fixtures, true);
+    let tests = convert_benchmarks_to_tests(tests);
+    let test = match tests.into_iter().find(|t| t.desc.name.as_slice() == name) {
+        Some(test) => fixtures.wrap(test),
+        None => {
+            eprintln!("error: no test named `{}`", name);
+            process::exit(TR_FAILED_EXIT);
+        }
+    };
+    let TestDescAndFn { desc, testfn } = test;
+    let testfn: Box<dyn FnBox() + Send> = match testfn {
+        StaticTestFn(f) => Box::new(move || __rust_begin_short_backtrace(f)),
+        DynTestFn(f) => Box::new(move || __rust_begin_short_backtrace(f)),
+        StaticBenchFn(..) | DynBenchFn(..) => unreachable!(),
+    };
+
+    let result = calc_result(&desc, catch_unwind(AssertUnwindSafe(testfn)));
+    let teardown_failures = fixtures.take_failures();
+    if !teardown_failures.is_empty() {
+        let reported = match report {
+            Some(report) => write_teardown_report(Path::new(&report), &teardown_failures),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, "no report file")),
+        };
+        // Without the report the parent can't tell, so the test fails instead.
+        if let Err(e) = reported {
+            for failure in &teardown_failures {
+                eprintln!("note: teardown `{}` failed: {}", failure.fixture, failure.message);
+            }
+            eprintln!("error: could not report the failed teardowns: {}", e);
+            process::exit(TR_FAILED_EXIT);
+        }
+    }
+    let code = match result {
+        TrOk => TR_OK_EXIT,
+        TrAllowedFail => TR_ALLOWED_FAIL_EXIT,
+        TrFailedMsg(msg) => {
+            eprintln!("note: {}", msg);
+            TR_FAILED_EXIT
+        }
+        _ => TR_FAILED_EXIT,
+    };
+    process::exit(code)
+}
+
 /// Fixed frame used to clean the backtrace with `RUST_BACKTRACE=1`.
 #[inline(never)]
 fn __rust_begin_short_backtrace<F: FnOnce()>(f: F) {
@@ -1477,6 +2421,11 @@ fn __rust_begin_short_backtrace<F: FnOnce()>(f: F) {
 }
 
 fn calc_result(desc: &TestDesc, task_result: Result<(), Box<dyn Any + Send>>) -> TestResult {
+    if let Err(ref err) = task_result {
+        if let Some(&SetupFailed(ref msg)) = err.downcast_ref::<SetupFailed>() {
+            return TrFailedMsg(msg.clone());
+        }
+    }
     match (&desc.should_panic, task_result) {
         (&ShouldPanic::No, Ok(())) | (&ShouldPanic::Yes, Err(_)) => TrOk,
         (&ShouldPanic::YesWithMessage(msg), Err(ref err)) => {
@@ -1533,8 +2482,99 @@ impl MetricMap {
             .collect::<Vec<_>>();
         v.join(", ")
     }
+
+    /// Compare against another MetricMap, holding the metrics of an earlier
+    /// run. Each noise is taken to be the half-width of a 95% confidence
+    /// interval for its value, so a change is only reported as an improvement
+    /// or regression if the interval of the difference doesn't include zero.
+    pub fn compare_to_old(&self, old: &MetricMap) -> MetricDiff {
+        let mut diff = MetricDiff::new();
+        for (name, old_metric) in &old.0 {
+            let change = match self.0.get(name) {
+                None => MetricChange::MetricRemoved,
+                Some(new_metric) => {
+                    let delta = new_metric.value - old_metric.value;
+                    let noise = (old_metric.noise.powi(2) + new_metric.noise.powi(2)).sqrt();
+                    let (pct, noise_pct) = if old_metric.value == 0.0 {
+                        (0.0, 0.0)
+                    } else {
+                        let base = old_metric.value.abs();
+                        (delta * 100.0 / base, noise * 100.0 / base)
+                    };
+                    if delta.abs() <= noise {
+                        MetricChange::LikelyNoise(pct, noise_pct)
+                    } else if (delta > 0.0) == (old_metric.noise >= 0.0) {
+                        // Growth is a regression unless the noise is negative.
+                        MetricChange::Regression(pct, noise_pct)
+                    } else {
+                        MetricChange::Improvement(pct, noise_pct)
+                    }
+                }
+            };
+            diff.insert(name.clone(), change);
+        }
+        for name in self.0.keys() {
+            if !old.0.contains_key(name) {
+                diff.insert(name.clone(), MetricChange::MetricAdded);
+            }
+        }
+        diff
+    }
+
+    /// Write the metrics to `p`, in a format read by `load`.
+    pub fn save(&self, p: &Path) -> io::Result<()> {
+        let mut file = io::BufWriter::new(File::create(p)?);
+        writeln!(file, "{}", METRIC_FILE_HEADER)?;
+        for (name, m) in &self.0 {
+            writeln!(file, "{}\t{}\t{}", m.value, m.noise, name)?;
+        }
+        file.flush()
+    }
+
+    /// Read metrics written by `save`.
+    pub fn load(p: &Path) -> io::Result<MetricMap> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let mut contents = String::new();
+        File::open(p)?.read_to_string(&mut contents)?;
+        let mut lines = contents.lines();
+        if lines.next() != Some(METRIC_FILE_HEADER) {
+            return Err(invalid("not a metrics file".to_string()));
+        }
+        let mut map = MetricMap::new();
+        for (i, line) in lines.enumerate() {
+            let mut fields = line.splitn(3, '\t');
+            let (value, noise, name) = match (fields.next(), fields.next(), fields.next()) {
+                (Some(value), Some(noise), Some(name)) => (value, noise, name),
+                _ => return Err(invalid(format!("line {} is malformed", i + 2))),
+            };
+            match (value.parse(), noise.parse()) {
+                (Ok(value), Ok(noise)) => map.insert_metric(name, value, noise),
+                _ => return Err(invalid(format!("line {} has a malformed number", i + 2))),
+            }
+        }
+        Ok(map)
+    }
+}
+
+const METRIC_FILE_HEADER: &str = "# libtest metrics, version 1";
+
+/// The change in a metric between two runs. Percentages are relative to the
+/// old value, and are accompanied by the half-width of the 95% confidence
+/// interval of the change.
+#[derive(Clone, PartialEq, Debug, Copy)]
+pub enum MetricChange {
+    /// The metric was only measured in the new run.
+    MetricAdded,
+    /// The metric was only measured in the old run.
+    MetricRemoved,
+    /// The metric changed by less than its noise.
+    LikelyNoise(f64, f64),
+    Improvement(f64, f64),
+    Regression(f64, f64),
 }
 
+pub type MetricDiff = BTreeMap<String, MetricChange>;
+
 // Benchmarking
 
 /// A function that is opaque to the optimizer, to allow benchmarks to
@@ -1613,7 +2653,7 @@ where
     n = cmp::max(1, n);
 
     let mut total_run = Duration::new(0, 0);
-    let samples: &mut [f64] = &mut [0.0_f64; 50];
+    let samples: &mut [f64] = &mut [0.0_f64; BENCH_SAMPLES];
     loop {
         let loop_start = Instant::now();
 
@@ -1742,11 +2782,15 @@ pub mod bench {
 
 #[cfg(test)]
 mod tests {
-    use test::{filter_tests, parse_opts, run_test, DynTestFn, DynTestName, MetricMap, ShouldPanic,
-               StaticTestName, TestDesc, TestDescAndFn, TestOpts, TrFailed, TrFailedMsg,
-               TrIgnored, TrOk};
+    use test::{filter_tests, parse_opts, run_test, DynTestFn, DynTestName, MetricChange,
+               MetricMap, ShouldPanic, StaticTestFn, StaticTestName, TestDesc, TestDescAndFn,
+               TestOpts, TrFailed, TrFailedMsg, TrIgnored, TrOk};
+    use std::env;
+    use std::fs;
+    use std::process;
     use std::sync::mpsc::channel;
     use bench;
+    use shuffle;
     use Bencher;
 
     #[test]
@@ -1760,6 +2804,8 @@ mod tests {
                 ignore: true,
                 should_panic: ShouldPanic::No,
                 allow_fail: false,
+                timeout: None,
+                retries: None,
             },
             testfn: DynTestFn(Box::new(f)),
         };
@@ -1778,6 +2824,8 @@ mod tests {
                 ignore: true,
                 should_panic: ShouldPanic::No,
                 allow_fail: false,
+                timeout: None,
+                retries: None,
             },
             testfn: DynTestFn(Box::new(f)),
         };
@@ -1798,6 +2846,8 @@ mod tests {
                 ignore: false,
                 should_panic: ShouldPanic::Yes,
                 allow_fail: false,
+                timeout: None,
+                retries: None,
             },
             testfn: DynTestFn(Box::new(f)),
         };
@@ -1818,6 +2868,8 @@ mod tests {
                 ignore: false,
                 should_panic: ShouldPanic::YesWithMessage("error message"),
                 allow_fail: false,
+                timeout: None,
+                retries: None,
             },
             testfn: DynTestFn(Box::new(f)),
         };
@@ -1840,6 +2892,8 @@ mod tests {
                 ignore: false,
                 should_panic: ShouldPanic::YesWithMessage(expected),
                 allow_fail: false,
+                timeout: None,
+                retries: None,
             },
             testfn: DynTestFn(Box::new(f)),
         };
@@ -1858,6 +2912,8 @@ mod tests {
                 ignore: false,
                 should_panic: ShouldPanic::Yes,
                 allow_fail: false,
+                timeout: None,
+                retries: None,
             },
             testfn: DynTestFn(Box::new(f)),
         };
@@ -1867,6 +2923,147 @@ mod tests {
         assert!(res == TrFailed);
     }
 
+    #[test]
+    fn flaky_test_is_retried() {
+        use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+        use test::TrFlaky;
+        use {run_tests, TestEvent};
+
+        static RUNS: AtomicUsize = ATOMIC_USIZE_INIT;
+        fn f() {
+            if RUNS.fetch_add(1, Ordering::SeqCst) < 2 {
+                panic!("not yet");
+            }
+        }
+        let test = || TestDescAndFn {
+            desc: TestDesc {
+                name: StaticTestName("flaky"),
+                ignore: false,
+                should_panic: ShouldPanic::No,
+                allow_fail: false,
+                timeout: None,
+                retries: None,
+            },
+            testfn: StaticTestFn(f),
+        };
+        let run = |retries| {
+            let opts = TestOpts {
+                run_tests: true,
+                retries,
+                test_threads: Some(1),
+                ..TestOpts::new()
+            };
+            let mut results = Vec::new();
+            run_tests(&opts, vec![test()], |event| {
+                if let TestEvent::TeResult(_, result, _) = event {
+                    results.push(result);
+                }
+                Ok(())
+            }).unwrap();
+            results
+        };
+
+        assert!(run(1) == vec![TrFailed]);
+        RUNS.store(0, Ordering::SeqCst);
+        assert!(run(2) == vec![TrFlaky(2)]);
+        assert_eq!(RUNS.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn fixtures_run_around_tests() {
+        use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+        use test::{FixtureKind, Options, TestFixture};
+        use {run_tests, TestEvent};
+
+        // The digits of LOG are the steps run, in order.
+        static LOG: AtomicUsize = ATOMIC_USIZE_INIT;
+        fn log(step: usize) {
+            LOG.store(LOG.load(Ordering::SeqCst) * 10 + step, Ordering::SeqCst);
+        }
+        fn module_setup() { log(1) }
+        fn setup() { log(2) }
+        fn test() { log(3) }
+        fn teardown() { log(4) }
+        fn module_teardown() { log(5) }
+        fn failing_setup() { panic!("no database") }
+        static FIXTURES: [&TestFixture; 5] = [
+            &TestFixture {
+                name: "m::module_setup",
+                kind: FixtureKind::ModuleSetup,
+                fixture_fn: module_setup,
+            },
+            &TestFixture { name: "m::setup", kind: FixtureKind::Setup, fixture_fn: setup },
+            &TestFixture { name: "m::teardown", kind: FixtureKind::Teardown, fixture_fn: teardown },
+            &TestFixture {
+                name: "m::module_teardown",
+                kind: FixtureKind::ModuleTeardown,
+                fixture_fn: module_teardown,
+            },
+            &TestFixture { name: "n::setup", kind: FixtureKind::Setup, fixture_fn: failing_setup },
+        ];
+
+        let item = |name, testfn| TestDescAndFn {
+            desc: TestDesc {
+                name: StaticTestName(name),
+                ignore: false,
+                should_panic: ShouldPanic::No,
+                allow_fail: false,
+                timeout: None,
+                retries: None,
+            },
+            testfn,
+        };
+        let tests = vec![
+            item("m::a", StaticTestFn(test)),
+            item("m::b", StaticTestFn(test)),
+            item("n::c", StaticTestFn(test)),
+        ];
+        let opts = TestOpts {
+            run_tests: true,
+            test_threads: Some(1),
+            options: Options::new().fixtures(&FIXTURES),
+            ..TestOpts::new()
+        };
+        let mut results = Vec::new();
+        run_tests(&opts, tests, |event| {
+            if let TestEvent::TeResult(desc, result, _) = event {
+                results.push((desc.name.to_string(), result));
+            }
+            Ok(())
+        }).unwrap();
+
+        assert_eq!(LOG.load(Ordering::SeqCst), 12342345);
+        assert!(results[0] == ("m::a".to_string(), TrOk));
+        assert!(results[1] == ("m::b".to_string(), TrOk));
+        assert!(results[2].1 == TrFailedMsg("setup `n::setup` failed: no database".to_string()));
+    }
+
+    #[test]
+    fn teardown_report_round_trips() {
+        use fixtures::{read_teardown_report, write_teardown_report};
+        use {FixtureFailure, FixtureKind};
+
+        let failure = |fixture: &str, message: &str| FixtureFailure {
+            kind: FixtureKind::Teardown,
+            fixture: DynTestName(fixture.to_string()),
+            test: Some(StaticTestName("m::a")),
+            message: message.to_string(),
+            stdout: Vec::new(),
+        };
+        let path = env::temp_dir().join(format!("teardown-report-test-{}", process::id()));
+        let failures = vec![failure("m::close", "no socket"), failure("close", "two\nlines")];
+        write_teardown_report(&path, &failures).unwrap();
+
+        let read = read_teardown_report(&path, &StaticTestName("m::a"));
+        assert!(!path.exists());
+        assert_eq!(read.len(), 2);
+        assert_eq!(read[0].fixture.as_slice(), "m::close");
+        assert_eq!(read[0].message, "no socket");
+        assert_eq!(read[1].test.as_ref().map(|t| t.as_slice()), Some("m::a"));
+        assert_eq!(read[1].message, "two\nlines");
+        assert!(read_teardown_report(&path, &StaticTestName("m::a")).is_empty());
+    }
+
     #[test]
     fn parse_ignored_flag() {
         let args = vec![
@@ -1881,6 +3078,63 @@ mod tests {
         assert!((opts.run_ignored));
     }
 
+    #[test]
+    fn parse_isolate_flag() {
+        let args = vec!["progname".to_string(), "--isolate".to_string()];
+        let opts = match parse_opts(&args) {
+            Some(Ok(o)) => o,
+            _ => panic!("Malformed arg in parse_isolate_flag"),
+        };
+        assert!(opts.isolate);
+    }
+
+    #[test]
+    fn parse_json_schema_version() {
+        let args = |extra: &[&str]| {
+            let mut args = vec!["progname".to_string(), "-Zunstable-options".to_string()];
+            args.extend(extra.iter().map(|s| s.to_string()));
+            args
+        };
+        let opts = match parse_opts(&args(&["--format", "json", "--json-schema-version", "1"])) {
+            Some(Ok(o)) => o,
+            _ => panic!("Malformed arg in parse_json_schema_version"),
+        };
+        assert_eq!(opts.json_schema_version, Some(1));
+
+        for bad in &[&["--format", "json", "--json-schema-version", "0"][..],
+                     &["--format", "json", "--json-schema-version", "x"][..],
+                     &["--json-schema-version", "1"][..]] {
+            assert!(match parse_opts(&args(bad)) {
+                Some(Err(_)) => true,
+                _ => false,
+            });
+        }
+    }
+
+    #[test]
+    fn parse_test_timeout() {
+        let args = vec![
+            "progname".to_string(),
+            "--test-timeout".to_string(),
+            "30".to_string(),
+        ];
+        let opts = match parse_opts(&args) {
+            Some(Ok(o)) => o,
+            _ => panic!("Malformed arg in parse_test_timeout"),
+        };
+        assert_eq!(opts.test_timeout, Some(30));
+
+        let args = vec![
+            "progname".to_string(),
+            "--test-timeout".to_string(),
+            "0".to_string(),
+        ];
+        assert!(match parse_opts(&args) {
+            Some(Err(_)) => true,
+            _ => false,
+        });
+    }
+
     #[test]
     pub fn filter_for_ignored_option() {
         // When we run ignored tests the test filter should filter out all the
@@ -1897,6 +3151,8 @@ mod tests {
                     ignore: true,
                     should_panic: ShouldPanic::No,
                     allow_fail: false,
+                    timeout: None,
+                    retries: None,
                 },
                 testfn: DynTestFn(Box::new(move || {})),
             },
@@ -1906,6 +3162,8 @@ mod tests {
                     ignore: false,
                     should_panic: ShouldPanic::No,
                     allow_fail: false,
+                    timeout: None,
+                    retries: None,
                 },
                 testfn: DynTestFn(Box::new(move || {})),
             },
@@ -1928,6 +3186,8 @@ mod tests {
                         ignore: false,
                         should_panic: ShouldPanic::No,
                         allow_fail: false,
+                        timeout: None,
+                        retries: None,
                     },
                     testfn: DynTestFn(Box::new(move || {})),
                 })
@@ -2037,6 +3297,8 @@ mod tests {
                         ignore: false,
                         should_panic: ShouldPanic::No,
                         allow_fail: false,
+                        timeout: None,
+                        retries: None,
                     },
                     testfn: DynTestFn(Box::new(testfn)),
                 };
@@ -2063,6 +3325,90 @@ mod tests {
         }
     }
 
+    #[test]
+    pub fn shards_partition_tests() {
+        let tests = || -> Vec<TestDescAndFn> {
+            (0..50)
+                .map(|i| TestDescAndFn {
+                    desc: TestDesc {
+                        name: DynTestName(format!("m::test{}", i)),
+                        ignore: false,
+                        should_panic: ShouldPanic::No,
+                        allow_fail: false,
+                        timeout: None,
+                        retries: None,
+                    },
+                    testfn: DynTestFn(Box::new(move || {})),
+                })
+                .collect()
+        };
+
+        let mut seen = Vec::new();
+        for index in 0..3 {
+            let opts = TestOpts {
+                shard: Some((index, 3)),
+                ..TestOpts::new()
+            };
+            let shard = filter_tests(&opts, tests());
+            assert!(!shard.is_empty());
+            // Sharding is deterministic.
+            let again = filter_tests(&opts, tests());
+            assert_eq!(shard.len(), again.len());
+            seen.extend(shard.into_iter().map(|t| t.desc.name.to_string()));
+        }
+        seen.sort();
+        seen.dedup();
+        assert_eq!(seen.len(), 50);
+    }
+
+    #[test]
+    pub fn shuffle_is_deterministic() {
+        let orig: Vec<u32> = (0..100).collect();
+        let mut a = orig.clone();
+        let mut b = orig.clone();
+        let mut c = orig.clone();
+        shuffle(&mut a, 42);
+        shuffle(&mut b, 42);
+        shuffle(&mut c, 43);
+        assert_eq!(a, b);
+        assert!(a != orig);
+        assert!(a != c);
+        a.sort();
+        assert_eq!(a, orig);
+    }
+
+    #[test]
+    fn parse_shard_and_shuffle() {
+        let args = |extra: &[&str]| -> Vec<String> {
+            let mut args = vec!["progname".to_string()];
+            args.extend(extra.iter().map(|s| s.to_string()));
+            args
+        };
+        let opts = match parse_opts(&args(&["--shard-index", "1", "--shard-count", "4"])) {
+            Some(Ok(o)) => o,
+            _ => panic!("Malformed arg in parse_shard_and_shuffle"),
+        };
+        assert_eq!(opts.shard, Some((1, 4)));
+        assert_eq!(opts.shuffle_seed, None);
+        for bad in &[&["--shard-index", "4", "--shard-count", "4"][..], &["--shard-index", "0"]] {
+            match parse_opts(&args(bad)) {
+                Some(Err(_)) => {}
+                _ => panic!("{:?} should be rejected", bad),
+            }
+        }
+
+        let opts = match parse_opts(&args(&["--shuffle-seed", "7"])) {
+            Some(Ok(o)) => o,
+            _ => panic!("Malformed arg in parse_shard_and_shuffle"),
+        };
+        assert_eq!(opts.shuffle_seed, Some(7));
+        let opts = match parse_opts(&args(&["--shuffle"])) {
+            Some(Ok(o)) => o,
+            _ => panic!("Malformed arg in parse_shard_and_shuffle"),
+        };
+        assert!(opts.shuffle_seed.is_some());
+    }
+
     #[test]
     pub fn test_metricmap_compare() {
         let mut m1 = MetricMap::new();
@@ -2084,6 +3430,46 @@ mod tests {
 
         m1.insert_metric("in-both-want-upwards-and-improved", 1000.0, -10.0);
         m2.insert_metric("in-both-want-upwards-and-improved", 2000.0, -10.0);
+
+        let diff = m2.compare_to_old(&m1);
+        assert_eq!(diff.len(), 7);
+        let change = |name: &str| diff[name];
+
+        match change("in-both-noise") {
+            MetricChange::LikelyNoise(pct, _) => assert_eq!(pct, 10.0),
+            c => panic!("unexpected change {:?}", c),
+        }
+        assert_eq!(change("in-first-noise"), MetricChange::MetricRemoved);
+        assert_eq!(change("in-second-noise"), MetricChange::MetricAdded);
+        match change("in-both-want-downwards-but-regressed") {
+            MetricChange::Regression(pct, _) => assert_eq!(pct, 100.0),
+            c => panic!("unexpected change {:?}", c),
+        }
+        match change("in-both-want-downwards-and-improved") {
+            MetricChange::Improvement(pct, _) => assert_eq!(pct, -50.0),
+            c => panic!("unexpected change {:?}", c),
+        }
+        match change("in-both-want-upwards-but-regressed") {
+            MetricChange::Regression(pct, _) => assert_eq!(pct, -50.0),
+            c => panic!("unexpected change {:?}", c),
+        }
+        match change("in-both-want-upwards-and-improved") {
+            MetricChange::Improvement(pct, _) => assert_eq!(pct, 100.0),
+            c => panic!("unexpected change {:?}", c),
+        }
+    }
+
+    #[test]
+    pub fn metricmap_save_and_load() {
+        let mut m = MetricMap::new();
+        m.insert_metric("a::bench", 1234.5, 6.25);
+        m.insert_metric("b::bench with spaces", 0.1, -0.3);
+
+        let path = env::temp_dir().join(format!("libtest-metrics-{}.txt", process::id()));
+        m.save(&path).unwrap();
+        let loaded = MetricMap::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(loaded.unwrap() == m);
     }
 
     #[test]
@@ -2111,6 +3497,8 @@ mod tests {
             ignore: false,
             should_panic: ShouldPanic::No,
             allow_fail: false,
+            timeout: None,
+            retries: None,
         };
 
         ::bench::benchmark(desc, tx, true, f);
@@ -2130,6 +3518,8 @@ mod tests {
             ignore: false,
             should_panic: ShouldPanic::No,
             allow_fail: false,
+            timeout: None,
+            retries: None,
         };
 
         ::bench::benchmark(desc, tx, true, f);
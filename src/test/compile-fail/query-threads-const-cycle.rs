@@ -0,0 +1,25 @@
+// Copyright 2018 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Check that a query cycle between bodies which are checked on different
+// query threads is reported as a cycle, by the deadlock handler, rather than
+// hanging. Which query the cycle is reported from depends on scheduling.
+
+// needs-parallel-queries
+// compile-flags: -Z query-threads=8
+// error-pattern: cycle detected
+
+const A: usize = B;
+const B: usize = C;
+const C: usize = A;
+
+fn main() {
+    let _ = [A, B, C];
+}
@@ -0,0 +1,31 @@
+// Copyright 2018 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Check that a cycle through the auto traits leaked by `impl Trait` return
+// types, whose bodies are type checked in parallel, is reported rather than
+// deadlocking the query threads.
+
+// needs-parallel-queries
+// compile-flags: -Z query-threads=8
+// error-pattern: cycle detected
+
+fn send<T: Send>(_: T) {}
+
+fn foo() -> impl Sized {
+    send(bar());
+    1u8
+}
+
+fn bar() -> impl Sized {
+    send(foo());
+    2u8
+}
+
+fn main() {}
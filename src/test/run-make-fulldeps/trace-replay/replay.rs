@@ -0,0 +1,357 @@
+// Copyright 2018 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+#![feature(rustc_private)]
+
+extern crate rustc;
+extern crate rustc_driver;
+extern crate rustc_lint;
+extern crate rustc_metadata;
+extern crate rustc_mir;
+extern crate rustc_errors;
+extern crate rustc_codegen_utils;
+extern crate syntax;
+
+use rustc::mir::interpret::EvalErrorKind;
+use rustc::session::{build_session, Session};
+use rustc::session::config::{ErrorOutputType, Input, Options};
+use rustc::ty::{self, Instance, TyCtxt};
+use rustc_driver::Compilation;
+use rustc_driver::driver::{self, compile_input, CompileController};
+use rustc_metadata::cstore::CStore;
+use rustc_mir::interpret::{replay_trace, Divergence, TraceLoc, YkTraceRecord};
+use rustc_errors::registry::Registry;
+use syntax::source_map::FileName;
+use rustc_codegen_utils::codegen_backend::CodegenBackend;
+
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+// The replayed functions are those of `traced.rs`, loaded from its rlib.
+const SRC: &str = "extern crate traced; fn main() {}";
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+
+    if args.len() < 3 {
+        panic!("expected rustc path and output directory");
+    }
+
+    let mut sysroot = PathBuf::from(&args[1]);
+    sysroot.pop();
+    sysroot.pop();
+
+    let tmpdir = &args[2];
+    let section = fs::read(format!("{}/traced.yk_mir_cfg", tmpdir)).unwrap();
+    let cfg = decode_section(&section);
+
+    let checked = Cell::new(false);
+    syntax::with_globals(|| {
+        let mut opts = Options::default();
+        opts.maybe_sysroot = Some(sysroot);
+        opts.search_paths.add_path(tmpdir, ErrorOutputType::default());
+        driver::spawn_thread_pool(opts, |opts| {
+            let (sess, cstore, codegen_backend) = basic_sess(opts);
+            let mut control = CompileController::basic();
+            control.after_analysis.stop = Compilation::Stop;
+            control.after_analysis.callback = Box::new(|state: &mut driver::CompileState| {
+                check(state.tcx.unwrap(), &cfg);
+                checked.set(true);
+            });
+            let input = Input::Str { name: FileName::Anon, input: SRC.to_string() };
+            let _ = compile_input(
+                codegen_backend,
+                &sess,
+                &cstore,
+                &None,
+                &input,
+                &None,
+                &None,
+                None,
+                &control
+            );
+        });
+    });
+    assert!(checked.get());
+}
+
+fn basic_sess(opts: Options) -> (Session, Rc<CStore>, Box<CodegenBackend>) {
+    let descriptions = Registry::new(&rustc::DIAGNOSTICS);
+    let sess = build_session(opts, None, descriptions);
+    let codegen_backend = rustc_driver::get_codegen_backend(&sess);
+    let cstore = Rc::new(CStore::new(codegen_backend.metadata_loader()));
+    rustc_lint::register_builtins(&mut sess.lint_store.borrow_mut(), Some(&sess));
+    (sess, cstore, codegen_backend)
+}
+
+/// A function, as identified by the section: its crate hash and def index.
+type Func = (u64, u32);
+
+/// Where a block goes, as far as building a trace is concerned.
+enum Edge {
+    /// A `Goto`, or anything else with a single successor on the normal path.
+    Goto(u32),
+    SwitchInt(Vec<u32>),
+    /// A call, and its callee if it is statically known.
+    Call(Option<Func>),
+    Return,
+    /// A block which doesn't continue on the normal path, e.g. `Resume`.
+    End,
+}
+
+/// The blocks of each function with MIR in the section.
+type Cfg = HashMap<Func, BTreeMap<u32, Edge>>;
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    // Fixed size integers are native endian. They are assembled as if they
+    // were little endian, so `from_le` puts them right.
+    fn int(&mut self, size: usize) -> u64 {
+        let bytes = &self.data[self.pos..self.pos + size];
+        self.pos += size;
+        bytes.iter().enumerate().fold(0, |v, (i, byte)| v | (*byte as u64) << (8 * i))
+    }
+
+    fn u8(&mut self) -> u8 {
+        self.int(1) as u8
+    }
+
+    fn u32(&mut self) -> u32 {
+        u32::from_le(self.int(4) as u32)
+    }
+
+    fn u64(&mut self) -> u64 {
+        u64::from_le(self.int(8))
+    }
+
+    fn func(&mut self) -> Func {
+        (self.u64(), self.u32())
+    }
+
+    fn skip_str(&mut self) {
+        let len = self.u32() as usize;
+        self.pos += len;
+    }
+}
+
+/// Decodes a version 1 `.yk_mir_cfg` section, as written by
+/// `librustc_yk_sections/mir_cfg.rs`.
+fn decode_section(data: &[u8]) -> Cfg {
+    let mut rdr = Reader { data, pos: 0 };
+    assert_eq!(u16::from_le(rdr.int(2) as u16), 1, "section version");
+    let mut cfg = Cfg::new();
+    loop {
+        let kind = rdr.u8();
+        if kind == 255 {
+            return cfg;
+        }
+        let func = rdr.func();
+        if kind == 254 {
+            // No MIR.
+            continue;
+        }
+        let bb = rdr.u32();
+        let edge = match kind {
+            // Goto, FalseEdges and FalseUnwind.
+            0 | 19 | 20 => Edge::Goto(rdr.u32()),
+            1 => {
+                let n = rdr.u64();
+                Edge::SwitchInt((0..n).map(|_| rdr.u32()).collect())
+            }
+            2 | 3 | 5 | 18 => Edge::End,
+            4 => Edge::Return,
+            // Drops and drop-and-replaces, with and without an unwind block.
+            6...9 => {
+                let target = rdr.u32();
+                if kind == 7 || kind == 9 {
+                    rdr.u32();
+                }
+                if rdr.u8() == 1 {
+                    // The drop glue's symbol and type.
+                    rdr.skip_str();
+                    rdr.skip_str();
+                }
+                Edge::Goto(target)
+            }
+            10 | 11 => {
+                let callee = rdr.func();
+                if kind == 11 {
+                    rdr.u32();
+                }
+                Edge::Call(Some(callee))
+            }
+            12 | 13 => {
+                if kind == 13 {
+                    rdr.u32();
+                }
+                Edge::Call(None)
+            }
+            // Asserts and yields, followed by their cleanup or drop blocks.
+            14...17 => {
+                let target = rdr.u32();
+                if kind == 15 || kind == 17 {
+                    rdr.u32();
+                }
+                Edge::Goto(target)
+            }
+            // The generator layout and states.
+            21 | 22 => {
+                let pairs = if kind == 21 { rdr.u32(); 1 } else { 2 };
+                for _ in 0..pairs {
+                    let n = rdr.u32();
+                    rdr.pos += 8 * n as usize;
+                }
+                continue;
+            }
+            _ => panic!("unknown record kind {}", kind),
+        };
+        cfg.entry(func).or_insert_with(BTreeMap::new).insert(bb, edge);
+    }
+}
+
+/// Builds the trace of a run of `start` by walking its blocks in the section,
+/// following calls into functions with blocks of their own. `choices` picks
+/// the target of each `SwitchInt` in turn. Calls don't record the block they
+/// return to, but in `traced.rs` it is always the one after the call.
+fn walk(cfg: &Cfg, start: Func, choices: &[usize]) -> Vec<YkTraceRecord> {
+    let mut trace = Vec::new();
+    let mut choices = choices.iter();
+    let mut returns = Vec::new();
+    let mut next = Some((start, 0));
+    while let Some((func, bb)) = next {
+        trace.push(YkTraceRecord { crate_hash: func.0, def_index: func.1, block: bb });
+        next = match cfg[&func][&bb] {
+            Edge::Goto(target) => Some((func, target)),
+            Edge::SwitchInt(ref targets) => Some((func, targets[*choices.next().unwrap()])),
+            Edge::Call(Some(callee)) if cfg.contains_key(&callee) => {
+                returns.push((func, bb + 1));
+                Some((callee, 0))
+            }
+            Edge::Call(_) => Some((func, bb + 1)),
+            Edge::Return => returns.pop(),
+            Edge::End => None,
+        };
+    }
+    trace
+}
+
+/// Maps the records of a trace to the blocks of the crates loaded by `tcx`.
+fn to_trace_locs(tcx: TyCtxt, records: &[YkTraceRecord]) -> Vec<TraceLoc> {
+    records.iter().map(|rec| TraceLoc::from_yk_record(tcx, *rec).unwrap()).collect()
+}
+
+/// Finds the function of `traced.rs` called `name` in the section.
+fn find_fn(tcx: TyCtxt, cfg: &Cfg, name: &str) -> Func {
+    *cfg.keys().find(|func| {
+        let record = YkTraceRecord { crate_hash: func.0, def_index: func.1, block: 0 };
+        match TraceLoc::from_yk_record(tcx, record) {
+            Some(loc) => {
+                tcx.crate_name(loc.def_id.krate) == "traced" && tcx.item_name(loc.def_id) == name
+            }
+            None => false,
+        }
+    }).unwrap()
+}
+
+fn check<'a, 'tcx>(tcx: TyCtxt<'a, 'tcx, 'tcx>, cfg: &Cfg) {
+    let param_env = ty::ParamEnv::reveal_all();
+    let instance = |func: Func| {
+        let record = YkTraceRecord { crate_hash: func.0, def_index: func.1, block: 0 };
+        Instance::mono(tcx, TraceLoc::from_yk_record(tcx, record).unwrap().def_id)
+    };
+    let u32_arg = |v| ty::Const::from_bits(tcx, v, param_env.and(tcx.types.u32));
+
+    // A matching trace follows calls into the callee.
+    let calls = find_fn(tcx, cfg, "calls");
+    let callee = instance(find_fn(tcx, cfg, "callee")).def_id();
+    let trace = to_trace_locs(tcx, &walk(cfg, calls, &[]));
+    assert!(trace.iter().any(|loc| loc.def_id == callee));
+    let replay = replay_trace(tcx, instance(calls), param_env, &[], &trace).unwrap();
+    assert_eq!(replay.divergence, None);
+    assert!(replay.steps.iter().any(|step| step.depth == 1));
+
+    // A loop running three times enters its header four times. Each
+    // `SwitchInt` on a `bool` has the `false` target first.
+    let loops = find_fn(tcx, cfg, "loops");
+    let trace = to_trace_locs(tcx, &walk(cfg, loops, &[1, 1, 1, 0]));
+    let most_entries = trace.iter()
+        .map(|loc| trace.iter().filter(|other| *other == loc).count())
+        .max();
+    assert_eq!(most_entries, Some(4));
+    let replay = replay_trace(tcx, instance(loops), param_env, &[], &trace).unwrap();
+    assert_eq!(replay.divergence, None);
+
+    // Replay stops at the first block that differs from the trace.
+    let mut wrong = trace.clone();
+    wrong[2] = TraceLoc { def_id: callee, block: trace[2].block };
+    let replay = replay_trace(tcx, instance(loops), param_env, &[], &wrong).unwrap();
+    assert_eq!(replay.divergence, Some(Divergence {
+        position: 2,
+        expected: Some(wrong[2]),
+        actual: Some(trace[2]),
+    }));
+
+    // A trace which goes on after the function returned also diverges.
+    let mut long = trace.clone();
+    long.push(trace[0]);
+    let replay = replay_trace(tcx, instance(loops), param_env, &[], &long).unwrap();
+    assert_eq!(replay.divergence, Some(Divergence {
+        position: trace.len(),
+        expected: Some(trace[0]),
+        actual: None,
+    }));
+
+    // Arguments decide which way the replay goes.
+    let branches = find_fn(tcx, cfg, "branches");
+    let trace = to_trace_locs(tcx, &walk(cfg, branches, &[1]));
+    let replay = replay_trace(tcx, instance(branches), param_env, &[u32_arg(3)], &trace);
+    assert_eq!(replay.unwrap().divergence, None);
+    let replay = replay_trace(tcx, instance(branches), param_env, &[u32_arg(0)], &trace);
+    assert_eq!(replay.unwrap().divergence.map(|d| d.position), Some(1));
+    match replay_trace(tcx, instance(branches), param_env, &[], &trace) {
+        Err(err) => match err.kind {
+            EvalErrorKind::FunctionArgCountMismatch => {}
+            ref kind => panic!("unexpected error {:?}", kind),
+        },
+        Ok(_) => panic!("replay without arguments succeeded"),
+    }
+
+    // Pointers into the same array are ordered by their offsets.
+    let compares_ptrs = find_fn(tcx, cfg, "compares_ptrs");
+    let trace = to_trace_locs(tcx, &walk(cfg, compares_ptrs, &[1]));
+    let replay = replay_trace(tcx, instance(compares_ptrs), param_env, &[], &trace).unwrap();
+    assert_eq!(replay.divergence, None);
+
+    // A box is allocated and read. The trace doesn't include the drop glue
+    // freeing it, so the replay diverges on entering the glue.
+    let boxes = find_fn(tcx, cfg, "boxes");
+    let trace = to_trace_locs(tcx, &walk(cfg, boxes, &[]));
+    let replay = replay_trace(tcx, instance(boxes), param_env, &[], &trace).unwrap();
+    let drop_in_place = tcx.lang_items().drop_in_place_fn();
+    assert_eq!(replay.divergence.and_then(|d| d.actual).map(|loc| loc.def_id), drop_in_place);
+
+    // Calls to code without MIR are rejected.
+    let foreign = find_fn(tcx, cfg, "foreign");
+    let trace = to_trace_locs(tcx, &walk(cfg, foreign, &[]));
+    match replay_trace(tcx, instance(foreign), param_env, &[], &trace) {
+        Err(err) => match err.kind {
+            EvalErrorKind::MachineError(ref msg) => assert!(msg.contains("has no MIR"), "{}", msg),
+            ref kind => panic!("unexpected error {:?}", kind),
+        },
+        Ok(replay) => panic!("replay succeeded: {:?}", replay.divergence),
+    }
+}
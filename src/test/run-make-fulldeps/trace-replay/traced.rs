@@ -0,0 +1,64 @@
+// Copyright 2018 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+
+// The program whose traces `replay.rs` replays. It is built as an executable,
+// for its `.yk_mir_cfg` section, and as an rlib, for `replay.rs` to load.
+
+#![feature(box_syntax)]
+
+extern {
+    fn abs(x: i32) -> i32;
+}
+
+fn main() {
+    calls();
+    loops();
+    branches(3);
+    compares_ptrs();
+    boxes();
+    foreign();
+}
+
+pub fn callee() -> u32 {
+    2
+}
+
+pub fn calls() -> u32 {
+    callee()
+}
+
+pub fn loops() -> u32 {
+    let mut i = 0;
+    while i < 3 {
+        i += 1;
+    }
+    i
+}
+
+pub fn branches(x: u32) -> u32 {
+    if x > 2 { 1 } else { 0 }
+}
+
+pub fn compares_ptrs() -> u32 {
+    let a = [1u8, 2];
+    let p = &a[0] as *const u8;
+    let q = &a[1] as *const u8;
+    if p < q { 1 } else { 0 }
+}
+
+pub fn boxes() -> u32 {
+    let b = box 7;
+    *b
+}
+
+pub fn foreign() -> i32 {
+    unsafe { abs(-1) }
+}
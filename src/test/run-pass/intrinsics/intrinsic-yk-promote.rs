@@ -0,0 +1,33 @@
+// Copyright 2018 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// `yk_promote` returns its argument, whatever its size and passing mode.
+
+#![feature(core_intrinsics)]
+
+use std::intrinsics::yk_promote;
+
+#[derive(Debug, PartialEq)]
+struct Big([u64; 8]);
+
+fn main() {
+    unsafe {
+        assert_eq!(yk_promote(7u8), 7);
+        assert_eq!(yk_promote(-1i64), -1);
+        assert_eq!(yk_promote(1.5f64), 1.5);
+        assert_eq!(yk_promote((1u8, 2u32)), (1, 2));
+        assert_eq!(yk_promote(()), ());
+        assert_eq!(yk_promote(String::from("opcode")), "opcode");
+        assert_eq!(yk_promote(Big([3; 8])), Big([3; 8]));
+
+        let x = 5;
+        assert_eq!(*yk_promote(&x), 5);
+    }
+}
@@ -0,0 +1,46 @@
+// Copyright 2018 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Check that type checking, borrow checking and optimizing the MIR of many
+// bodies in parallel, which depend on each other's results through `impl
+// Trait` and constants, completes and compiles correctly.
+
+// needs-parallel-queries
+// compile-flags: -Z query-threads=8
+
+use std::fmt::Debug;
+
+const BASE: u32 = 10;
+const DOUBLE: u32 = BASE * 2;
+
+fn send<T: Send>(t: T) -> T { t }
+
+fn a() -> impl Debug + Clone { send(b()).clone() }
+fn b() -> impl Debug + Clone { send(c()) }
+fn c() -> impl Debug + Clone { (d(), DOUBLE) }
+fn d() -> impl Debug + Clone { send(vec![BASE; 3]) }
+
+fn closures() -> u32 {
+    let add = |x: u32| x + BASE;
+    let mul = |x: u32| add(x) * 2;
+    (0..4).map(mul).sum()
+}
+
+struct S<T>(T);
+
+impl<T: Clone> S<T> {
+    fn get(&self) -> T { self.0.clone() }
+}
+
+fn main() {
+    assert_eq!(format!("{:?}", a()), "([10, 10, 10], 20)");
+    assert_eq!(closures(), 92);
+    assert_eq!(S(DOUBLE).get(), 20);
+}
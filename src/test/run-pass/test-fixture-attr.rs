@@ -0,0 +1,55 @@
+// Copyright 2018 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// compile-flags: --test
+#![feature(test_fixtures)]
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+static MODULE_READY: AtomicBool = AtomicBool::new(false);
+static SETUPS: AtomicUsize = AtomicUsize::new(0);
+
+#[test_fixture(module_setup)]
+fn prepare_module() {
+    MODULE_READY.store(true, Ordering::SeqCst);
+}
+
+#[test_fixture(setup)]
+fn prepare() {
+    SETUPS.fetch_add(1, Ordering::SeqCst);
+}
+
+#[test_fixture(teardown)]
+fn clean_up() -> Result<(), String> {
+    Ok(())
+}
+
+#[test]
+fn fixtures_ran_first() {
+    assert!(MODULE_READY.load(Ordering::SeqCst));
+    assert!(SETUPS.load(Ordering::SeqCst) >= 1);
+}
+
+mod inner {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    static INNER_READY: AtomicBool = AtomicBool::new(false);
+
+    #[test_fixture(setup)]
+    fn prepare_inner() {
+        INNER_READY.store(true, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn outer_fixtures_apply() {
+        assert!(super::MODULE_READY.load(Ordering::SeqCst));
+        assert!(INNER_READY.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,73 @@
+// Copyright 2018 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Decodes the `.yk_promote` section of the running executable, which must
+// list the two `yk_promote` call sites below.
+
+// only-linux
+
+#![feature(core_intrinsics, int_to_from_bytes, yk_sections)]
+
+use std::intrinsics::yk_promote;
+use std::yk;
+
+#[inline(never)]
+fn opcode(code: &[u8], pc: usize) -> u8 {
+    unsafe { yk_promote(code[pc]) }
+}
+
+#[inline(never)]
+fn operand(code: &[u8], pc: usize) -> u8 {
+    unsafe { yk_promote(code[pc + 1]) }
+}
+
+fn leb(data: &[u8], pos: &mut usize) -> u64 {
+    let mut result = 0;
+    let mut shift = 0;
+    loop {
+        let byte = data[*pos];
+        *pos += 1;
+        result |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return result;
+        }
+        shift += 7;
+    }
+}
+
+fn main() {
+    let code = [1, 2];
+    assert_eq!((opcode(&code, 0), operand(&code, 0)), (1, 2));
+
+    let sections = yk::promote_sections();
+    let exe = sections.iter().find(|sec| sec.object().as_os_str().is_empty())
+        .expect("no promotion section in the executable");
+    let data = exe.data();
+
+    assert_eq!(u16::from_ne_bytes([data[0], data[1]]), 1);
+    let mut pos = 2;
+    let count = leb(data, &mut pos);
+    assert_eq!(count, 2);
+
+    let mut sites = Vec::new();
+    for _ in 0..count {
+        let mut hash = [0; 8];
+        hash.copy_from_slice(&data[pos..pos + 8]);
+        pos += 8;
+        let def_index = leb(data, &mut pos);
+        let block = leb(data, &mut pos);
+        sites.push((u64::from_ne_bytes(hash), def_index, block));
+    }
+    assert_eq!(pos, data.len());
+
+    // Both call sites are in this crate, in different functions, and sorted.
+    assert_eq!(sites[0].0, sites[1].0);
+    assert!(sites[0].1 < sites[1].1);
+}
@@ -0,0 +1,26 @@
+// Copyright 2018 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// `Abort` in the cleanup path of a function that must not unwind.
+
+#![feature(unwind_attributes)]
+
+#[unwind(aborts)]
+#[inline(never)]
+extern "C" fn f(x: u8) -> u8 {
+    if x == 0 {
+        panic!();
+    }
+    x
+}
+
+fn main() {
+    f(1);
+}
@@ -0,0 +1,28 @@
+// Copyright 2018 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// compile-flags: -C overflow-checks=on
+
+// `Assert` terminators from overflow and bounds checks.
+
+#[inline(never)]
+fn add(x: u8, y: u8) -> u8 {
+    x + y
+}
+
+#[inline(never)]
+fn index(a: [u8; 2], i: usize) -> u8 {
+    a[i]
+}
+
+fn main() {
+    add(1, 2);
+    index([1, 2], 1);
+}
@@ -0,0 +1,33 @@
+// Copyright 2018 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// `Call` to statically known and unknown targets, with and without cleanup.
+
+#[inline(never)]
+fn known() -> u8 {
+    1
+}
+
+#[inline(never)]
+fn unknown(f: fn() -> u8) -> u8 {
+    f()
+}
+
+#[inline(never)]
+fn with_cleanup() -> u8 {
+    let _s = String::from("x");
+    known()
+}
+
+fn main() {
+    known();
+    unknown(known);
+    with_cleanup();
+}
@@ -0,0 +1,35 @@
+// Copyright 2018 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// A compressed version 2 section: the `drop` test with `-Z yk-compress-sections`.
+
+// compile-flags: -Z yk-compress-sections
+
+struct D(u8);
+
+impl Drop for D {
+    fn drop(&mut self) {}
+}
+
+#[inline(never)]
+fn no_unwind() {
+    let _d = D(0);
+}
+
+#[inline(never)]
+fn with_unwind() {
+    let _d = D(0);
+    let _e = D(1);
+}
+
+fn main() {
+    no_unwind();
+    with_unwind();
+}
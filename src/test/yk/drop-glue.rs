@@ -0,0 +1,31 @@
+// Copyright 2018 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Drops whose glue is known to do nothing, and whose glue can't be known
+// until monomorphisation.
+
+// An `impl Trait` type needs dropping until codegen reveals it to be `u8`.
+#[inline(never)]
+fn make() -> impl Sized {
+    1u8
+}
+
+#[inline(never)]
+fn no_glue() {
+    let _x = make();
+}
+
+#[inline(never)]
+fn generic<T>(_x: T) {}
+
+fn main() {
+    no_glue();
+    generic(String::new());
+}
@@ -0,0 +1,35 @@
+// Copyright 2018 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Generators. `Yield` and `GeneratorDrop` are rewritten by the generator state
+// transform before the CFG is serialised, so their records are covered by the
+// decoder's unit tests instead. The resume function's switch over the
+// generator state is described by the generator records.
+
+#![feature(generators, generator_trait)]
+
+use std::ops::Generator;
+
+#[inline(never)]
+fn run() {
+    let mut gen = || {
+        let _s = String::from("x");
+        yield 1u8;
+        yield 2u8;
+    };
+    unsafe {
+        gen.resume();
+        gen.resume();
+    }
+}
+
+fn main() {
+    run();
+}
@@ -0,0 +1,28 @@
+// Copyright 2018 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Loops and matches are built with `FalseUnwind` and `FalseEdges`, which are
+// simplified away before the CFG is serialised. Their records are covered by
+// the decoder's unit tests instead.
+
+#[inline(never)]
+fn f(mut x: u8) -> u8 {
+    loop {
+        match x {
+            0 => break,
+            _ => x -= 1,
+        }
+    }
+    x
+}
+
+fn main() {
+    f(3);
+}
@@ -0,0 +1,33 @@
+// Copyright 2018 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// `SwitchInt` on an integer and on an enum discriminant.
+
+#[inline(never)]
+fn f(x: u8) -> u8 {
+    match x {
+        0 => 10,
+        1 => 20,
+        _ => 30,
+    }
+}
+
+#[inline(never)]
+fn g(x: Option<u8>) -> u8 {
+    match x {
+        Some(y) => y,
+        None => 0,
+    }
+}
+
+fn main() {
+    f(1);
+    g(Some(1));
+}
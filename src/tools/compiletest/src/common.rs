@@ -34,6 +34,7 @@ pub enum Mode {
     RunMake,
     Ui,
     MirOpt,
+    Yk,
 }
 
 impl Mode {
@@ -69,6 +70,7 @@ impl FromStr for Mode {
             "run-make" => Ok(RunMake),
             "ui" => Ok(Ui),
             "mir-opt" => Ok(MirOpt),
+            "yk" => Ok(Yk),
             _ => Err(()),
         }
     }
@@ -92,6 +94,7 @@ impl fmt::Display for Mode {
             RunMake => "run-make",
             Ui => "ui",
             MirOpt => "mir-opt",
+            Yk => "yk",
         };
         fmt::Display::fmt(s, f)
     }
@@ -214,6 +217,9 @@ pub struct Config {
     /// Is LLVM a system LLVM
     pub system_llvm: bool,
 
+    /// Was rustc built with parallel queries
+    pub parallel_queries: bool,
+
     /// Path to the android tools
     pub android_cross_path: PathBuf,
 
@@ -262,14 +268,15 @@ pub struct TestPaths {
     pub relative_dir: PathBuf, // e.g., foo/bar
 }
 
-/// Used by `ui` tests to generate things like `foo.stderr` from `foo.rs`.
+/// Used by `ui` tests to generate things like `foo.stderr` from `foo.rs`, and by `yk` tests
+/// for `foo.yk`.
 pub fn expected_output_path(
     testpaths: &TestPaths,
     revision: Option<&str>,
     compare_mode: &Option<CompareMode>,
     kind: &str,
 ) -> PathBuf {
-    assert!(UI_EXTENSIONS.contains(&kind));
+    assert!(UI_EXTENSIONS.contains(&kind) || kind == YK_DUMP);
     let mut parts = Vec::new();
 
     if let Some(x) = revision {
@@ -288,6 +295,7 @@ pub const UI_EXTENSIONS: &[&str] = &[UI_STDERR, UI_STDOUT, UI_FIXED];
 pub const UI_STDERR: &str = "stderr";
 pub const UI_STDOUT: &str = "stdout";
 pub const UI_FIXED: &str = "fixed";
+pub const YK_DUMP: &str = "yk";
 
 /// Absolute path to the directory where all output for all tests in the given
 /// `relative_dir` group should reside. Example:
@@ -15,6 +15,7 @@
 extern crate diff;
 extern crate env_logger;
 extern crate filetime;
+extern crate flate2;
 extern crate getopts;
 #[cfg(unix)]
 extern crate libc;
@@ -31,6 +32,7 @@ extern crate rustfix;
 
 use common::CompareMode;
 use common::{expected_output_path, output_base_dir, output_relative_path, UI_EXTENSIONS};
+use common::YK_DUMP;
 use common::{Config, TestPaths};
 use common::{DebugInfoGdb, DebugInfoLldb, Mode, Pretty};
 use filetime::FileTime;
@@ -54,6 +56,7 @@ mod raise_fd_limit;
 mod read2;
 pub mod runtest;
 pub mod util;
+mod yk;
 
 fn main() {
     env_logger::init();
@@ -140,7 +143,7 @@ pub fn parse_config(args: Vec<String>) -> Config {
             "mode",
             "which sort of compile tests to run",
             "(compile-fail|parse-fail|run-fail|run-pass|\
-             run-pass-valgrind|pretty|debug-info|incremental|mir-opt)",
+             run-pass-valgrind|pretty|debug-info|incremental|mir-opt|yk)",
         )
         .optflag("", "ignored", "run tests marked as ignored")
         .optflag("", "exact", "filters match exactly")
@@ -197,6 +200,7 @@ pub fn parse_config(args: Vec<String>) -> Config {
             "VERSION STRING",
         )
         .optflag("", "system-llvm", "is LLVM the system LLVM")
+        .optflag("", "parallel-queries", "was rustc built with parallel queries")
         .optopt(
             "",
             "android-cross-path",
@@ -331,6 +335,7 @@ pub fn parse_config(args: Vec<String>) -> Config {
         lldb_native_rust,
         llvm_version: matches.opt_str("llvm-version"),
         system_llvm: matches.opt_present("system-llvm"),
+        parallel_queries: matches.opt_present("parallel-queries"),
         android_cross_path: android_cross_path,
         adb_path: opt_str2(matches.opt_str("adb-path")),
         adb_test_dir: opt_str2(matches.opt_str("adb-test-dir")),
@@ -739,6 +744,12 @@ fn up_to_date(
         inputs.push(mtime(path));
     }
 
+    // Yorick section dumps.
+    if config.mode == Mode::Yk {
+        let path = &expected_output_path(testpaths, revision, &config.compare_mode, YK_DUMP);
+        inputs.push(mtime(path));
+    }
+
     inputs.iter().any(|input| *input > stamp)
 }
 
@@ -9,12 +9,12 @@
 // except according to those terms.
 
 use common::CompareMode;
-use common::{expected_output_path, UI_EXTENSIONS, UI_FIXED, UI_STDERR, UI_STDOUT};
+use common::{expected_output_path, UI_EXTENSIONS, UI_FIXED, UI_STDERR, UI_STDOUT, YK_DUMP};
 use common::{output_base_dir, output_base_name, output_testname_unique};
 use common::{Codegen, CodegenUnits, DebugInfoGdb, DebugInfoLldb, Rustdoc};
 use common::{CompileFail, ParseFail, Pretty, RunFail, RunPass, RunPassValgrind};
 use common::{Config, TestPaths};
-use common::{Incremental, MirOpt, RunMake, Ui};
+use common::{Incremental, MirOpt, RunMake, Ui, Yk};
 use diff;
 use errors::{self, Error, ErrorKind};
 use filetime::FileTime;
@@ -23,6 +23,7 @@ use json;
 use regex::Regex;
 use rustfix::{apply_suggestions, get_suggestions_from_json, Filter};
 use util::{logv, PathBufExt};
+use yk;
 
 use std::collections::hash_map::DefaultHasher;
 use std::collections::{HashMap, HashSet, VecDeque};
@@ -277,6 +278,7 @@ impl<'test> TestCx<'test> {
             RunMake => self.run_rmake_test(),
             RunPass | Ui => self.run_ui_test(),
             MirOpt => self.run_mir_opt_test(),
+            Yk => self.run_yk_test(),
         }
     }
 
@@ -1775,7 +1777,7 @@ impl<'test> TestCx<'test> {
                 rustc.arg(dir_opt);
             }
             RunFail | RunPassValgrind | Pretty | DebugInfoGdb | DebugInfoLldb
-            | Codegen | Rustdoc | RunMake | CodegenUnits => {
+            | Codegen | Rustdoc | RunMake | CodegenUnits | Yk => {
                 // do not use JSON output
             }
         }
@@ -2818,6 +2820,51 @@ impl<'test> TestCx<'test> {
         self.check_mir_dump();
     }
 
+    fn run_yk_test(&self) {
+        let mut rustc = self.make_compile_args(
+            &self.testpaths.file,
+            TargetLocation::ThisFile(self.make_exe_name()),
+        );
+        rustc.arg("-L").arg(&self.aux_output_dir_name());
+        rustc.env("YK_DEBUG_SECTIONS", "1");
+        rustc.env("YK_PRINT_CRATE_HASHES", "1");
+        let proc_res = self.compose_and_run_compiler(rustc, None);
+        if !proc_res.status.success() {
+            self.fatal_proc_rec("compilation failed!", &proc_res);
+        }
+
+        // Pull the raw section out of the binary.
+        let section_file = self.make_exe_name().with_extra_extension("yk_mir_cfg");
+        let output = Command::new("objcopy")
+            .args(&["-O", "binary", "--only-section", yk::MIR_CFG_SECTION_NAME])
+            .arg(&self.make_exe_name())
+            .arg(&section_file)
+            .output()
+            .unwrap_or_else(|e| self.fatal(&format!("failed to run objcopy: {}", e)));
+        if !output.status.success() {
+            self.fatal(&format!(
+                "objcopy failed:\n{}",
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+        let mut data = Vec::new();
+        File::open(&section_file)
+            .and_then(|mut f| f.read_to_end(&mut data))
+            .unwrap_or_else(|e| self.fatal(&format!("failed to read section: {}", e)));
+
+        let crates = yk::parse_crate_hashes(&proc_res.stderr);
+        let crate_name = self.testpaths.file.file_stem().unwrap().to_str().unwrap()
+            .replace("-", "_");
+        let dump = yk::dump_mir_cfg(&data, &crates, &crate_name)
+            .unwrap_or_else(|e| self.fatal_proc_rec(&e, &proc_res));
+
+        let expected = self.load_expected_output(YK_DUMP);
+        if self.compare_output(YK_DUMP, &dump, &expected) > 0 {
+            println!("To update references, rerun the tests and pass the `--bless` flag");
+            self.fatal_proc_rec("Yorick section dump differed from expected", &proc_res);
+        }
+    }
+
     fn check_mir_dump(&self) {
         let mut test_file_contents = String::new();
         fs::File::open(self.testpaths.file.clone())
@@ -0,0 +1,718 @@
+// Copyright 2018 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Decoder for the Yorick sections that ykrustc links into executables.
+//!
+//! The decoder turns a raw `.yk_mir_cfg` section into a stable textual dump
+//! which `yk` tests compare against a checked-in `.yk` file. The format
+//! written by `librustc_yk_sections/mir_cfg.rs` must be mirrored here. All
+//! section versions are understood, and decode to the same dump, except that
+//! version 0 records no drop glue.
+
+use flate2::read::DeflateDecoder;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::io::Read;
+
+pub const MIR_CFG_SECTION_NAME: &str = ".yk_mir_cfg";
+
+// Section versions. Version 0 is version 1 without drop glue.
+const SECTION_VERSION_0: u16 = 0;
+const SECTION_VERSION_1: u16 = 1;
+const SECTION_VERSION_2: u16 = 2;
+
+// Version 2 header flags.
+const FLAG_DEFLATE: u8 = 1;
+
+// What follows the blocks of a function in version 2 sections.
+const GENERATOR_INFO_NONE: u8 = 0;
+const GENERATOR_INFO_FULL: u8 = 1;
+const GENERATOR_INFO_LAYOUT: u8 = 2;
+
+// Edge kinds.
+const GOTO: u8 = 0;
+const SWITCHINT: u8 = 1;
+const RESUME: u8 = 2;
+const ABORT: u8 = 3;
+const RETURN: u8 = 4;
+const UNREACHABLE: u8 = 5;
+const DROP_NO_UNWIND: u8 = 6;
+const DROP_WITH_UNWIND: u8 = 7;
+const DROP_AND_REPLACE_NO_UNWIND: u8 = 8;
+const DROP_AND_REPLACE_WITH_UNWIND: u8 = 9;
+const CALL_NO_CLEANUP: u8 = 10;
+const CALL_WITH_CLEANUP: u8 = 11;
+const CALL_UNKNOWN_NO_CLEANUP: u8 = 12;
+const CALL_UNKNOWN_WITH_CLEANUP: u8 = 13;
+const ASSERT_NO_CLEANUP: u8 = 14;
+const ASSERT_WITH_CLEANUP: u8 = 15;
+const YIELD_NO_DROP: u8 = 16;
+const YIELD_WITH_DROP: u8 = 17;
+const GENERATOR_DROP: u8 = 18;
+const FALSE_EDGES: u8 = 19;
+const FALSE_UNWIND: u8 = 20;
+const GENERATOR_LAYOUT: u8 = 21;
+const GENERATOR_STATES: u8 = 22;
+const NO_MIR: u8 = 254;
+const SENTINAL: u8 = 255;
+
+// What a drop runs.
+const DROP_GLUE_NONE: u8 = 0;
+const DROP_GLUE_SYMBOL: u8 = 1;
+const DROP_GLUE_UNRESOLVED: u8 = 2;
+
+/// Parses the crate hash table that the compiler prints to stderr when
+/// `YK_PRINT_CRATE_HASHES` is set. Lines look like `0x<hash> -> <crate name>`.
+pub fn parse_crate_hashes(stderr: &str) -> HashMap<u64, String> {
+    let mut map = HashMap::new();
+    for line in stderr.lines() {
+        if !line.starts_with("0x") {
+            continue;
+        }
+        let mut parts = line[2..].splitn(2, " -> ");
+        if let (Some(hash), Some(name)) = (parts.next(), parts.next()) {
+            if let Ok(hash) = u64::from_str_radix(hash, 16) {
+                map.insert(hash, name.trim().to_owned());
+            }
+        }
+    }
+    map
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if self.pos + n > self.data.len() {
+            return Err(format!("truncated section at offset {}", self.pos));
+        }
+        let ret = &self.data[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(ret)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    // The compiler writes fixed size integers in native endian. We assemble
+    // them as if they were little endian, so `from_le` puts them right on any
+    // host.
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_le(b[0] as u16 | (b[1] as u16) << 8))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let b = self.bytes(4)?;
+        let v = b.iter().enumerate().fold(0, |v, (i, byte)| v | (*byte as u32) << (8 * i));
+        Ok(u32::from_le(v))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        let b = self.bytes(8)?;
+        let v = b.iter().enumerate().fold(0, |v, (i, byte)| v | (*byte as u64) << (8 * i));
+        Ok(u64::from_le(v))
+    }
+
+    fn leb(&mut self) -> Result<u64, String> {
+        let mut result = 0;
+        let mut shift = 0;
+        loop {
+            let byte = self.u8()?;
+            if shift >= 64 {
+                return Err(format!("overlong LEB128 at offset {}", self.pos));
+            }
+            result |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(result);
+            }
+            shift += 7;
+        }
+    }
+
+    fn str(&mut self) -> Result<String, String> {
+        let len = self.leb()? as usize;
+        self.utf8(len)
+    }
+
+    /// Reads a version 1 string, whose length is a `u32`.
+    fn v1_str(&mut self) -> Result<String, String> {
+        let len = self.u32()? as usize;
+        self.utf8(len)
+    }
+
+    fn utf8(&mut self, len: usize) -> Result<String, String> {
+        Ok(String::from_utf8_lossy(self.bytes(len)?).into_owned())
+    }
+}
+
+/// The parts of the records which are encoded differently in each version.
+enum Encoding {
+    /// Fixed size native endian integers. Callees are a crate hash and def
+    /// index, and drop glue strings are stored inline. Version 0 sections have
+    /// no drop glue.
+    V1 { glue: bool },
+    /// LEB128 integers. Callees and drop glue strings are table indices.
+    V2 { funcs: Vec<String>, strs: Vec<String> },
+}
+
+/// The decoded functions of the local crate.
+#[derive(Default)]
+struct Dump {
+    /// Def index -> block -> description. Both sorted, as the compiler emits
+    /// functions in hash set order.
+    funcs: BTreeMap<u32, BTreeMap<u32, String>>,
+    /// Def index -> function-level records, such as generator information.
+    extra: BTreeMap<u32, Vec<String>>,
+}
+
+/// Decodes the MIR CFG section `data`, keeping only the functions of
+/// `local_crate`. Foreign call targets are printed by crate name only, so that
+/// the dump doesn't change whenever a dependency does.
+pub fn dump_mir_cfg(data: &[u8],
+                    crates: &HashMap<u64, String>,
+                    local_crate: &str) -> Result<String, String> {
+    let mut rdr = Reader { data, pos: 0 };
+    let mut dump = Dump::default();
+    match rdr.u16()? {
+        SECTION_VERSION_0 => {
+            let enc = Encoding::V1 { glue: false };
+            decode_v1(&mut rdr, &enc, crates, local_crate, &mut dump)?
+        }
+        SECTION_VERSION_1 => {
+            let enc = Encoding::V1 { glue: true };
+            decode_v1(&mut rdr, &enc, crates, local_crate, &mut dump)?
+        }
+        SECTION_VERSION_2 => decode_v2(&mut rdr, local_crate, &mut dump)?,
+        version => return Err(format!("unsupported section version {}", version)),
+    }
+
+    let mut out = String::new();
+    for (index, blocks) in dump.funcs {
+        writeln!(out, "fn {}::#{} {{", local_crate, index).unwrap();
+        if blocks.is_empty() {
+            writeln!(out, "    no mir").unwrap();
+        }
+        for (bb, desc) in blocks {
+            writeln!(out, "    bb{}: {}", bb, desc).unwrap();
+        }
+        for desc in dump.extra.remove(&index).unwrap_or_default() {
+            writeln!(out, "    {}", desc).unwrap();
+        }
+        writeln!(out, "}}").unwrap();
+    }
+    Ok(out)
+}
+
+fn def_path(crate_name: &str, index: u32, local_crate: &str) -> String {
+    if crate_name == local_crate {
+        format!("{}::#{}", crate_name, index)
+    } else {
+        format!("{}::_", crate_name)
+    }
+}
+
+fn decode_v1(rdr: &mut Reader,
+             enc: &Encoding,
+             crates: &HashMap<u64, String>,
+             local_crate: &str,
+             dump: &mut Dump) -> Result<(), String> {
+    let crate_name = |hash: u64| {
+        crates.get(&hash).cloned().unwrap_or_else(|| format!("0x{:016x}", hash))
+    };
+
+    loop {
+        let kind = rdr.u8()?;
+        if kind == SENTINAL {
+            return Ok(());
+        }
+        let hash = rdr.u64()?;
+        let index = rdr.u32()?;
+        let is_local = crate_name(hash) == local_crate;
+
+        if kind == NO_MIR {
+            if is_local {
+                dump.funcs.entry(index).or_insert_with(BTreeMap::new);
+            }
+            continue;
+        }
+
+        let bb = rdr.u32()?;
+        if kind == GENERATOR_LAYOUT || kind == GENERATOR_STATES {
+            let desc = decode_generator_record(rdr, enc, kind)?;
+            if is_local {
+                dump.extra.entry(index).or_insert_with(Vec::new).push(desc);
+            }
+            continue;
+        }
+
+        let desc = decode_edge(rdr, enc, kind, &|rdr: &mut Reader| {
+            let (h, i) = (rdr.u64()?, rdr.u32()?);
+            Ok(def_path(&crate_name(h), i, local_crate))
+        })?;
+        if is_local {
+            dump.funcs.entry(index).or_insert_with(BTreeMap::new).insert(bb, desc);
+        }
+    }
+}
+
+fn decode_v2(rdr: &mut Reader, local_crate: &str, dump: &mut Dump) -> Result<(), String> {
+    if rdr.u8()? & FLAG_DEFLATE != 0 {
+        let mut data = Vec::new();
+        DeflateDecoder::new(&rdr.data[rdr.pos..])
+            .read_to_end(&mut data)
+            .map_err(|e| format!("failed to decompress section: {}", e))?;
+        return decode_v2_body(&mut Reader { data: &data, pos: 0 }, local_crate, dump);
+    }
+    decode_v2_body(rdr, local_crate, dump)
+}
+
+/// Decodes the tables and groups of an uncompressed version 2 section.
+fn decode_v2_body(rdr: &mut Reader, local_crate: &str, dump: &mut Dump) -> Result<(), String> {
+    let mut crates = Vec::new();
+    for _ in 0..rdr.leb()? {
+        let _hash = rdr.u64()?;
+        crates.push(rdr.str()?);
+    }
+    let mut strs = Vec::new();
+    for _ in 0..rdr.leb()? {
+        strs.push(rdr.str()?);
+    }
+    // (is local, def index, printable path) for each function.
+    let mut funcs = Vec::new();
+    for _ in 0..rdr.leb()? {
+        let krate = crates.get(rdr.leb()? as usize).ok_or("bad crate index")?;
+        let index = rdr.leb()? as u32;
+        funcs.push((krate == local_crate, index, def_path(krate, index, local_crate)));
+    }
+    let enc = Encoding::V2 {
+        funcs: funcs.iter().map(|f| f.2.clone()).collect(),
+        strs,
+    };
+
+    for _ in 0..rdr.leb()? {
+        let (is_local, index, _) = *funcs.get(rdr.leb()? as usize).ok_or("bad function index")?;
+        let mut blocks = BTreeMap::new();
+        if rdr.u8()? != 0 {
+            for bb in 0..rdr.leb()? {
+                let kind = rdr.u8()?;
+                let desc = decode_edge(rdr, &enc, kind, &|rdr: &mut Reader| callee(rdr, &enc))?;
+                blocks.insert(bb as u32, desc);
+            }
+        }
+        let mut extra = Vec::new();
+        match rdr.u8()? {
+            GENERATOR_INFO_NONE => {}
+            GENERATOR_INFO_FULL => {
+                extra.push(decode_generator_record(rdr, &enc, GENERATOR_LAYOUT)?);
+                extra.push(decode_generator_record(rdr, &enc, GENERATOR_STATES)?);
+            }
+            GENERATOR_INFO_LAYOUT => {
+                extra.push(decode_generator_record(rdr, &enc, GENERATOR_LAYOUT)?);
+            }
+            kind => return Err(format!("unknown generator info kind {}", kind)),
+        }
+        if is_local {
+            dump.funcs.insert(index, blocks);
+            dump.extra.insert(index, extra);
+        }
+    }
+    Ok(())
+}
+
+fn callee(rdr: &mut Reader, enc: &Encoding) -> Result<String, String> {
+    match *enc {
+        Encoding::V2 { ref funcs, .. } => {
+            funcs.get(rdr.leb()? as usize).cloned().ok_or("bad function index".to_owned())
+        }
+        Encoding::V1 { .. } => unreachable!(),
+    }
+}
+
+fn int(rdr: &mut Reader, enc: &Encoding) -> Result<u32, String> {
+    match *enc {
+        Encoding::V1 { .. } => rdr.u32(),
+        Encoding::V2 { .. } => Ok(rdr.leb()? as u32),
+    }
+}
+
+fn decode_edge(rdr: &mut Reader,
+               enc: &Encoding,
+               kind: u8,
+               callee: &dyn Fn(&mut Reader) -> Result<String, String>)
+               -> Result<String, String> {
+    let pos = rdr.pos;
+    Ok(match kind {
+        GOTO => format!("goto bb{}", int(rdr, enc)?),
+        SWITCHINT => {
+            let n = match *enc {
+                Encoding::V1 { .. } => rdr.u64()?,
+                Encoding::V2 { .. } => rdr.leb()?,
+            };
+            let mut targets = Vec::new();
+            for _ in 0..n {
+                targets.push(format!("bb{}", int(rdr, enc)?));
+            }
+            format!("switch_int [{}]", targets.join(", "))
+        }
+        RESUME => "resume".to_owned(),
+        ABORT => "abort".to_owned(),
+        RETURN => "return".to_owned(),
+        UNREACHABLE => "unreachable".to_owned(),
+        DROP_NO_UNWIND => decode_drop(rdr, enc, "drop", false)?,
+        DROP_WITH_UNWIND => decode_drop(rdr, enc, "drop", true)?,
+        DROP_AND_REPLACE_NO_UNWIND => decode_drop(rdr, enc, "drop_and_replace", false)?,
+        DROP_AND_REPLACE_WITH_UNWIND => decode_drop(rdr, enc, "drop_and_replace", true)?,
+        CALL_NO_CLEANUP => format!("call {}", callee(rdr)?),
+        CALL_WITH_CLEANUP => {
+            let target = callee(rdr)?;
+            format!("call {}, cleanup bb{}", target, int(rdr, enc)?)
+        }
+        CALL_UNKNOWN_NO_CLEANUP => "call ?".to_owned(),
+        CALL_UNKNOWN_WITH_CLEANUP => format!("call ?, cleanup bb{}", int(rdr, enc)?),
+        ASSERT_NO_CLEANUP => format!("assert -> bb{}", int(rdr, enc)?),
+        ASSERT_WITH_CLEANUP => {
+            let (target, cleanup) = (int(rdr, enc)?, int(rdr, enc)?);
+            format!("assert -> bb{}, cleanup bb{}", target, cleanup)
+        }
+        YIELD_NO_DROP => format!("yield -> bb{}", int(rdr, enc)?),
+        YIELD_WITH_DROP => {
+            let (resume, drop) = (int(rdr, enc)?, int(rdr, enc)?);
+            format!("yield -> bb{}, drop bb{}", resume, drop)
+        }
+        GENERATOR_DROP => "generator_drop".to_owned(),
+        FALSE_EDGES => format!("false_edges -> bb{}", int(rdr, enc)?),
+        FALSE_UNWIND => format!("false_unwind -> bb{}", int(rdr, enc)?),
+        _ => return Err(format!("unknown record kind {} at offset {}", kind, pos)),
+    })
+}
+
+fn decode_drop(rdr: &mut Reader, enc: &Encoding, name: &str, with_unwind: bool)
+               -> Result<String, String> {
+    let mut desc = format!("{} -> bb{}", name, int(rdr, enc)?);
+    if with_unwind {
+        write!(desc, ", unwind bb{}", int(rdr, enc)?).unwrap();
+    }
+    if let Encoding::V1 { glue: false } = *enc {
+        return Ok(desc);
+    }
+    write!(desc, ", {}", decode_drop_glue(rdr, enc)?).unwrap();
+    Ok(desc)
+}
+
+/// Decodes the drop glue of a drop record. The glue is printed as the type it
+/// drops, as its symbol name has a hash which changes with the compiler.
+fn decode_drop_glue(rdr: &mut Reader, enc: &Encoding) -> Result<String, String> {
+    match rdr.u8()? {
+        DROP_GLUE_NONE => Ok("glue none".to_owned()),
+        DROP_GLUE_UNRESOLVED => Ok("glue ?".to_owned()),
+        DROP_GLUE_SYMBOL => {
+            let ty = match *enc {
+                Encoding::V1 { .. } => {
+                    let _sym = rdr.v1_str()?;
+                    rdr.v1_str()?
+                }
+                Encoding::V2 { ref strs, .. } => {
+                    let _sym = rdr.leb()?;
+                    strs.get(rdr.leb()? as usize).cloned().ok_or("bad string index")?
+                }
+            };
+            Ok(format!("glue {}", strip_closure_details(&ty)))
+        }
+        kind => Err(format!("unknown drop glue kind {}", kind)),
+    }
+}
+
+/// Closure and generator types are printed with the path and span of their
+/// definition and the types of their captures. Only their kind is kept, e.g.
+/// `[generator]`.
+fn strip_closure_details(ty: &str) -> String {
+    let mut out = String::new();
+    let mut depth = 0;
+    // The bracket depth of the closure or generator being stripped.
+    let mut stripping = None;
+    for c in ty.chars() {
+        match c {
+            '[' => depth += 1,
+            ']' => {
+                if stripping == Some(depth) {
+                    stripping = None;
+                }
+                depth -= 1;
+            }
+            '@' if stripping.is_none() => {
+                let kind = out.rfind('[').map(|i| &out[i + 1..]);
+                if let Some("closure") | Some("generator") | Some("static generator") = kind {
+                    stripping = Some(depth);
+                }
+            }
+            _ => {}
+        }
+        if stripping.is_none() {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn decode_generator_record(rdr: &mut Reader, enc: &Encoding, kind: u8)
+                           -> Result<String, String> {
+    let pairs = |rdr: &mut Reader, fmt: &dyn Fn(u32, u32) -> String| {
+        let n = int(rdr, enc)?;
+        let mut ret = Vec::new();
+        for _ in 0..n {
+            let (a, b) = (int(rdr, enc)?, int(rdr, enc)?);
+            ret.push(fmt(a, b));
+        }
+        Ok::<_, String>(ret.join(", "))
+    };
+    if kind == GENERATOR_LAYOUT {
+        let state_field = int(rdr, enc)?;
+        let saved = pairs(rdr, &|field, local| format!("{} <- _{}", field, local))?;
+        Ok(format!("generator_layout state {}, saved [{}]", state_field, saved))
+    } else {
+        let resumes = pairs(rdr, &|state, bb| format!("{} -> bb{}", state, bb))?;
+        let suspends = pairs(rdr, &|bb, state| format!("bb{} -> {}", bb, state))?;
+        Ok(format!("generator_states resume [{}], suspend [{}]", resumes, suspends))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push_header(buf: &mut Vec<u8>, kind: u8, hash: u64, index: u32, bb: u32) {
+        buf.push(kind);
+        for i in 0..8 {
+            buf.push((hash >> (8 * i)) as u8);
+        }
+        push_u32(buf, index);
+        push_u32(buf, bb);
+    }
+
+    fn push_u32(buf: &mut Vec<u8>, v: u32) {
+        for i in 0..4 {
+            buf.push((v >> (8 * i)) as u8);
+        }
+    }
+
+    const EXPECTED: &str = "fn foo::#3 {\n    bb0: goto bb1\n    bb1: call std::_\n}\n";
+
+    #[test]
+    #[cfg(target_endian = "little")]
+    fn dump_v0() {
+        let mut crates = HashMap::new();
+        crates.insert(1, "foo".to_owned());
+
+        let mut buf = vec![0, 0];
+        push_header(&mut buf, DROP_NO_UNWIND, 1, 3, 0);
+        push_u32(&mut buf, 1);
+        push_header(&mut buf, RETURN, 1, 3, 1);
+        buf.push(SENTINAL);
+
+        assert_eq!(dump_mir_cfg(&buf, &crates, "foo").unwrap(),
+                   "fn foo::#3 {\n    bb0: drop -> bb1\n    bb1: return\n}\n");
+    }
+
+    #[test]
+    #[cfg(target_endian = "little")]
+    fn dump_v1() {
+        let mut crates = HashMap::new();
+        crates.insert(1, "foo".to_owned());
+        crates.insert(2, "std".to_owned());
+
+        let mut buf = vec![1, 0];
+        push_header(&mut buf, CALL_NO_CLEANUP, 1, 3, 1);
+        buf.extend_from_slice(&[2, 0, 0, 0, 0, 0, 0, 0, 9, 0, 0, 0]);
+        push_header(&mut buf, GOTO, 1, 3, 0);
+        buf.extend_from_slice(&[1, 0, 0, 0]);
+        push_header(&mut buf, RETURN, 2, 7, 0);
+        buf.push(SENTINAL);
+
+        assert_eq!(dump_mir_cfg(&buf, &crates, "foo").unwrap(), EXPECTED);
+    }
+
+    // The terminators of these records are lowered before the CFG is
+    // serialised, so they can only be tested here.
+    #[test]
+    #[cfg(target_endian = "little")]
+    fn dump_lowered_terminators() {
+        let mut crates = HashMap::new();
+        crates.insert(1, "foo".to_owned());
+
+        // Kind, block operands and drop glue kind.
+        let records: &[(u8, &[u32], Option<u8>)] = &[
+            (DROP_AND_REPLACE_NO_UNWIND, &[1], Some(DROP_GLUE_NONE)),
+            (DROP_AND_REPLACE_WITH_UNWIND, &[2, 7], Some(DROP_GLUE_UNRESOLVED)),
+            (YIELD_NO_DROP, &[3], None),
+            (YIELD_WITH_DROP, &[4, 6], None),
+            (GENERATOR_DROP, &[], None),
+            (FALSE_EDGES, &[6], None),
+            (FALSE_UNWIND, &[7], None),
+        ];
+        let mut buf = vec![1, 0];
+        for (bb, &(kind, operands, glue)) in records.iter().enumerate() {
+            push_header(&mut buf, kind, 1, 3, bb as u32);
+            for op in operands {
+                push_u32(&mut buf, *op);
+            }
+            buf.extend(glue);
+        }
+        buf.push(SENTINAL);
+
+        let expected = [
+            "fn foo::#3 {",
+            "    bb0: drop_and_replace -> bb1, glue none",
+            "    bb1: drop_and_replace -> bb2, unwind bb7, glue ?",
+            "    bb2: yield -> bb3",
+            "    bb3: yield -> bb4, drop bb6",
+            "    bb4: generator_drop",
+            "    bb5: false_edges -> bb6",
+            "    bb6: false_unwind -> bb7",
+            "}",
+            "",
+        ].join("\n");
+        assert_eq!(dump_mir_cfg(&buf, &crates, "foo").unwrap(), expected);
+    }
+
+    // A generator whose state switch wasn't recognised has only its layout
+    // recorded.
+    #[test]
+    #[cfg(target_endian = "little")]
+    fn dump_v1_generator_layout_only() {
+        let mut crates = HashMap::new();
+        crates.insert(1, "foo".to_owned());
+
+        let mut buf = vec![1, 0];
+        push_header(&mut buf, RETURN, 1, 3, 0);
+        push_header(&mut buf, GENERATOR_LAYOUT, 1, 3, 0);
+        for v in &[0, 1, 1, 2] {
+            push_u32(&mut buf, *v);
+        }
+        buf.push(SENTINAL);
+
+        assert_eq!(dump_mir_cfg(&buf, &crates, "foo").unwrap(),
+                   "fn foo::#3 {\n    bb0: return\n    \
+                    generator_layout state 0, saved [1 <- _2]\n}\n");
+    }
+
+    /// The tables and groups of a version 2 section matching `EXPECTED`.
+    fn v2_body() -> Vec<u8> {
+        let mut buf = Vec::new();
+        // Crates.
+        buf.push(2);
+        buf.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0, 3]);
+        buf.extend_from_slice(b"foo");
+        buf.extend_from_slice(&[2, 0, 0, 0, 0, 0, 0, 0, 3]);
+        buf.extend_from_slice(b"std");
+        // Strings.
+        buf.push(0);
+        // Functions: foo::#3, std::#7, std::#9.
+        buf.extend_from_slice(&[3, 0, 3, 1, 7, 1, 9]);
+        // Groups.
+        buf.push(2);
+        buf.extend_from_slice(&[0, 1, 2, GOTO, 1, CALL_NO_CLEANUP, 2, 0]);
+        buf.extend_from_slice(&[1, 1, 1, RETURN, 0]);
+        buf
+    }
+
+    #[test]
+    #[cfg(target_endian = "little")]
+    fn dump_v2() {
+        let mut buf = vec![2, 0, 0];
+        buf.extend(v2_body());
+        assert_eq!(dump_mir_cfg(&buf, &HashMap::new(), "foo").unwrap(), EXPECTED);
+    }
+
+    #[test]
+    #[cfg(target_endian = "little")]
+    fn dump_v2_compressed() {
+        use flate2::write::DeflateEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+
+        let mut enc = DeflateEncoder::new(vec![2, 0, FLAG_DEFLATE], Compression::best());
+        enc.write_all(&v2_body()).unwrap();
+        let buf = enc.finish().unwrap();
+        assert_eq!(dump_mir_cfg(&buf, &HashMap::new(), "foo").unwrap(), EXPECTED);
+    }
+
+    #[test]
+    #[cfg(target_endian = "little")]
+    fn dump_v2_drop_glue() {
+        let sym = b"_ZN4core3ptr13drop_in_place17h0123456789abcdefE";
+        let mut buf = vec![2, 0, 0];
+        // Crates: foo.
+        buf.extend_from_slice(&[1, 1, 0, 0, 0, 0, 0, 0, 0, 3]);
+        buf.extend_from_slice(b"foo");
+        // Strings: the glue's symbol and type.
+        buf.extend_from_slice(&[2, sym.len() as u8]);
+        buf.extend_from_slice(sym);
+        buf.extend_from_slice(&[1, b'D']);
+        // Functions: foo::#3.
+        buf.extend_from_slice(&[1, 0, 3]);
+        // One group with a drop and a return.
+        buf.extend_from_slice(&[1, 0, 1, 2, DROP_NO_UNWIND, 1, DROP_GLUE_SYMBOL, 0, 1, RETURN,
+                                GENERATOR_INFO_NONE]);
+
+        assert_eq!(dump_mir_cfg(&buf, &HashMap::new(), "foo").unwrap(),
+                   "fn foo::#3 {\n    bb0: drop -> bb1, glue D\n    bb1: return\n}\n");
+    }
+
+    #[test]
+    #[cfg(target_endian = "little")]
+    fn dump_v2_generator_layout_only() {
+        let mut buf = vec![2, 0, 0];
+        // Crates, strings and functions: foo::#3.
+        buf.extend_from_slice(&[1, 1, 0, 0, 0, 0, 0, 0, 0, 3]);
+        buf.extend_from_slice(b"foo");
+        buf.extend_from_slice(&[0, 1, 0, 3]);
+        // One group, with state field 0 and one saved local.
+        buf.extend_from_slice(&[1, 0, 1, 1, RETURN, GENERATOR_INFO_LAYOUT, 0, 1, 1, 2]);
+
+        assert_eq!(dump_mir_cfg(&buf, &HashMap::new(), "foo").unwrap(),
+                   "fn foo::#3 {\n    bb0: return\n    \
+                    generator_layout state 0, saved [1 <- _2]\n}\n");
+    }
+
+    #[test]
+    fn crate_hashes() {
+        let map = parse_crate_hashes("warning: x\n0x00000000000000ff -> foo\n");
+        assert_eq!(map.get(&0xff).map(|s| &s[..]), Some("foo"));
+    }
+
+    #[test]
+    #[cfg(target_endian = "little")]
+    fn drop_glue_prints_type() {
+        let mut buf = vec![DROP_GLUE_SYMBOL];
+        for s in &["_ZN4core3ptr13drop_in_place17h0123456789abcdefE", "D"] {
+            push_u32(&mut buf, s.len() as u32);
+            buf.extend_from_slice(s.as_bytes());
+        }
+        let mut rdr = Reader { data: &buf, pos: 0 };
+        assert_eq!(decode_drop_glue(&mut rdr, &Encoding::V1 { glue: true }).unwrap(),
+                   "glue D");
+    }
+
+    #[test]
+    fn closure_details_stripped() {
+        assert_eq!(strip_closure_details("std::option::Option<[closure@$DIR/a.rs:1:9: 1:20 \
+                                          x:&[u8]]>"),
+                   "std::option::Option<[closure]>");
+        assert_eq!(strip_closure_details("[generator@$DIR/a.rs:2:5: 4:6 {u8, ()}]"),
+                   "[generator]");
+        assert_eq!(strip_closure_details("&[u8]"), "&[u8]");
+    }
+}